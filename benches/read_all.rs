@@ -0,0 +1,235 @@
+//! Compares the slice based `Bytes` API with the compatibility shims it replaced and with the
+//! encoding it replaced, measures `Database::read_all` against reading the same records the way
+//! the database did before, and compares full reads with projections.
+//!
+//! Run with `cargo bench --bench read_all`.
+
+use database::{Database, Bytes, Store, Projection};
+use std::fs;
+use std::time::{Duration, Instant};
+
+const RECORDS: u32 = 2_000;
+const ROUNDS: u32 = 20;
+
+#[derive(Bytes, Store)]
+#[projection(PersonName(name, age))]
+struct Person {
+    #[id] id: u32,
+    name: String,
+    email: String,
+    age: u16,
+    tags: Vec<String>,
+    scores: Vec<u32>,
+}
+
+impl Person {
+    fn new(id: u32) -> Person {
+        Person {
+            id,
+            name: format!("Person {}", id),
+            email: format!("person{}@example.com", id),
+            age: (id % 100) as u16,
+            tags: (0..4).map(|i| format!("tag {}", i)).collect(),
+            scores: (0..16).collect(),
+        }
+    }
+}
+
+/// The `Bytes` API before `serialize_into` and `deserialize_from`, as the derive generated it:
+/// every value returns a `Vec` of its own, strings end with a NUL byte, and records are reversed
+/// and decoded by popping bytes off the back.
+mod baseline {
+    pub trait Bytes: Sized {
+        fn serialize(&self) -> Vec<u8>;
+        fn deserialize(bytes: &mut Vec<u8>) -> Option<Self>;
+    }
+
+    macro_rules! integers {
+        ($($t:ty),+) => {
+            $(
+                impl Bytes for $t {
+                    fn serialize(&self) -> Vec<u8> {
+                        let mut bytes = Vec::new();
+                        bytes.extend_from_slice(&self.to_le_bytes()[..]);
+                        bytes
+                    }
+
+                    fn deserialize(bytes: &mut Vec<u8>) -> Option<$t> {
+                        let mut own = [0; std::mem::size_of::<$t>()];
+                        for byte in own.iter_mut() {
+                            *byte = bytes.pop()?;
+                        }
+                        Some(<$t>::from_le_bytes(own))
+                    }
+                }
+            )*
+        }
+    }
+
+    integers!(u16, u32, u64);
+
+    impl Bytes for String {
+        fn serialize(&self) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(format!("{}\0", self).as_bytes());
+            bytes
+        }
+
+        fn deserialize(bytes: &mut Vec<u8>) -> Option<String> {
+            let mut own = Vec::new();
+            while let Some(byte) = bytes.pop() {
+                if byte == b'\0' {
+                    break;
+                }
+                own.push(byte);
+            }
+            String::from_utf8(own).ok()
+        }
+    }
+
+    impl<T: Bytes> Bytes for Vec<T> {
+        fn serialize(&self) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            bytes.append(&mut (self.len() as u64).serialize());
+            for element in self {
+                bytes.append(&mut element.serialize());
+            }
+            bytes
+        }
+
+        fn deserialize(bytes: &mut Vec<u8>) -> Option<Vec<T>> {
+            let mut output = Vec::new();
+            for _ in 0..u64::deserialize(bytes)? {
+                output.push(T::deserialize(bytes)?);
+            }
+            Some(output)
+        }
+    }
+
+    impl Bytes for super::Person {
+        fn serialize(&self) -> Vec<u8> {
+            let mut bytes = Vec::new();
+            bytes.append(&mut 0u64.serialize());
+            bytes.append(&mut self.id.serialize());
+            bytes.append(&mut self.name.serialize());
+            bytes.append(&mut self.email.serialize());
+            bytes.append(&mut self.age.serialize());
+            bytes.append(&mut self.tags.serialize());
+            bytes.append(&mut self.scores.serialize());
+            bytes
+        }
+
+        fn deserialize(bytes: &mut Vec<u8>) -> Option<super::Person> {
+            bytes.reverse();
+            u64::deserialize(bytes)?;
+            Some(super::Person {
+                id: Bytes::deserialize(bytes)?,
+                name: Bytes::deserialize(bytes)?,
+                email: Bytes::deserialize(bytes)?,
+                age: Bytes::deserialize(bytes)?,
+                tags: Bytes::deserialize(bytes)?,
+                scores: Bytes::deserialize(bytes)?,
+            })
+        }
+    }
+}
+
+fn time<F: FnMut()>(name: &str, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    let elapsed = start.elapsed() / ROUNDS;
+    println!("{:<40} {:>10.3} ms", name, elapsed.as_secs_f64() * 1000.0);
+    elapsed
+}
+
+fn main() {
+    let persons = (0..RECORDS).map(Person::new).collect::<Vec<_>>();
+    let encoded = persons.iter().map(Bytes::serialize).collect::<Vec<_>>();
+
+    println!("{} records, average of {} rounds", RECORDS, ROUNDS);
+
+    let baseline = time("serialize (baseline)", || {
+        for person in &persons {
+            std::hint::black_box(baseline::Bytes::serialize(person));
+        }
+    });
+    let old = time("serialize", || {
+        for person in &persons {
+            std::hint::black_box(person.serialize());
+        }
+    });
+    let mut buffer = Vec::new();
+    let new = time("serialize_into (reused buffer)", || {
+        for person in &persons {
+            buffer.clear();
+            person.serialize_into(&mut buffer);
+            std::hint::black_box(&buffer);
+        }
+    });
+    println!("{:<40} {:>10.2}x", "speedup", old.as_secs_f64() / new.as_secs_f64());
+    println!("{:<40} {:>10.2}x", "speedup over baseline", baseline.as_secs_f64() / new.as_secs_f64());
+
+    let baseline_encoded = persons.iter().map(baseline::Bytes::serialize).collect::<Vec<_>>();
+    let baseline = time("deserialize (baseline)", || {
+        for bytes in &baseline_encoded {
+            let person: Person = baseline::Bytes::deserialize(&mut bytes.clone()).unwrap();
+            std::hint::black_box(person);
+        }
+    });
+    let old = time("deserialize (reversed Vec shim)", || {
+        for bytes in &encoded {
+            let mut reversed = bytes.clone();
+            reversed.reverse();
+            std::hint::black_box(Person::deserialize(&mut reversed).unwrap());
+        }
+    });
+    let new = time("deserialize_from (slice)", || {
+        for bytes in &encoded {
+            std::hint::black_box(Person::deserialize_from(&mut &bytes[..]).unwrap());
+        }
+    });
+    println!("{:<40} {:>10.2}x", "speedup", old.as_secs_f64() / new.as_secs_f64());
+    println!("{:<40} {:>10.2}x", "speedup over baseline", baseline.as_secs_f64() / new.as_secs_f64());
+    let projected = time("project_from (name and age)", || {
+        for bytes in &encoded {
+            let person = PersonName::project_from(&mut &bytes[..]).unwrap();
+            std::hint::black_box((person.name, person.age));
+        }
+    });
+    println!("{:<40} {:>10.2}x", "speedup over deserialize_from", new.as_secs_f64() / projected.as_secs_f64());
+
+    // Entry files as the database wrote them before, read like its `read_all` did. `Database::read_all`
+    // also takes the read lock of every entry and verifies its checksum, which the baseline didn't.
+    let directory = std::path::Path::new("data/bench-read-all-baseline/persons");
+    fs::create_dir_all(directory).unwrap();
+    for (person, bytes) in persons.iter().zip(&baseline_encoded) {
+        fs::write(directory.join(person.id.to_string()), bytes).unwrap();
+    }
+    let baseline = time("read_all (baseline)", || {
+        let mut persons = Vec::new();
+        for path in fs::read_dir(directory).unwrap() {
+            let mut bytes = fs::read(path.unwrap().path()).unwrap();
+            let person: Person = baseline::Bytes::deserialize(&mut bytes).unwrap();
+            persons.push(person);
+        }
+        std::hint::black_box(persons);
+    });
+    fs::remove_dir_all("data/bench-read-all-baseline").unwrap();
+
+    let database = Database::new("data/bench-read-all");
+    database.delete_all::<Person>().unwrap();
+    for person in &persons {
+        database.create(person).unwrap();
+    }
+    let read_all = time("Database::read_all", || {
+        std::hint::black_box(database.read_all::<Person>().unwrap());
+    });
+    println!("{:<40} {:>10.2}x", "speedup over baseline", baseline.as_secs_f64() / read_all.as_secs_f64());
+    time("Database::read_all_fields", || {
+        std::hint::black_box(database.read_all_fields::<PersonName>().unwrap());
+    });
+    database.delete_all::<Person>().unwrap();
+}
@@ -2,119 +2,1091 @@ extern crate proc_macro;
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn;
-use syn::{Data, Fields, Type, Meta, NestedMeta};
+use syn::{Data, DataEnum, Fields, Meta, NestedMeta, Lit, Attribute, Expr, Token, Path, Member, Index, Generics, GenericParam, Type, Ident, Error};
+use syn::parse::{Parse, ParseStream};
 
-#[proc_macro_derive(Bytes, attributes(from))]
+/// Derives `Bytes` for a struct or an enum.
+/// Every type parameter is required to implement `Bytes` too.
+///
+/// Fields marked with `#[default]` or `#[default = expr]` may be missing from older
+/// records and are filled in with `Default::default()` or `expr` when decoding them.
+/// `#[default]` fields are taken to have been added in the order they are declared in, so a record
+/// may only lack the last of them: with `#[default] a` declared before `#[default] b`, records without
+/// `b` are read, but records with `b` and without `a` aren't. Declare fields added later below those
+/// added earlier, or convert older records with `#[from(...)]`.
+/// Fields marked with `#[skip]` aren't stored at all and are filled in the same way.
+///
+/// Enum variants are stored as their index in declaration order, followed by their fields.
+/// Adding, removing or reordering variants changes the signature, so older records
+/// have to be migrated using `#[from(...)]`.
+///
+/// Fields marked with `#[varint]` are stored in the compact encoding of `Compact`, which stores
+/// integers and lengths as varints. Marking the type with `#[varint]` applies it to every field.
+/// Derived types implement `Compact` by their regular encoding, so they can be nested in such fields.
+///
+/// Derived types implement `View` by decoding an owned value. Marking a struct with `#[view(Name)]`
+/// generates a struct `Name<'a>` instead, with a field holding the view of every stored field, like
+/// `&'a str` for a `String`. Use `#[view(Name, derive(Debug, ...))]` to derive traits for it.
+/// Views only decode records in the current layout, and fields marked with `#[varint]` are decoded as owned values.
+///
+/// `#[projection(Name(field, ...))]` generates a struct `Name` holding copies of the listed fields,
+/// which implements `Projection` by decoding only those fields and skipping over the others.
+/// It may be given more than once and takes `derive(...)` like `#[view(...)]`.
+///
+/// Derived types implement `Bytes::layout` by the layouts of their stored fields, so the database
+/// can record how they are encoded and decode them without the type. `Bytes::hashes` lists the
+/// hashes of every older layout they read, including those of the types in `#[from(...)]`.
+///
+/// Derived types implement `Bytes::to_value` by the values of their stored fields, as a `Value::Struct`
+/// for structs and a `Value::Variant` for enums.
+///
+/// The generated code refers to the `database` crate by `::database`.
+/// Use `#[database(crate = "path::to::database")]` if it is available under another path.
+#[proc_macro_derive(Bytes, attributes(from, default, skip, varint, view, projection, database))]
 pub fn bytes_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
     impl_serialize(&ast)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
 }
 
-fn impl_serialize(ast: &syn::DeriveInput) -> TokenStream {
+/// The value of a `#[default = expr]` attribute.
+struct DefaultValue(Expr);
 
-    let nested = 
-        if let Some(Meta::List(meta_list)) = 
-            if let Some(some) = &ast.attrs
-                .clone()
-                .into_iter()
-                .filter(|attr| attr.path.is_ident("from"))
-                .nth(0)
-            {
-                Some(some
-                    .parse_meta()
-                    .unwrap())
-            } else {
-                None
+impl Parse for DefaultValue {
+    fn parse(input: ParseStream) -> syn::Result<DefaultValue> {
+        input.parse::<Token![=]>()?;
+        Ok(DefaultValue(input.parse()?))
+    }
+}
+
+/// Returns the expression used to fill in a field that isn't stored.
+/// A plain `#[default]` or `#[skip]` falls back to `Default::default()`.
+fn default_value(attrs: &[Attribute]) -> syn::Result<proc_macro2::TokenStream> {
+    match attrs.iter().find(|attr| attr.path.is_ident("default")) {
+        Some(attr) if !attr.tokens.is_empty() => {
+            let DefaultValue(expr) = syn::parse2(attr.tokens.clone())
+                .map_err(|_| Error::new_spanned(attr, "expected `#[default]` or `#[default = expr]`"))?;
+            Ok(quote! { #expr })
+        },
+        _ => Ok(quote! { ::std::default::Default::default() })
+    }
+}
+
+/// Rejects field types that can't be decoded into an owned value.
+fn check_type(ty: &Type) -> syn::Result<()> {
+    match ty {
+        Type::Reference(_) | Type::Ptr(_) | Type::BareFn(_) | Type::TraitObject(_)
+            | Type::ImplTrait(_) | Type::Infer(_) | Type::Never(_) => Err(Error::new_spanned(
+                ty,
+                "unsupported field type, stored fields have to be owned values implementing `Bytes`"
+            )),
+        Type::Array(array) => check_type(&array.elem),
+        Type::Slice(slice) => check_type(&slice.elem),
+        Type::Paren(paren) => check_type(&paren.elem),
+        Type::Group(group) => check_type(&group.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().try_for_each(check_type),
+        _ => Ok(())
+    }
+}
+
+fn has_attr(attrs: &[Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(name))
+}
+
+/// Returns whether `#[varint]` is present.
+fn varint_attr(attrs: &[Attribute]) -> syn::Result<bool> {
+    match attrs.iter().find(|attr| attr.path.is_ident("varint")) {
+        Some(attr) if !attr.tokens.is_empty() => Err(Error::new_spanned(attr, "`#[varint]` doesn't take any arguments")),
+        Some(_) => Ok(true),
+        None => Ok(false)
+    }
+}
+
+/// Returns the statement that appends `value`, a reference to a field of type `ty`, to `bytes`.
+fn serialize_field(value: proc_macro2::TokenStream, ty: &Type, varint: bool, krate: &Path) -> proc_macro2::TokenStream {
+    if varint {
+        quote! { <#ty as #krate::Compact>::serialize_compact_into(#value, bytes); }
+    } else {
+        quote! { <#ty as #krate::Bytes>::serialize_into(#value, bytes); }
+    }
+}
+
+/// Returns the expression that deserializes a field of type `ty` from `bytes`.
+fn deserialize_field(ty: &Type, varint: bool, krate: &Path) -> proc_macro2::TokenStream {
+    if varint {
+        quote! { <#ty as #krate::Compact>::deserialize_compact_from(bytes)? }
+    } else {
+        quote! { <#ty as #krate::Bytes>::deserialize_from(bytes)? }
+    }
+}
+
+/// Returns the statement that advances `bytes` past a field of type `ty`.
+/// The compact encoding has no cheaper way to skip, so such fields are decoded.
+fn skip_field(ty: &Type, varint: bool, krate: &Path) -> proc_macro2::TokenStream {
+    if varint {
+        quote! { <#ty as #krate::Compact>::deserialize_compact_from(bytes)?; }
+    } else {
+        quote! { <#ty as #krate::Bytes>::skip(bytes)?; }
+    }
+}
+
+/// Returns the name and the `Value` of the field `member`, whose value is given by `value`.
+fn field_value(member: &Member, value: proc_macro2::TokenStream, krate: &Path) -> proc_macro2::TokenStream {
+    let name = member_name(member);
+    quote! { (::std::string::String::from(#name), #krate::Bytes::to_value(#value)) }
+}
+
+/// Returns the expression reading the field `name` of type `ty` from the `Value` `source`,
+/// evaluating `missing` if the value lacks the field.
+fn field_from_value(source: &Ident, name: &str, ty: &Type, missing: proc_macro2::TokenStream, krate: &Path) -> proc_macro2::TokenStream {
+    quote! {
+        match #source.field(#name) {
+            ::std::option::Option::Some(field) => <#ty as #krate::Bytes>::from_value(field)?,
+            ::std::option::Option::None => #missing
+        }
+    }
+}
+
+/// Returns the name of the field `member` as used in `Value`s.
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.to_string(),
+        Member::Unnamed(index) => index.index.to_string()
+    }
+}
+
+/// Returns the expression for the signature of a field of type `ty`.
+fn field_signature(ty: &Type, varint: bool, krate: &Path) -> proc_macro2::TokenStream {
+    if varint {
+        quote! { <#ty as #krate::Compact>::compact_signature() }
+    } else {
+        quote! { <#ty as #krate::Bytes>::signature() }
+    }
+}
+
+/// Returns the expression for the name and the layout of a field of type `ty`.
+fn field_layout(member: &Member, ty: &Type, varint: bool, krate: &Path) -> proc_macro2::TokenStream {
+    let name = member_name(member);
+    let layout = if varint {
+        quote! { <#ty as #krate::Compact>::compact_layout() }
+    } else {
+        quote! { <#ty as #krate::Bytes>::layout() }
+    };
+    quote! { (::std::string::String::from(#name), #layout) }
+}
+
+/// Caches the hash of types without type or const parameters, since it is needed for every value
+/// serialized or deserialized. A static inside a generic function would be shared by every
+/// instantiation, so generic types compute it each time.
+fn cached_hash(generics: &Generics, krate: &Path) -> proc_macro2::TokenStream {
+    if generics.params.iter().any(|param| !matches!(param, GenericParam::Lifetime(_))) {
+        return quote! {};
+    }
+    quote! {
+        fn hash() -> u64 {
+            static HASH: ::std::sync::OnceLock<u64> = ::std::sync::OnceLock::new();
+            *HASH.get_or_init(|| {
+                let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+                ::std::hash::Hasher::write(&mut hasher, <Self as #krate::Bytes>::signature().as_bytes());
+                ::std::hash::Hasher::finish(&hasher)
+            })
+        }
+    }
+}
+
+/// Implements `Compact` by the regular encoding, so derived types can be used in `#[varint]` fields.
+fn impl_compact(ast: &syn::DeriveInput, krate: &Path) -> proc_macro2::TokenStream {
+    let name = &ast.ident;
+    let generics = add_bounds(&ast.generics, krate);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #krate::Compact for #name #ty_generics #where_clause {
+            fn serialize_compact_into(&self, bytes: &mut ::std::vec::Vec<u8>) {
+                #krate::Bytes::serialize_into(self, bytes);
             }
-        { Some(meta_list.nested) } else { None };
-    
-    let from = if let Some(nested) = nested {
-        let mut from = Vec::new();
 
-        for nested_meta in nested {
-            if let NestedMeta::Meta(meta) = nested_meta {
-                if let Meta::Path(path) = meta {
-                    from.push(path);
-                } 
+            fn deserialize_compact_from(bytes: &mut &[u8]) -> ::std::result::Result<Self, #krate::Error> {
+                <Self as #krate::Bytes>::deserialize_from(bytes)
+            }
+
+            fn compact_signature() -> ::std::string::String {
+                <Self as #krate::Bytes>::signature()
+            }
+
+            fn compact_layout() -> #krate::Layout {
+                <Self as #krate::Bytes>::layout()
             }
         }
-        from
+    }
+}
+
+/// Returns the name and the derived traits given by `#[view(Name)]` or `#[view(Name, derive(...))]`, if present.
+fn view_attr(attrs: &[Attribute]) -> syn::Result<Option<(Ident, Vec<Path>)>> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("view")) {
+        Some(attr) => attr,
+        None => return Ok(None)
+    };
+    let error = || Error::new_spanned(attr, "expected `#[view(Name)]` or `#[view(Name, derive(Trait, ...))]`");
+    let nested = match attr.parse_meta() {
+        Ok(Meta::List(meta_list)) => meta_list.nested,
+        _ => return Err(error())
+    };
+    let mut nested = nested.iter();
+    let name = match nested.next() {
+        Some(NestedMeta::Meta(Meta::Path(path))) => path.get_ident().cloned().ok_or_else(error)?,
+        _ => return Err(error())
+    };
+    let derives = derive_list(nested.next()).ok_or_else(error)?;
+    if nested.next().is_some() {
+        return Err(error());
+    }
+    Ok(Some((name, derives)))
+}
+
+/// Returns the traits listed in an optional `derive(...)` argument, or `None` if it is malformed.
+fn derive_list(nested: Option<&NestedMeta>) -> Option<Vec<Path>> {
+    match nested {
+        Some(NestedMeta::Meta(Meta::List(meta_list))) if meta_list.path.is_ident("derive") => {
+            meta_list.nested.iter().map(|nested_meta| match nested_meta {
+                NestedMeta::Meta(Meta::Path(path)) => Some(path.clone()),
+                _ => None
+            }).collect()
+        },
+        Some(_) => None,
+        None => Some(Vec::new())
+    }
+}
+
+/// A struct requested by `#[projection(Name(field, ...))]` or `#[projection(Name(field, ...), derive(...))]`.
+struct ProjectionAttr {
+    name: Ident,
+    fields: Vec<Ident>,
+    derives: Vec<Path>
+}
+
+/// Returns every projection requested by `#[projection(...)]`.
+fn projection_attrs(attrs: &[Attribute]) -> syn::Result<Vec<ProjectionAttr>> {
+    let mut projections = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("projection")) {
+        let error = || Error::new_spanned(attr, "expected `#[projection(Name(field, ...))]` or `#[projection(Name(field, ...), derive(Trait, ...))]`");
+        let nested = match attr.parse_meta() {
+            Ok(Meta::List(meta_list)) => meta_list.nested,
+            _ => return Err(error())
+        };
+        let mut nested = nested.iter();
+        let (name, fields) = match nested.next() {
+            Some(NestedMeta::Meta(Meta::List(meta_list))) => {
+                let name = meta_list.path.get_ident().cloned().ok_or_else(error)?;
+                let mut fields = Vec::new();
+                for nested_meta in &meta_list.nested {
+                    let field = match nested_meta {
+                        NestedMeta::Meta(Meta::Path(path)) => path.get_ident().cloned().ok_or_else(error)?,
+                        _ => return Err(error())
+                    };
+                    if fields.contains(&field) {
+                        return Err(Error::new_spanned(field, "field is listed more than once"));
+                    }
+                    fields.push(field);
+                }
+                (name, fields)
+            },
+            _ => return Err(error())
+        };
+        if fields.is_empty() {
+            return Err(Error::new_spanned(name, "a projection requires at least one field"));
+        }
+        let derives = derive_list(nested.next()).ok_or_else(error)?;
+        if nested.next().is_some() {
+            return Err(error());
+        }
+        projections.push(ProjectionAttr { name, fields, derives });
+    }
+    Ok(projections)
+}
+
+/// Generates the struct requested by `#[projection(...)]` and implements `Projection` for it.
+/// Records in the current layout are decoded field by field, skipping the fields that aren't
+/// projected. Older layouts are decoded in full, since only the decoder knows about them.
+fn impl_projection(ast: &syn::DeriveInput, fields: &Fields, projection: &ProjectionAttr, krate: &Path) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &ast.ident;
+    let projection_name = &projection.name;
+    if ast.generics.params.iter().next().is_some() {
+        return Err(Error::new_spanned(&ast.generics, "`#[projection(...)]` isn't supported on generic types"));
+    }
+    if !matches!(fields, Fields::Named(_)) {
+        return Err(Error::new_spanned(projection_name, "`#[projection(...)]` requires a struct with named fields"));
+    }
+    for name in &projection.fields {
+        if !fields.iter().any(|field| field.ident.as_ref() == Some(name)) {
+            return Err(Error::new_spanned(name, format!("`{}` has no field `{}`", struct_name, name)));
+        }
+    }
+
+    let type_varint = varint_attr(&ast.attrs)?;
+    let mut steps = Vec::new();
+    let mut field_definitions = Vec::new();
+    let mut current_fields = Vec::new();
+    let mut source_fields = Vec::new();
+    for name in &projection.fields {
+        let field = fields.iter().find(|field| field.ident.as_ref() == Some(name)).unwrap_or_else(|| unreachable!());
+        let vis = &field.vis;
+        let ty = &field.ty;
+        let local = quote::format_ident!("field_{}", name);
+        field_definitions.push(quote! { #vis #name: #ty });
+        current_fields.push(quote! { #name: #local });
+        source_fields.push(quote! { #name: source.#name });
+    }
+    for field in fields {
+        let name = field.ident.as_ref().unwrap_or_else(|| unreachable!());
+        let projected = projection.fields.contains(name);
+        let local = quote::format_ident!("field_{}", name);
+        if has_attr(&field.attrs, "skip") {
+            if projected {
+                let value = default_value(&field.attrs)?;
+                steps.push(quote! { let #local = #value; });
+            }
+            continue;
+        }
+        let varint = varint_attr(&field.attrs)? || type_varint;
+        steps.push(if projected {
+            let deserialize = deserialize_field(&field.ty, varint, krate);
+            quote! { let #local = #deserialize; }
+        } else {
+            skip_field(&field.ty, varint, krate)
+        });
+    }
+
+    let vis = &ast.vis;
+    let doc = format!("Some of the fields of `{}`, decoded without the others, see `Projection`.", struct_name);
+    let derives = &projection.derives;
+    let derives = if derives.is_empty() {
+        quote! {}
     } else {
-        Vec::new()
+        quote! { #[derive(#(#derives),*)] }
     };
 
+    Ok(quote! {
+        #[doc = #doc]
+        #derives
+        #vis struct #projection_name {
+            #(#field_definitions),*
+        }
+
+        impl #krate::Projection for #projection_name {
+            type Source = #struct_name;
+
+            fn project_from(bytes: &mut &[u8]) -> ::std::result::Result<#projection_name, #krate::Error> {
+                let start: &[u8] = *bytes;
+                if <u64 as #krate::Bytes>::deserialize_from(bytes)? == <#struct_name as #krate::Bytes>::hash() {
+                    #(
+                        #steps
+                    )*
+                    return ::std::result::Result::Ok(#projection_name {
+                        #(#current_fields),*
+                    });
+                }
+                *bytes = start;
+                let source = <#struct_name as #krate::Bytes>::deserialize_from(bytes)?;
+                ::std::result::Result::Ok(#projection_name {
+                    #(#source_fields),*
+                })
+            }
+        }
+    })
+}
+
+/// Implements `View` by decoding an owned value, used unless `#[view(...)]` is present.
+fn impl_owned_view(ast: &syn::DeriveInput, krate: &Path) -> proc_macro2::TokenStream {
+    let name = &ast.ident;
+    let generics = add_bounds(&ast.generics, krate);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #krate::View for #name #ty_generics #where_clause {
+            type Borrowed<'view> = Self;
+
+            fn view_from(bytes: &mut &[u8]) -> ::std::result::Result<Self, #krate::Error> {
+                <Self as #krate::Bytes>::deserialize_from(bytes)
+            }
+        }
+    }
+}
+
+/// Generates the view struct requested by `#[view(...)]` and implements `View` with it.
+fn impl_view_struct(ast: &syn::DeriveInput, fields: &Fields, view_name: &Ident, derives: &[Path], krate: &Path) -> syn::Result<proc_macro2::TokenStream> {
     let struct_name = &ast.ident;
-    let mut field_names = Vec::new();
-    let mut field_types = Vec::new();
-    match *&ast.data {
-        Data::Struct(ref data) => {
-            match data.fields {
-                Fields::Named(ref fields) => {
-                    for field in &fields.named {
-                        if let Some(field_name) = &field.ident {
-                            if let Type::Path(tp) = &field.ty {
-                                field_names.push(field_name); 
-                                field_types.push(&tp.path);
-                            }
-                        }
-                    }
-                },
-                Fields::Unnamed(_) => unimplemented!(),
-                Fields::Unit => unimplemented!()
+    if ast.generics.params.iter().next().is_some() {
+        return Err(Error::new_spanned(&ast.generics, "`#[view(...)]` isn't supported on generic types"));
+    }
+    let type_varint = varint_attr(&ast.attrs)?;
+    let mut field_definitions = Vec::new();
+    let mut field_views = Vec::new();
+    for field in fields {
+        if has_attr(&field.attrs, "skip") {
+            continue;
+        }
+        let vis = &field.vis;
+        let ty = &field.ty;
+        let varint = varint_attr(&field.attrs)? || type_varint;
+        let (view_type, view) = if varint {
+            (quote! { #ty }, quote! { <#ty as #krate::Compact>::deserialize_compact_from(bytes)? })
+        } else {
+            (quote! { <#ty as #krate::View>::Borrowed<'a> }, quote! { <#ty as #krate::View>::view_from(bytes)? })
+        };
+        match &field.ident {
+            Some(field_name) => {
+                field_definitions.push(quote! { #vis #field_name: #view_type });
+                field_views.push(quote! { #field_name: #view });
+            },
+            None => {
+                field_definitions.push(quote! { #vis #view_type });
+                field_views.push(view);
+            }
+        }
+    }
+    if field_definitions.is_empty() {
+        return Err(Error::new_spanned(struct_name, "`#[view(...)]` requires at least one stored field"));
+    }
+
+    let vis = &ast.vis;
+    let doc = format!("A view of `{}` borrowing from the encoded bytes, see `View`.", struct_name);
+    let derives = if derives.is_empty() {
+        quote! {}
+    } else {
+        quote! { #[derive(#(#derives),*)] }
+    };
+    let (definition, construct) = match fields {
+        Fields::Named(_) => (
+            quote! { #vis struct #view_name<'a> { #(#field_definitions),* } },
+            quote! { #view_name { #(#field_views),* } }
+        ),
+        _ => (
+            quote! { #vis struct #view_name<'a>(#(#field_definitions),*); },
+            quote! { #view_name(#(#field_views),*) }
+        )
+    };
+    let view_string = view_name.to_string();
+    let name_string = struct_name.to_string();
+
+    Ok(quote! {
+        #[doc = #doc]
+        #derives
+        #definition
+
+        impl #krate::View for #struct_name {
+            type Borrowed<'a> = #view_name<'a>;
+
+            fn view_from<'a>(bytes: &mut &'a [u8]) -> ::std::result::Result<#view_name<'a>, #krate::Error> {
+                let data_hash = <u64 as #krate::Bytes>::deserialize_from(bytes)?;
+                if data_hash != <Self as #krate::Bytes>::hash() {
+                    return ::std::result::Result::Err(#krate::Error::new(::std::format!(
+                        "{} only reads records in the current layout of {}, use `Database::upgrade_all` to convert older ones.", #view_string, #name_string
+                    )));
+                }
+                ::std::result::Result::Ok(#construct)
             }
+        }
+    })
+}
+
+/// Returns the path of the `database` crate, `::database` unless overridden by
+/// `#[database(crate = "...")]`.
+fn crate_path(attrs: &[Attribute]) -> syn::Result<Path> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("database")) {
+        Some(attr) => attr,
+        None => return Ok(syn::parse_quote!(::database))
+    };
+    if let Meta::List(meta_list) = attr.parse_meta()? {
+        if let (1, Some(NestedMeta::Meta(Meta::NameValue(name_value)))) = (meta_list.nested.len(), meta_list.nested.first()) {
+            if name_value.path.is_ident("crate") {
+                return match &name_value.lit {
+                    Lit::Str(path) => path.parse(),
+                    lit => Err(Error::new_spanned(lit, "expected a string containing a path"))
+                };
+            }
+        }
+    }
+    Err(Error::new_spanned(attr, "expected `#[database(crate = \"...\")]`"))
+}
+
+/// Returns the types listed in `#[from(...)]`.
+fn from_types(attrs: &[Attribute]) -> syn::Result<Vec<Path>> {
+    let mut from = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("from")) {
+        let nested = match attr.parse_meta()? {
+            Meta::List(meta_list) => meta_list.nested,
+            meta => return Err(Error::new_spanned(meta, "expected `#[from(Type, ...)]`"))
+        };
+        if nested.is_empty() {
+            return Err(Error::new_spanned(attr, "expected at least one type in `#[from(...)]`"));
+        }
+        for nested_meta in nested {
+            match nested_meta {
+                NestedMeta::Meta(Meta::Path(path)) => from.push(path),
+                nested_meta => return Err(Error::new_spanned(nested_meta, "expected a type"))
+            }
+        }
+    }
+    Ok(from)
+}
+
+fn impl_serialize(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let from = from_types(&ast.attrs)?;
+    let krate = crate_path(&ast.attrs)?;
+
+    let view = view_attr(&ast.attrs)?;
+    let projections = projection_attrs(&ast.attrs)?;
+
+    let (bytes, view, projections) = match ast.data {
+        Data::Struct(ref data) => (
+            impl_struct(ast, &data.fields, &from, &krate)?,
+            match view {
+                Some((view_name, derives)) => impl_view_struct(ast, &data.fields, &view_name, &derives, &krate)?,
+                None => impl_owned_view(ast, &krate)
+            },
+            projections.iter()
+                .map(|projection| impl_projection(ast, &data.fields, projection, &krate))
+                .collect::<syn::Result<Vec<_>>>()?
+        ),
+        Data::Enum(ref data) => {
+            if let Some((view_name, _)) = view {
+                return Err(Error::new_spanned(view_name, "`#[view(...)]` is only supported on structs"));
+            }
+            if let Some(projection) = projections.first() {
+                return Err(Error::new_spanned(&projection.name, "`#[projection(...)]` is only supported on structs"));
+            }
+            (impl_enum(ast, data, &from, &krate)?, impl_owned_view(ast, &krate), Vec::new())
         },
-        Data::Enum(_) | Data::Union(_) => unimplemented!()
+        Data::Union(ref data) => return Err(Error::new_spanned(
+            data.union_token,
+            "`Bytes` can't be derived for unions"
+        ))
     };
+    let compact = impl_compact(ast, &krate);
+    Ok(quote! {
+        #bytes
+        #compact
+        #view
+        #(#projections)*
+    })
+}
 
+/// Returns the statement that remembers where the value starts, needed by `from_fallback`.
+fn remember_start(from: &[Path]) -> proc_macro2::TokenStream {
+    if from.is_empty() {
+        quote! {}
+    } else {
+        quote! { let start: &[u8] = *bytes; }
+    }
+}
 
-    let gen = quote! {
-        impl Bytes for #struct_name {
-            fn serialize(&self) -> Vec<u8> {
-                let mut bytes = Vec::new();
+/// Tries to decode a record of an older type listed in `#[from(...)]` and converts it.
+/// Each attempt starts over at the hash of the value, so a failed attempt doesn't
+/// consume bytes that belong to the next one, or to the enclosing value if nested.
+fn from_fallback(from: &[Path], krate: &Path) -> proc_macro2::TokenStream {
+    quote! {
+        #(
+            let mut old_bytes = start;
+            if let ::std::result::Result::Ok(old) = <#from as #krate::Bytes>::deserialize_from(&mut old_bytes) {
+                *bytes = old_bytes;
+                return ::std::result::Result::Ok(::std::convert::From::from(old));
+            }
+        )*
+    }
+}
 
-                bytes.append(&mut #struct_name::hash().serialize());
+/// Generates `Bytes::hashes`, listing the current hash, the hashes of the layouts before
+/// `#[default]` fields were added and the hashes every type in `#[from(...)]` reads.
+fn readable_hashes(legacy_hashes: &[proc_macro2::TokenStream], from: &[Path], krate: &Path) -> proc_macro2::TokenStream {
+    quote! {
+        fn hashes() -> ::std::vec::Vec<u64> {
+            let mut hashes = ::std::vec![<Self as #krate::Bytes>::hash()];
+            #(
+                hashes.push(#legacy_hashes);
+            )*
+            #(
+                hashes.extend(<#from as #krate::Bytes>::hashes());
+            )*
+            hashes
+        }
+    }
+}
 
+/// Requires every type parameter to implement `Bytes`.
+fn add_bounds(generics: &Generics, krate: &Path) -> Generics {
+    let mut generics = generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            type_param.bounds.push(syn::parse_quote!(#krate::Bytes));
+        }
+    }
+    generics
+}
+
+fn impl_struct(ast: &syn::DeriveInput, fields: &Fields, from: &[Path], krate: &Path) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &ast.ident;
+    let generics = add_bounds(&ast.generics, krate);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let type_varint = varint_attr(&ast.attrs)?;
+    // How the fields that are written to and read from storage are encoded.
+    let mut field_serializers = Vec::new();
+    let mut field_skips = Vec::new();
+    let mut field_size_hints = Vec::new();
+    let mut field_signatures = Vec::new();
+    let mut field_values = Vec::new();
+    let mut field_layouts = Vec::new();
+    // Stored fields marked with `#[default]`, in declaration order.
+    let mut default_names = Vec::new();
+    // Every field of the struct with its type if it is stored, and the value
+    // used in its place if it isn't.
+    let mut all_fields = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let field_name = match &field.ident {
+            Some(field_name) => Member::Named(field_name.clone()),
+            None => Member::Unnamed(Index::from(index))
+        };
+        let value = default_value(&field.attrs)?;
+        let varint = varint_attr(&field.attrs)? || type_varint;
+        if has_attr(&field.attrs, "skip") {
+            all_fields.push((field_name, None, value));
+        } else {
+            check_type(&field.ty)?;
+            if has_attr(&field.attrs, "default") {
+                default_names.push(field_name.clone());
+            }
+            field_serializers.push(serialize_field(quote! { &self.#field_name }, &field.ty, varint, krate));
+            field_skips.push(skip_field(&field.ty, varint, krate));
+            let field_type = &field.ty;
+            field_size_hints.push(quote! { <#field_type as #krate::Bytes>::size_hint(&self.#field_name) });
+            field_signatures.push(field_signature(&field.ty, varint, krate));
+            field_values.push(field_value(&field_name, quote! { &self.#field_name }, krate));
+            field_layouts.push(field_layout(&field_name, &field.ty, varint, krate));
+            all_fields.push((field_name, Some((&field.ty, varint)), value));
+        }
+    }
+
+    // Builds the struct from the stored fields, filling in every field in `missing`.
+    let construct = |missing: &[Member]| {
+        let mut signatures = Vec::new();
+        let mut fields = Vec::new();
+        for (field_name, stored, value) in &all_fields {
+            fields.push(match stored {
+                Some((field_type, varint)) if !missing.contains(field_name) => {
+                    signatures.push(field_signature(field_type, *varint, krate));
+                    let deserialize = deserialize_field(field_type, *varint, krate);
+                    quote! { #field_name: #deserialize }
+                },
+                _ => quote! { #field_name: #value }
+            });
+        }
+        (signatures, fields)
+    };
+
+    let (_, current_fields) = construct(&[]);
+
+    let struct_name_string = struct_name.to_string();
+    let source = Ident::new("value", proc_macro2::Span::call_site());
+    let mut field_names = Vec::new();
+    let mut value_fields = Vec::new();
+    for (field_name, stored, value) in &all_fields {
+        value_fields.push(match stored {
+            Some((field_type, _)) => {
+                let name = member_name(field_name);
+                let missing = if default_names.contains(field_name) {
+                    value.clone()
+                } else {
+                    quote! {
+                        return ::std::result::Result::Err(#krate::Error::new(::std::format!("Missing field {} of {}.", #name, #struct_name_string)))
+                    }
+                };
+                let from_value = field_from_value(&source, &name, field_type, missing, krate);
+                field_names.push(name);
+                quote! { #field_name: #from_value }
+            },
+            None => quote! { #field_name: #value }
+        });
+    }
+
+    // Records written before `#[default]` fields were added lack those fields.
+    // Default fields are assumed to have been added in declaration order, so older
+    // layouts are obtained by dropping them from the back one at a time.
+    let mut legacy = Vec::new();
+    let mut legacy_hashes = Vec::new();
+    for dropped in 1..=default_names.len() {
+        let (legacy_signatures, legacy_fields) = construct(&default_names[default_names.len() - dropped..]);
+        let legacy_hash = quote! {
+            {
+                let mut hasher = ::std::collections::hash_map::DefaultHasher::new();
+                ::std::hash::Hasher::write(&mut hasher, {
+                    let mut output = ::std::string::String::new();
+                    #(
+                        output.push_str(&#legacy_signatures);
+                    )*
+                    output
+                }.as_bytes());
+                ::std::hash::Hasher::finish(&hasher)
+            }
+        };
+        legacy.push(quote! {
+            if data_hash == #legacy_hash {
+                return ::std::result::Result::Ok(Self {
+                    #(
+                        #legacy_fields,
+                    )*
+                });
+            }
+        });
+        legacy_hashes.push(legacy_hash);
+    }
+
+    // Structs without stored fields don't read the value.
+    let read_value = if field_names.is_empty() {
+        quote! { let _ = value; }
+    } else {
+        quote! { let value = #krate::structured(value)?; }
+    };
+
+    let start = remember_start(from);
+    let hashes = readable_hashes(&legacy_hashes, from, krate);
+    let from = from_fallback(from, krate);
+    let hash = cached_hash(&ast.generics, krate);
+    let name_string = struct_name.to_string();
+
+    let gen = quote! {
+        impl #impl_generics #krate::Bytes for #struct_name #ty_generics #where_clause {
+            fn serialize_into(&self, bytes: &mut ::std::vec::Vec<u8>) {
+                #krate::Bytes::serialize_into(&<Self as #krate::Bytes>::hash(), bytes);
                 #(
-                    bytes.append(&mut self.#field_names.serialize());
+                    #field_serializers
                 )*
-                bytes
             }
 
-            fn deserialize(mut bytes: &mut Vec<u8>) -> Result<#struct_name, crate::Error> {
-                bytes.reverse();
-
-                let data_hash = u64::deserialize(&mut bytes)?;
-                let mut hash_bytes = data_hash.serialize();
-                hash_bytes.reverse();
-                let mut hasher = std::collections::hash_map::DefaultHasher::new();
-                std::hash::Hasher::write(&mut hasher, #struct_name::signature().as_bytes());
+            fn deserialize_from(bytes: &mut &[u8]) -> ::std::result::Result<Self, #krate::Error> {
+                #start
+                let data_hash = <u64 as #krate::Bytes>::deserialize_from(bytes)?;
 
-                if data_hash ==  #struct_name::hash() {
-                    return Ok(#struct_name {
+                if data_hash == <Self as #krate::Bytes>::hash() {
+                    return ::std::result::Result::Ok(Self {
                         #(
-                            #field_names: #field_types::deserialize(&mut bytes)?,
+                            #current_fields,
                         )*
                     });
                 }
-                #(  
-                    bytes.append(&mut hash_bytes.clone());
-                    bytes.reverse();
-                    if let Ok(old) = #from::deserialize(&mut bytes) {
-                        return Ok(#struct_name::from(old));
-                    }
+                #(
+                    #legacy
                 )*
-                Err(crate::Error::new(format!("Hash not matching.")))
+                #from
+                ::std::result::Result::Err(#krate::Error::new(::std::format!("Hash not matching for {}.", #name_string)))
+            }
+
+            fn skip(bytes: &mut &[u8]) -> ::std::result::Result<(), #krate::Error> {
+                let start: &[u8] = *bytes;
+                if <u64 as #krate::Bytes>::deserialize_from(bytes)? == <Self as #krate::Bytes>::hash() {
+                    #(
+                        #field_skips
+                    )*
+                    return ::std::result::Result::Ok(());
+                }
+                // Older layouts are only known to the decoder.
+                *bytes = start;
+                <Self as #krate::Bytes>::deserialize_from(bytes).map(::std::mem::drop)
+            }
+
+            fn size_hint(&self) -> usize {
+                8 #(+ #field_size_hints)*
+            }
+
+            fn to_value(&self) -> #krate::Value {
+                #krate::Value::Struct(::std::vec![#(#field_values),*])
+            }
+
+            fn from_value(value: &#krate::Value) -> ::std::result::Result<Self, #krate::Error> {
+                #read_value
+                ::std::result::Result::Ok(Self {
+                    #(
+                        #value_fields,
+                    )*
+                })
+            }
+
+            const FIELDS: &'static [&'static str] = &[#(#field_names),*];
+
+            fn layout() -> #krate::Layout {
+                #krate::guard_layout::<Self, _>(|| #krate::Layout::Struct {
+                    name: ::std::string::String::from(#name_string),
+                    hash: <Self as #krate::Bytes>::hash(),
+                    fields: ::std::vec![#(#field_layouts),*]
+                })
             }
 
-            fn signature() -> String {
-                let mut output = String::new();
+            #hash
+
+            #hashes
+
+            fn signature() -> ::std::string::String {
+                #krate::guard_signature::<Self, _>(#name_string, || {
+                    let mut output = ::std::string::String::new();
+                    #(
+                        output.push_str(&#field_signatures);
+                    )*
+                    output
+                })
+            }
+        }
+    };
+    Ok(gen)
+}
+
+fn impl_enum(ast: &syn::DeriveInput, data: &DataEnum, from: &[Path], krate: &Path) -> syn::Result<proc_macro2::TokenStream> {
+    let enum_name = &ast.ident;
+    let generics = add_bounds(&ast.generics, krate);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let mut serialize_arms = Vec::new();
+    let mut deserialize_arms = Vec::new();
+    let mut skip_arms = Vec::new();
+    let mut size_hint_arms = Vec::new();
+    let mut value_arms = Vec::new();
+    let mut from_value_arms = Vec::new();
+    let mut signatures = Vec::new();
+    let mut variant_layouts = Vec::new();
+    let type_varint = varint_attr(&ast.attrs)?;
+    let enum_name_string = enum_name.to_string();
+
+    for (index, variant) in data.variants.iter().enumerate() {
+        let index = index as u32;
+        let variant_name = &variant.ident;
+        let mut field_serializers = Vec::new();
+        let mut field_skips = Vec::new();
+        let mut field_size_hints = Vec::new();
+        let mut field_deserializers = Vec::new();
+        let mut field_signatures = Vec::new();
+        let mut field_layouts = Vec::new();
+        for (i, field) in variant.fields.iter().enumerate() {
+            if let Some(attr) = field.attrs.iter().find(|attr| attr.path.is_ident("skip") || attr.path.is_ident("default")) {
+                return Err(Error::new_spanned(attr, "`#[skip]` and `#[default]` aren't supported on enum variants"));
+            }
+            check_type(&field.ty)?;
+            let varint = varint_attr(&field.attrs)? || type_varint;
+            let binding = match &field.ident {
+                Some(field_name) => field_name.clone(),
+                None => quote::format_ident!("field{}", i)
+            };
+            field_serializers.push(serialize_field(quote! { #binding }, &field.ty, varint, krate));
+            field_skips.push(skip_field(&field.ty, varint, krate));
+            let field_type = &field.ty;
+            field_size_hints.push(quote! { <#field_type as #krate::Bytes>::size_hint(#binding) });
+            field_deserializers.push(deserialize_field(&field.ty, varint, krate));
+            field_signatures.push(field_signature(&field.ty, varint, krate));
+            let member = match &field.ident {
+                Some(field_name) => Member::Named(field_name.clone()),
+                None => Member::Unnamed(Index::from(i))
+            };
+            field_layouts.push(field_layout(&member, &field.ty, varint, krate));
+        }
+        let bindings = variant.fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| match &field.ident {
+                Some(field_name) => field_name.clone(),
+                None => quote::format_ident!("field{}", i)
+            })
+            .collect::<Vec<_>>();
+
+        let (pattern, construct) = match variant.fields {
+            Fields::Named(_) => (
+                quote! { Self::#variant_name { #(#bindings),* } },
+                quote! { Self::#variant_name { #(#bindings: #field_deserializers),* } }
+            ),
+            Fields::Unnamed(_) => (
+                quote! { Self::#variant_name(#(#bindings),*) },
+                quote! { Self::#variant_name(#(#field_deserializers),*) }
+            ),
+            Fields::Unit => (
+                quote! { Self::#variant_name },
+                quote! { Self::#variant_name }
+            )
+        };
+
+        serialize_arms.push(quote! {
+            #pattern => {
+                #krate::Bytes::serialize_into(&#index, bytes);
                 #(
-                    output.push_str(&#field_types::signature());
+                    #field_serializers
                 )*
-                output
+            }
+        });
+        size_hint_arms.push(quote! {
+            #pattern => 4 #(+ #field_size_hints)*
+        });
+        let variant_string = variant_name.to_string();
+        variant_layouts.push(match variant.fields {
+            Fields::Unit => quote! {
+                (::std::string::String::from(#variant_string), ::std::option::Option::None)
+            },
+            _ => quote! {
+                (::std::string::String::from(#variant_string), ::std::option::Option::Some(::std::vec![#(#field_layouts),*]))
+            }
+        });
+        let fields_value = match variant.fields {
+            Fields::Unit => quote! { #krate::Value::Null },
+            _ => {
+                let field_values = variant.fields.iter().zip(&bindings).enumerate().map(|(i, (field, binding))| {
+                    let member = match &field.ident {
+                        Some(field_name) => Member::Named(field_name.clone()),
+                        None => Member::Unnamed(Index::from(i))
+                    };
+                    field_value(&member, quote! { #binding }, krate)
+                });
+                quote! { #krate::Value::Struct(::std::vec![#(#field_values),*]) }
+            }
+        };
+        value_arms.push(quote! {
+            #pattern => #krate::Value::Variant(::std::string::String::from(#variant_string), ::std::boxed::Box::new(#fields_value))
+        });
+        let source = Ident::new("fields", proc_macro2::Span::call_site());
+        let field_values = variant.fields.iter().enumerate().map(|(i, field)| {
+            let member = match &field.ident {
+                Some(field_name) => Member::Named(field_name.clone()),
+                None => Member::Unnamed(Index::from(i))
+            };
+            let name = member_name(&member);
+            let missing = quote! {
+                return ::std::result::Result::Err(#krate::Error::new(::std::format!("Missing field {} of {}::{}.", #name, #enum_name_string, #variant_string)))
+            };
+            let from_value = field_from_value(&source, &name, &field.ty, missing, krate);
+            quote! { #member: #from_value }
+        });
+        from_value_arms.push(quote! {
+            #variant_string => ::std::result::Result::Ok(Self::#variant_name { #(#field_values),* })
+        });
+        deserialize_arms.push(quote! {
+            #index => ::std::result::Result::Ok(#construct)
+        });
+        skip_arms.push(quote! {
+            #index => {
+                #(
+                    #field_skips
+                )*
+            }
+        });
+        let variant_name = variant_name.to_string();
+        signatures.push(quote! {
+            output.push_str(#variant_name);
+            output.push('(');
+            #(
+                output.push_str(&#field_signatures);
+            )*
+            output.push(')');
+        });
+    }
+
+    // Unit variants don't read their fields.
+    let read_fields = if data.variants.iter().any(|variant| !variant.fields.is_empty()) {
+        quote! { let fields = #krate::structured(fields)?; }
+    } else {
+        quote! { let _ = fields; }
+    };
+
+    let start = remember_start(from);
+    let hashes = readable_hashes(&[], from, krate);
+    let from = from_fallback(from, krate);
+    let hash = cached_hash(&ast.generics, krate);
+    let name_string = enum_name.to_string();
+
+    let gen = quote! {
+        impl #impl_generics #krate::Bytes for #enum_name #ty_generics #where_clause {
+            fn serialize_into(&self, bytes: &mut ::std::vec::Vec<u8>) {
+                #krate::Bytes::serialize_into(&<Self as #krate::Bytes>::hash(), bytes);
+                match self {
+                    #(
+                        #serialize_arms
+                    )*
+                }
+            }
+
+            fn deserialize_from(bytes: &mut &[u8]) -> ::std::result::Result<Self, #krate::Error> {
+                #start
+                let data_hash = <u64 as #krate::Bytes>::deserialize_from(bytes)?;
+
+                if data_hash == <Self as #krate::Bytes>::hash() {
+                    return match <u32 as #krate::Bytes>::deserialize_from(bytes)? {
+                        #(
+                            #deserialize_arms,
+                        )*
+                        discriminant => ::std::result::Result::Err(#krate::Error::new(::std::format!("Invalid variant {} of {}.", discriminant, #name_string)))
+                    };
+                }
+                #from
+                ::std::result::Result::Err(#krate::Error::new(::std::format!("Hash not matching for {}.", #name_string)))
+            }
+
+            fn skip(bytes: &mut &[u8]) -> ::std::result::Result<(), #krate::Error> {
+                let start: &[u8] = *bytes;
+                if <u64 as #krate::Bytes>::deserialize_from(bytes)? == <Self as #krate::Bytes>::hash() {
+                    match <u32 as #krate::Bytes>::deserialize_from(bytes)? {
+                        #(
+                            #skip_arms,
+                        )*
+                        discriminant => return ::std::result::Result::Err(#krate::Error::new(::std::format!("Invalid variant {} of {}.", discriminant, #name_string)))
+                    }
+                    return ::std::result::Result::Ok(());
+                }
+                // Older layouts are only known to the decoder.
+                *bytes = start;
+                <Self as #krate::Bytes>::deserialize_from(bytes).map(::std::mem::drop)
+            }
+
+            fn size_hint(&self) -> usize {
+                8 + match self {
+                    #(
+                        #size_hint_arms,
+                    )*
+                }
+            }
+
+            fn to_value(&self) -> #krate::Value {
+                match self {
+                    #(
+                        #value_arms,
+                    )*
+                }
+            }
+
+            fn from_value(value: &#krate::Value) -> ::std::result::Result<Self, #krate::Error> {
+                let value = #krate::structured(value)?;
+                let (name, fields) = #krate::variant(&value)?;
+                #read_fields
+                match name {
+                    #(
+                        #from_value_arms,
+                    )*
+                    name => ::std::result::Result::Err(#krate::Error::new(::std::format!("Unknown variant {} of {}.", name, #enum_name_string)))
+                }
+            }
+
+            fn layout() -> #krate::Layout {
+                #krate::guard_layout::<Self, _>(|| #krate::Layout::Enum {
+                    name: ::std::string::String::from(#name_string),
+                    hash: <Self as #krate::Bytes>::hash(),
+                    variants: ::std::vec![#(#variant_layouts),*]
+                })
+            }
+
+            #hash
+
+            #hashes
+
+            fn signature() -> ::std::string::String {
+                #krate::guard_signature::<Self, _>(#name_string, || {
+                    let mut output = ::std::string::String::new();
+                    #(
+                        #signatures
+                        output.push('|');
+                    )*
+                    output
+                })
             }
         }
     };
-    gen.into()
-}
\ No newline at end of file
+    Ok(gen)
+}
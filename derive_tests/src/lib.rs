@@ -0,0 +1 @@
+//! Tests that the derive macros can be used from a crate other than `database`.
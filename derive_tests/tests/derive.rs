@@ -0,0 +1,67 @@
+// Neither `Bytes` nor `Store` is imported here, and the names the generated code
+// could accidentally pick up are shadowed.
+#[allow(dead_code)]
+struct Error;
+#[allow(dead_code)]
+type Result = ();
+
+#[derive(database::Bytes, database::Store, PartialEq, Debug)]
+struct Person {
+    #[id] name: String,
+    age: u16,
+    status: Status,
+}
+
+#[derive(database::Bytes, PartialEq, Debug)]
+enum Status {
+    Active,
+    Suspended { until: u64 },
+}
+
+#[derive(database::Bytes, database::Store, PartialEq, Debug)]
+struct Wrapper<T>(#[id] u32, T);
+
+#[derive(database::Bytes, database::Store, PartialEq, Debug)]
+#[varint]
+struct Counter {
+    #[id] id: u64,
+    status: Status,
+}
+
+mod storage {
+    pub use database as db;
+}
+
+#[derive(storage::db::Bytes, storage::db::Store, PartialEq, Debug)]
+#[database(crate = "crate::storage::db")]
+struct Reexported {
+    #[id] id: u32,
+}
+
+#[test]
+fn absolute_paths() {
+    let database = database::Database::new("../data/derive-tests/absolute-paths");
+    let peter = Person {
+        name: String::from("Peter"),
+        age: 25,
+        status: Status::Suspended { until: 10 }
+    };
+    database.create(&peter).unwrap();
+    assert_eq!(database.read::<Person>(&String::from("Peter")).unwrap(), peter);
+    database.create(&Wrapper(1, String::from("one"))).unwrap();
+    assert_eq!(database.read::<Wrapper<String>>(&1).unwrap(), Wrapper(1, String::from("one")));
+    database.create(&Counter { id: 300, status: Status::Active }).unwrap();
+    assert_eq!(database.read::<Counter>(&300).unwrap(), Counter { id: 300, status: Status::Active });
+    database.delete_all::<Person>().unwrap();
+    database.delete_all::<Wrapper<String>>().unwrap();
+    database.delete_all::<Counter>().unwrap();
+}
+
+#[test]
+fn crate_override() {
+    let database = storage::db::Database::new("../data/derive-tests/crate-override");
+    database.create(&Reexported { id: 3 }).unwrap();
+    assert_eq!(database.read::<Reexported>(&3).unwrap(), Reexported { id: 3 });
+    database.delete_all::<Reexported>().unwrap();
+    assert_eq!(<Reexported as storage::db::Store>::NAME, "reexporteds");
+}
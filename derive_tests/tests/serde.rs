@@ -0,0 +1,106 @@
+use database::{Database, Bytes, Store, Serde};
+use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, BTreeMap};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+enum Role {
+    Guest,
+    Member(u32),
+    Admin { since: u64, scopes: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct Settings {
+    theme: String,
+    font_size: Option<u8>,
+    limits: HashMap<String, i64>,
+    ranges: BTreeMap<u16, (f32, f32)>,
+    role: Role,
+    flags: [bool; 3],
+    #[serde(with = "serde_bytes_as_seq")]
+    raw: Vec<u8>,
+}
+
+// Exercises the byte array path of the encoder.
+mod serde_bytes_as_seq {
+    use serde::{Serializer, Deserializer, Deserialize};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        <&[u8]>::deserialize(deserializer).map(<[u8]>::to_vec)
+    }
+}
+
+#[derive(Bytes, Store, PartialEq, Debug)]
+struct Account {
+    #[id] id: u32,
+    settings: Serde<Settings>,
+}
+
+#[derive(Bytes, Serialize, Deserialize, PartialEq, Debug)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Shape {
+    name: String,
+    #[serde(with = "database::as_bytes")]
+    origin: Point,
+}
+
+fn settings() -> Settings {
+    let mut limits = HashMap::new();
+    for i in 0..16 {
+        limits.insert(format!("limit {}", i), -i);
+    }
+    Settings {
+        theme: String::from("dark"),
+        font_size: Some(12),
+        limits,
+        ranges: (0..3).map(|i| (i, (i as f32, i as f32 * 2.0))).collect(),
+        role: Role::Admin { since: 7, scopes: vec![String::from("all")] },
+        flags: [true, false, true],
+        raw: vec![0, 1, 255],
+    }
+}
+
+#[test]
+fn serde_wrapper() {
+    let account = Account { id: 1, settings: Serde(settings()) };
+    let bytes = account.serialize();
+    let mut remaining = &bytes[..];
+    assert_eq!(Account::deserialize_from(&mut remaining).unwrap(), account);
+    assert!(remaining.is_empty());
+
+    // Hash maps are written in a deterministic order.
+    let mut reordered = settings();
+    reordered.limits = reordered.limits.into_iter().collect::<Vec<_>>().into_iter().rev().collect();
+    assert_eq!(Bytes::serialize(&Serde(reordered)), Bytes::serialize(&Serde(settings())));
+
+    for role in [Role::Guest, Role::Member(3)] {
+        let bytes = Bytes::serialize(&Serde(role.clone()));
+        assert_eq!(Serde::<Role>::deserialize_from(&mut &bytes[..]).unwrap().into_inner(), role);
+    }
+    assert!(Serde::<Settings>::deserialize_from(&mut &bytes[..4]).is_err());
+    assert_ne!(Serde::<Settings>::signature(), Serde::<Role>::signature());
+
+    let database = Database::new("../data/derive-tests/serde");
+    database.create(&account).unwrap();
+    assert_eq!(database.read::<Account>(&1).unwrap(), account);
+    database.delete_all::<Account>().unwrap();
+}
+
+#[test]
+fn bytes_in_serde() {
+    let shape = Shape { name: String::from("dot"), origin: Point { x: -1, y: 2 } };
+    let json = serde_json::to_string(&shape).unwrap();
+    assert_eq!(serde_json::from_str::<Shape>(&json).unwrap(), shape);
+    let bytes = Bytes::serialize(&Serde(shape));
+    let shape = Shape { name: String::from("dot"), origin: Point { x: -1, y: 2 } };
+    assert_eq!(Serde::<Shape>::deserialize_from(&mut &bytes[..]).unwrap().0, shape);
+}
@@ -0,0 +1,7 @@
+#[derive(database::Bytes)]
+#[database(krate = "database")]
+struct Person {
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(database::Bytes)]
+struct Person {
+    name: String,
+    #[default(18)] age: u16,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+#[derive(database::Bytes)]
+#[from = "Person2"]
+struct Person {
+    name: String,
+}
+
+#[derive(database::Bytes)]
+#[from("Person2")]
+struct Person3 {
+    name: String,
+}
+
+fn main() {}
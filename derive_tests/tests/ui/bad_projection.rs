@@ -0,0 +1,25 @@
+#[derive(database::Bytes)]
+#[projection(PointX(x, z))]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+#[derive(database::Bytes)]
+#[projection(PairFirst(0))]
+struct Pair(i32, i32);
+
+#[derive(database::Bytes)]
+#[projection(LineStart(start, start))]
+struct Line {
+    start: i32,
+    end: i32,
+}
+
+#[derive(database::Bytes)]
+#[projection(Empty())]
+struct Unit {
+    value: i32,
+}
+
+fn main() {}
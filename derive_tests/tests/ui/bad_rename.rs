@@ -0,0 +1,7 @@
+#[derive(database::Bytes, database::Store)]
+#[rename("persons")]
+struct Person {
+    #[id] name: String,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[derive(database::Bytes)]
+struct Counter {
+    #[varint(zigzag)] value: i32,
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+#[derive(database::Bytes)]
+#[view(PointView, derive)]
+struct Point {
+    x: i32,
+}
+
+#[derive(database::Bytes)]
+#[view(ShapeView)]
+enum Shape {
+    Point(i32),
+}
+
+#[derive(database::Bytes)]
+#[view(WrapperView)]
+struct Wrapper<T> {
+    value: T,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(database::Bytes, database::Store)]
+enum Shape {
+    Point { #[id] id: u32 },
+    Empty,
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[derive(database::Bytes, database::Store)]
+struct Person {
+    name: String,
+    age: u16,
+}
+
+fn main() {}
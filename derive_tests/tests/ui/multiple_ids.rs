@@ -0,0 +1,7 @@
+#[derive(database::Bytes, database::Store)]
+struct Person {
+    #[id] name: String,
+    #[id] age: u16,
+}
+
+fn main() {}
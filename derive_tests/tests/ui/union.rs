@@ -0,0 +1,7 @@
+#[derive(database::Bytes)]
+union Number {
+    integer: u32,
+    float: f32,
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[derive(database::Bytes)]
+struct Person<'a> {
+    name: &'a str,
+}
+
+fn main() {}
@@ -1,12 +1,115 @@
-use super::Error;
-use std::collections::HashMap;
-use std::hash::Hash;
+use super::{Error, Value, Layout};
+use super::value::{structured, variant, unexpected};
+use std::collections::{HashMap, BTreeMap, HashSet, BTreeSet, VecDeque, BinaryHeap};
+use std::hash::{Hash, BuildHasher};
 use std::cmp::Eq;
+use std::convert::TryFrom;
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::path::PathBuf;
+use std::cell::Cell;
+use std::thread::LocalKey;
 
 /// The `Bytes` trait has to be implemented in order to use the `Store` trait.
+///
+/// Implementations provide `serialize_into` and `deserialize_from`, which append to a shared
+/// buffer and read from the front of a slice, so nested values don't allocate buffers of their own.
+/// `serialize` and `deserialize` are kept for the API that came before: each pair is implemented by
+/// the other, so implementations written for the old API keep working, and have to provide at least
+/// one method of each pair. Providing neither panics with a message saying which is missing.
 pub trait Bytes {
-    fn serialize(&self) -> Vec<u8>;
-    fn deserialize(_: &mut Vec<u8>) -> Result<Self, Error> where Self: Sized;
+    /// Appends the encoded value to `bytes`.
+    /// Defaults to appending the bytes of `serialize`, for implementations of the old API.
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let _shim = Shim::enter::<Self>(&SERIALIZE_SHIM);
+        bytes.extend_from_slice(&self.serialize());
+    }
+
+    /// Decodes a value from the front of `bytes` and advances it past the value.
+    /// Defaults to `deserialize` on a reversed copy of `bytes`, for implementations of the old API.
+    /// The copy takes time in the length of the remaining bytes, so implement this for types nested in others.
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Self, Error> where Self: Sized {
+        let _shim = Shim::enter::<Self>(&DESERIALIZE_SHIM);
+        let mut stack = bytes.iter().rev().copied().collect::<Vec<u8>>();
+        let output = Self::deserialize(&mut stack)?;
+        *bytes = &bytes[bytes.len() - stack.len()..];
+        Ok(output)
+    }
+
+    /// Advances `bytes` past an encoded value without decoding it, used to read only some fields.
+    /// Implementations may skip without checking the value, like `String` does with UTF-8.
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> where Self: Sized {
+        Self::deserialize_from(bytes).map(drop)
+    }
+
+    /// Estimates the number of bytes `serialize_into` appends, used to preallocate buffers.
+    fn size_hint(&self) -> usize {
+        0
+    }
+
+    /// Returns the encoded value.
+    fn serialize(&self) -> Vec<u8> {
+        Shim::check::<Self>(&SERIALIZE_SHIM, "serialize_into", "serialize");
+        let mut bytes = Vec::with_capacity(self.size_hint());
+        self.serialize_into(&mut bytes);
+        bytes
+    }
+
+    /// Decodes a value from `bytes`, which holds the encoded bytes in reverse order
+    /// and is consumed from the back. The bytes are turned around in place to decode them from a
+    /// slice, which takes time in the length of all of them, so values nested in others are
+    /// decoded with `deserialize_from`.
+    fn deserialize(bytes: &mut Vec<u8>) -> Result<Self, Error> where Self: Sized {
+        Shim::check::<Self>(&DESERIALIZE_SHIM, "deserialize_from", "deserialize");
+        bytes.reverse();
+        let mut remaining = &bytes[..];
+        let output = Self::deserialize_from(&mut remaining);
+        let consumed = if output.is_ok() { bytes.len() - remaining.len() } else { 0 };
+        bytes.drain(..consumed);
+        bytes.reverse();
+        output
+    }
+
+    /// The names of the stored fields of a struct in the order they are stored, empty for other types.
+    /// Generated by the derive, and used for the columns of CSV exports.
+    const FIELDS: &'static [&'static str] = &[];
+
+    /// Returns the value as a `Value`, to inspect and query it without knowing its type.
+    /// Defaults to the encoded value as `Value::Bytes`.
+    fn to_value(&self) -> Value {
+        Value::Bytes(self.serialize())
+    }
+
+    /// Builds a value from a `Value`, the reverse of `to_value`.
+    /// Scalars are also read from strings, and other types from strings holding JSON, as in CSV cells.
+    /// Defaults to decoding `Value::Bytes`, or a list of bytes.
+    fn from_value(value: &Value) -> Result<Self, Error> where Self: Sized {
+        match &*structured(value)? {
+            Value::Bytes(bytes) => Self::deserialize_from(&mut &bytes[..]),
+            Value::List(elements) => {
+                let bytes = elements.iter().map(u8::from_value).collect::<Result<Vec<_>, _>>()?;
+                Self::deserialize_from(&mut &bytes[..])
+            },
+            value => Err(unexpected("bytes", value))
+        }
+    }
+
+    /// Describes how the value is encoded, so stored values can be decoded without the type.
+    /// Generated by the derive, and defaults to `Layout::Opaque` holding the signature.
+    fn layout() -> Layout where Self: Sized {
+        Layout::Opaque(Self::signature())
+    }
+
+    /// Returns the hashes of every layout values can be read from, starting with `hash`.
+    /// Generated by the derive to include the layouts before `#[default]` fields were added
+    /// and the layouts of the types in `#[from(...)]`.
+    fn hashes() -> Vec<u64> where Self: Sized {
+        vec![Self::hash()]
+    }
+
     // TODO: Move signature computation to compile time.
     fn signature() -> String;
     fn hash() -> u64 {
@@ -16,26 +119,208 @@ pub trait Bytes {
     }
 }
 
+thread_local! {
+    /// The type whose default `serialize_into` is running on this thread, innermost first.
+    static SERIALIZE_SHIM: Cell<Option<&'static str>> = const { Cell::new(None) };
+    /// The type whose default `deserialize_from` is running on this thread, innermost first.
+    static DESERIALIZE_SHIM: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// The type whose default of a new method is running, `SERIALIZE_SHIM` or `DESERIALIZE_SHIM`.
+type Running = LocalKey<Cell<Option<&'static str>>>;
+
+/// Marks the default of a new method running for a type, to detect types implementing neither
+/// method of a pair, whose defaults would call each other until the stack overflows.
+struct Shim {
+    running: &'static Running,
+    previous: Option<&'static str>
+}
+
+impl Shim {
+    fn enter<T: ?Sized>(running: &'static Running) -> Shim {
+        let previous = running.with(|running| running.replace(Some(std::any::type_name::<T>())));
+        Shim { running, previous }
+    }
+
+    /// Panics if the default of the new method of `T` called the default of the old one.
+    fn check<T: ?Sized>(running: &'static Running, new: &str, old: &str) {
+        if running.with(|running| running.get()) == Some(std::any::type_name::<T>()) {
+            panic!("`Bytes` for {} has to implement `{}` or `{}`", std::any::type_name::<T>(), new, old);
+        }
+    }
+}
+
+impl Drop for Shim {
+    fn drop(&mut self) {
+        self.running.with(|running| running.set(self.previous));
+    }
+}
+
+thread_local! {
+    static SIGNATURES_IN_PROGRESS: std::cell::RefCell<Vec<&'static str>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Computes the signature of a derived type, used by the derive macros.
+/// A type that contains itself, like `struct Node { next: Option<Box<Node>> }`, refers
+/// back to itself by name instead of recursing forever.
+#[doc(hidden)]
+pub fn guard_signature<T: ?Sized, F>(name: &str, signature: F) -> String
+    where F: FnOnce() -> String
+{
+    let key = std::any::type_name::<T>();
+    if SIGNATURES_IN_PROGRESS.with(|in_progress| in_progress.borrow().contains(&key)) {
+        return format!("<{}>", name);
+    }
+    SIGNATURES_IN_PROGRESS.with(|in_progress| in_progress.borrow_mut().push(key));
+    let output = signature();
+    SIGNATURES_IN_PROGRESS.with(|in_progress| in_progress.borrow_mut().pop());
+    output
+}
+
+/// Splits the next `len` bytes off the front of `bytes`.
+pub(crate) fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if bytes.len() < len {
+        return Err(Error::new(format!("Unexpected end of data, {} more bytes expected but only {} left.", len, bytes.len())));
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+/// Converts numbers of any kind, and strings holding them, used by `from_value` of the primitives.
+trait FromNumber: Sized + std::str::FromStr {
+    fn from_number(value: &Value) -> Option<Self>;
+}
+
+macro_rules! impl_FromNumber_for_integers {
+    ($($t:ty),+) => {
+        $(
+            impl FromNumber for $t {
+                fn from_number(value: &Value) -> Option<$t> {
+                    match *value {
+                        Value::Int(value) => <$t>::try_from(value).ok(),
+                        Value::UInt(value) => <$t>::try_from(value).ok(),
+                        Value::Float(value) if value.fract() == 0.0 => <$t>::try_from(value as i128).ok(),
+                        _ => None
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_FromNumber_for_integers!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_FromNumber_for_floats {
+    ($($t:ty),+) => {
+        $(
+            impl FromNumber for $t {
+                fn from_number(value: &Value) -> Option<$t> {
+                    match *value {
+                        Value::Int(value) => Some(value as $t),
+                        Value::UInt(value) => Some(value as $t),
+                        Value::Float(value) => Some(value as $t),
+                        _ => None
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_FromNumber_for_floats!(f32, f64);
+
+fn number<T: FromNumber>(value: &Value, name: &str) -> Result<T, Error> {
+    match value {
+        Value::String(_) => parsed(value, name),
+        value => T::from_number(value).ok_or_else(|| unexpected(name, value))
+    }
+}
+
+/// Parses a value stored as a string, like an address.
+fn parsed<T: std::str::FromStr>(value: &Value, name: &str) -> Result<T, Error> {
+    match value {
+        Value::String(text) => text.trim().parse().map_err(|_| unexpected(name, value)),
+        value => Err(unexpected(name, value))
+    }
+}
+
+/// Returns the elements of a list, used by `from_value` of sequences.
+fn elements(value: &Value) -> Result<Vec<Value>, Error> {
+    match structured(value)?.into_owned() {
+        Value::List(elements) => Ok(elements),
+        Value::Bytes(bytes) => Ok(bytes.into_iter().map(Value::from).collect()),
+        value => Err(unexpected("a list", &value))
+    }
+}
+
+/// Returns the entries of a map, used by `from_value` of maps. Struct fields are read as entries with string keys.
+fn entries(value: &Value) -> Result<Vec<(Value, Value)>, Error> {
+    match structured(value)?.into_owned() {
+        Value::Map(entries) => Ok(entries),
+        Value::Struct(fields) => Ok(fields.into_iter().map(|(name, value)| (Value::String(name), value)).collect()),
+        value => Err(unexpected("a map", &value))
+    }
+}
+
+/// Reads the number of elements of a sequence or entries of a map, stored as `u64`.
+/// Fails if it exceeds the remaining bytes like `check_count` says.
+pub(crate) fn deserialize_count(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let count = u64::deserialize_from(bytes)?;
+    check_count(count as u128, bytes)?;
+    Ok(count)
+}
+
+/// Fails if `count` elements can't fit into the remaining `bytes`, taking every element to fill at least
+/// one byte, so a corrupted count fails at once instead of looping over elements decoded from no input.
+/// Sequences of elements encoded in no bytes, like `Vec<()>`, can't be longer than the bytes after them.
+pub(crate) fn check_count(count: u128, bytes: &[u8]) -> Result<(), Error> {
+    if count > bytes.len() as u128 {
+        return Err(Error::new(format!("{} elements exceed the remaining {} bytes.", count, bytes.len())));
+    }
+    Ok(())
+}
+
+/// Limits the capacity reserved for a decoded length, so a corrupted length
+/// fails on the missing data instead of on a huge allocation.
+pub(crate) fn capacity(len: u64, bytes: &[u8]) -> usize {
+    std::cmp::min(len, bytes.len() as u64) as usize
+}
+
 macro_rules! impl_SerializeBinary_for_primitives {
     ($($t:ty),+) => {
         $(
             impl Bytes for $t {
-                fn serialize(&self) -> Vec<u8> {
-                    let mut bytes = Vec::new();
+                fn serialize_into(&self, bytes: &mut Vec<u8>) {
                     bytes.extend_from_slice(&self.to_le_bytes()[..]);
-                    bytes
                 }
 
-                fn deserialize(bytes: &mut Vec<u8>) -> Result<$t, Error> {
+                fn deserialize_from(bytes: &mut &[u8]) -> Result<$t, Error> {
                     const SIZE: usize = std::mem::size_of::<$t>();
-                    let mut my_bytes = [0; SIZE];
-                    for i in 0..SIZE {
-                        let byte = bytes.pop().unwrap();
-                        my_bytes[i] = byte;
-                    }
+                    let my_bytes = <[u8; SIZE]>::try_from(take(bytes, SIZE)?).unwrap_or_else(|_| unreachable!());
                     Ok(<$t>::from_le_bytes(my_bytes))
                 }
 
+                fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+                    take(bytes, std::mem::size_of::<$t>()).map(drop)
+                }
+
+                fn size_hint(&self) -> usize {
+                    std::mem::size_of::<$t>()
+                }
+
+                fn from_value(value: &Value) -> Result<$t, Error> {
+                    number(value, stringify!($t))
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::from(*self)
+                }
+
+                fn layout() -> Layout {
+                    Layout::Scalar(String::from(stringify!($t)))
+                }
+
                 fn signature() -> String {
                     String::from(stringify!($t))
                 }
@@ -46,15 +331,114 @@ macro_rules! impl_SerializeBinary_for_primitives {
 
 impl_SerializeBinary_for_primitives!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
 
+// `usize` and `isize` are stored as 64 bit integers, so files are portable between platforms.
+macro_rules! impl_Bytes_for_pointer_sized {
+    ($($t:ty as $fixed:ty),+) => {
+        $(
+            impl Bytes for $t {
+                fn serialize_into(&self, bytes: &mut Vec<u8>) {
+                    (*self as $fixed).serialize_into(bytes);
+                }
+
+                fn deserialize_from(bytes: &mut &[u8]) -> Result<$t, Error> {
+                    let value = <$fixed>::deserialize_from(bytes)?;
+                    <$t>::try_from(value).map_err(|_| Error::new(format!(
+                        "Value {} doesn't fit into {}.", value, stringify!($t)
+                    )))
+                }
+
+                fn size_hint(&self) -> usize {
+                    std::mem::size_of::<$fixed>()
+                }
+
+                fn from_value(value: &Value) -> Result<$t, Error> {
+                    number(value, stringify!($t))
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::from(*self)
+                }
+
+                fn layout() -> Layout {
+                    Layout::Scalar(String::from(stringify!($t)))
+                }
+
+                fn signature() -> String {
+                    String::from(stringify!($t))
+                }
+            }
+        )*
+    }
+}
+
+impl_Bytes_for_pointer_sized!(usize as u64, isize as i64);
+
+impl Bytes for char {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (*self as u32).serialize_into(bytes);
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<char, Error> {
+        let value = u32::deserialize_from(bytes)?;
+        char::from_u32(value).ok_or_else(|| Error::new(format!("Invalid char {:#x}.", value)))
+    }
+
+    fn size_hint(&self) -> usize {
+        4
+    }
+
+    fn from_value(value: &Value) -> Result<char, Error> {
+        let mut chars = match value {
+            Value::String(string) => string.chars(),
+            value => return Err(unexpected("a char", value))
+        };
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(unexpected("a char", value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::from(*self)
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("char"))
+    }
+
+    fn signature() -> String {
+        String::from("char")
+    }
+}
+
 impl Bytes for bool {
-    fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
         bytes.push(if *self { 1 } else { 0 });
-        bytes
     }
 
-    fn deserialize(bytes: &mut Vec<u8>) -> Result<bool, Error> {
-        Ok(bytes.pop().unwrap() == 1)
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<bool, Error> {
+        Ok(take(bytes, 1)?[0] == 1)
+    }
+
+    fn size_hint(&self) -> usize {
+        1
+    }
+
+    fn from_value(value: &Value) -> Result<bool, Error> {
+        match value {
+            Value::Bool(value) => Ok(*value),
+            Value::String(text) if text.trim() == "true" => Ok(true),
+            Value::String(text) if text.trim() == "false" => Ok(false),
+            value => Err(unexpected("a bool", value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Bool(*self)
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("bool"))
     }
 
     fn signature() -> String {
@@ -63,84 +447,1095 @@ impl Bytes for bool {
 }
 
 impl<S: Bytes> Bytes for Vec<S> {
-    fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.append(&mut (self.len() as u64).serialize());
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u64).serialize_into(bytes);
         for element in self {
-            bytes.append(&mut element.serialize());
+            element.serialize_into(bytes);
         }
-        bytes
     }
 
-    fn deserialize(bytes: &mut Vec<u8>) -> Result<Vec<S>, Error> {
-        let mut output = Vec::new();
-        let size = u64::deserialize(bytes)?;
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Vec<S>, Error> {
+        let size = deserialize_count(bytes)?;
+        let mut output = Vec::with_capacity(capacity(size, bytes));
         for _ in 0..size {
-            output.push(S::deserialize(bytes)?);
+            output.push(S::deserialize_from(bytes)?);
         }
         Ok(output)
     }
 
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        skip_elements::<S>(bytes)
+    }
+
+    fn size_hint(&self) -> usize {
+        8 + self.iter().map(Bytes::size_hint).sum::<usize>()
+    }
+
+    fn from_value(value: &Value) -> Result<Vec<S>, Error> {
+        elements(value)?.iter().map(S::from_value).collect()
+    }
+
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(Bytes::to_value).collect())
+    }
+
+    fn layout() -> Layout {
+        Layout::Sequence(Box::new(S::layout()))
+    }
+
     fn signature() -> String {
         format!("Vec<{}>", S::signature())
     }
 }
 
-impl<A: Bytes, B: Bytes> Bytes for (A, B) {
-    fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.append(&mut self.0.serialize());
-        bytes.append(&mut self.1.serialize());
-        bytes
+impl<S: Bytes, const N: usize> Bytes for [S; N] {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        for element in self {
+            element.serialize_into(bytes);
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<[S; N], Error> {
+        let mut output = Vec::with_capacity(N);
+        for _ in 0..N {
+            output.push(S::deserialize_from(bytes)?);
+        }
+        Ok(<[S; N]>::try_from(output).unwrap_or_else(|_| unreachable!()))
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        for _ in 0..N {
+            S::skip(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn size_hint(&self) -> usize {
+        self.iter().map(Bytes::size_hint).sum()
+    }
+
+    fn from_value(value: &Value) -> Result<[S; N], Error> {
+        let elements = elements(value)?.iter().map(S::from_value).collect::<Result<Vec<_>, _>>()?;
+        let len = elements.len();
+        <[S; N]>::try_from(elements).map_err(|_| Error::new(format!("Expected {} elements but found {}.", N, len)))
     }
 
-    fn deserialize(mut bytes: &mut Vec<u8>) -> Result<(A, B), Error> {        
-        Ok((A::deserialize(&mut bytes)?, B::deserialize(&mut bytes)?))
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(Bytes::to_value).collect())
+    }
+
+    fn layout() -> Layout {
+        Layout::Array(Box::new(S::layout()), N)
     }
 
     fn signature() -> String {
-        format!("({},{})", A::signature(), B::signature())
+        format!("[{};{}]", S::signature(), N)
     }
 }
 
-impl Bytes for String {
-    fn serialize(&self) -> Vec<u8> {
-        let mut bytes = Vec::new();
-        bytes.extend_from_slice(format!("{}\0", self).as_bytes());
-        bytes
+impl Bytes for () {
+    fn serialize_into(&self, _: &mut Vec<u8>) {}
+
+    fn deserialize_from(_: &mut &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn from_value(value: &Value) -> Result<(), Error> {
+        match value {
+            Value::Null => Ok(()),
+            value => Err(unexpected("null", value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Null
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("()"))
+    }
+
+    fn signature() -> String {
+        String::from("()")
     }
+}
+
+macro_rules! impl_Bytes_for_tuples {
+    ($(($($t:ident $i:tt),+)),+) => {
+        $(
+            impl<$($t: Bytes),+> Bytes for ($($t,)+) {
+                fn serialize_into(&self, bytes: &mut Vec<u8>) {
+                    $(self.$i.serialize_into(bytes);)+
+                }
+
+                fn deserialize_from(bytes: &mut &[u8]) -> Result<($($t,)+), Error> {
+                    Ok(($($t::deserialize_from(bytes)?,)+))
+                }
+
+                fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+                    $($t::skip(bytes)?;)+
+                    Ok(())
+                }
+
+                fn size_hint(&self) -> usize {
+                    0 $(+ self.$i.size_hint())+
+                }
+
+                fn from_value(value: &Value) -> Result<($($t,)+), Error> {
+                    let elements = elements(value)?;
+                    let expected = [$(stringify!($i)),+].len();
+                    if elements.len() != expected {
+                        return Err(Error::new(format!("Expected {} elements but found {}.", expected, elements.len())));
+                    }
+                    Ok(($($t::from_value(&elements[$i])?,)+))
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::List(vec![$(self.$i.to_value()),+])
+                }
+
+                fn layout() -> Layout {
+                    Layout::Tuple(vec![$($t::layout()),+])
+                }
 
-    fn deserialize(bytes: &mut Vec<u8>) -> Result<String, Error> {
-        let mut my_bytes = Vec::new();
-        loop {
-            match bytes.pop() {
-                Some(byte) => {
-                    if byte == b'\0' {
-                        break;
+                fn signature() -> String {
+                    let signatures: &[String] = &[$($t::signature()),+];
+                    if signatures.len() == 1 {
+                        format!("({},)", signatures[0])
+                    } else {
+                        format!("({})", signatures.join(","))
                     }
-                    my_bytes.push(byte);
-                },
-                None => { break; }
+                }
             }
+        )*
+    }
+}
+
+impl_Bytes_for_tuples!(
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+    (A 0, B 1, C 2, D 3, E 4),
+    (A 0, B 1, C 2, D 3, E 4, F 5),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+);
+
+/// Appends `value` as LEB128, seven bits per byte starting with the least significant ones,
+/// with the high bit set on every byte but the last.
+pub(crate) fn serialize_varint(mut value: u128, bytes: &mut Vec<u8>) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads a value written by `serialize_varint`.
+pub(crate) fn deserialize_varint(bytes: &mut &[u8]) -> Result<u128, Error> {
+    let mut value = 0u128;
+    for shift in (0..128).step_by(7) {
+        let byte = take(bytes, 1)?[0];
+        value |= ((byte & 0x7f) as u128) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
         }
-        Ok(String::from(std::str::from_utf8(&my_bytes).unwrap()))
     }
+    Err(Error::new(String::from("Varint is too long.")))
+}
 
-    fn signature() -> String {
-        String::from("String")
+/// Reads a string in the encoding of `String` without copying it.
+pub(crate) fn deserialize_str<'a>(bytes: &mut &'a [u8]) -> Result<&'a str, Error> {
+    let len = deserialize_varint(bytes)?;
+    if len > bytes.len() as u128 {
+        return Err(Error::new(format!("String of length {} exceeds the remaining {} bytes.", len, bytes.len())));
     }
+    let my_bytes = take(bytes, len as usize)?;
+    std::str::from_utf8(my_bytes).map_err(|error| Error::new(format!("Invalid UTF-8 in string: {}", error)))
 }
 
-impl<K: Bytes + Hash + Eq + Copy, V: Bytes + Copy> Bytes for HashMap<K, V> {
-    fn serialize(&self) -> Vec<u8> {
-        self.clone().drain().collect::<Vec<(K, V)>>().serialize()
+/// Skips a string in the encoding of `String` without checking it for UTF-8.
+fn skip_str(bytes: &mut &[u8]) -> Result<(), Error> {
+    let len = deserialize_varint(bytes)?;
+    if len > bytes.len() as u128 {
+        return Err(Error::new(format!("String of length {} exceeds the remaining {} bytes.", len, bytes.len())));
+    }
+    take(bytes, len as usize).map(drop)
+}
+
+/// Skips the length and the elements of a sequence.
+fn skip_elements<S: Bytes>(bytes: &mut &[u8]) -> Result<(), Error> {
+    let size = deserialize_count(bytes)?;
+    for _ in 0..size {
+        S::skip(bytes)?;
+    }
+    Ok(())
+}
+
+/// Strings are stored as their length in bytes as varint, followed by their UTF-8 bytes.
+impl Bytes for String {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        serialize_varint(self.len() as u128, bytes);
+        bytes.extend_from_slice(self.as_bytes());
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<String, Error> {
+        deserialize_str(bytes).map(String::from)
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        skip_str(bytes)
+    }
+
+    fn size_hint(&self) -> usize {
+        self.len() + 1
+    }
+
+    fn from_value(value: &Value) -> Result<String, Error> {
+        match value {
+            Value::String(string) => Ok(string.clone()),
+            value => Err(unexpected("a string", value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.clone())
     }
 
-    fn deserialize(mut bytes: &mut Vec<u8>) -> Result<HashMap<K, V>, Error> {        
-        Ok(Vec::<(K, V)>::deserialize(&mut bytes)?.into_iter().collect::<HashMap<K, V>>())
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("String"))
     }
 
     fn signature() -> String {
-        format!("HashMap<{},{}>", K::signature(), V::signature())
+        String::from("Utf8String")
+    }
+}
+
+/// A string in the format used before strings were length prefixed, terminated by a NUL byte.
+/// Its signature is the one `String` had back then, so a copy of an old type using
+/// `LegacyString` in place of `String` decodes old records and can be listed in `#[from(...)]`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LegacyString(pub String);
+
+impl From<LegacyString> for String {
+    fn from(legacy: LegacyString) -> String {
+        legacy.0
+    }
+}
+
+impl From<String> for LegacyString {
+    fn from(string: String) -> LegacyString {
+        LegacyString(string)
+    }
+}
+
+/// Reads a string in the encoding of `LegacyString` without copying it.
+pub(crate) fn deserialize_legacy_str<'a>(bytes: &mut &'a [u8]) -> Result<&'a str, Error> {
+    let len = bytes.iter().position(|&byte| byte == b'\0').unwrap_or(bytes.len());
+    let my_bytes = take(bytes, len)?;
+    if !bytes.is_empty() {
+        take(bytes, 1)?;
     }
-}
\ No newline at end of file
+    std::str::from_utf8(my_bytes).map_err(|error| Error::new(format!("Invalid UTF-8 in string: {}", error)))
+}
+
+impl Bytes for LegacyString {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(self.0.as_bytes());
+        bytes.push(b'\0');
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<LegacyString, Error> {
+        deserialize_legacy_str(bytes).map(|string| LegacyString(String::from(string)))
+    }
+
+    fn size_hint(&self) -> usize {
+        self.0.len() + 1
+    }
+
+    fn from_value(value: &Value) -> Result<LegacyString, Error> {
+        String::from_value(value).map(LegacyString)
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("LegacyString"))
+    }
+
+    fn signature() -> String {
+        String::from("String")
+    }
+}
+
+/// Appends the elements in the order of their encoding, so hash based collections
+/// with equal contents produce identical bytes regardless of their iteration order.
+/// The elements are encoded into one scratch buffer by `encode` and sorted in place.
+pub(crate) fn serialize_sorted_into<I, F>(elements: I, bytes: &mut Vec<u8>, mut encode: F)
+    where I: Iterator, F: FnMut(I::Item, &mut Vec<u8>)
+{
+    let mut scratch = Vec::new();
+    let mut ranges = Vec::new();
+    for element in elements {
+        let start = scratch.len();
+        encode(element, &mut scratch);
+        ranges.push(start..scratch.len());
+    }
+    ranges.sort_unstable_by(|a, b| scratch[a.clone()].cmp(&scratch[b.clone()]));
+    for range in ranges {
+        bytes.extend_from_slice(&scratch[range]);
+    }
+}
+
+/// Returns the values of the elements in the order of their encoding, like they are stored.
+fn sorted_values<'a, S: Bytes + 'a>(elements: impl Iterator<Item = &'a S>) -> Vec<Value> {
+    let mut elements = elements.collect::<Vec<_>>();
+    elements.sort_by_cached_key(|element| element.serialize());
+    elements.into_iter().map(Bytes::to_value).collect()
+}
+
+impl<K: Bytes + Hash + Eq, V: Bytes, H: BuildHasher + Default> Bytes for HashMap<K, V, H> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u64).serialize_into(bytes);
+        serialize_sorted_into(self.iter(), bytes, |(key, value), bytes| {
+            key.serialize_into(bytes);
+            value.serialize_into(bytes);
+        });
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<HashMap<K, V, H>, Error> {
+        let size = deserialize_count(bytes)?;
+        let mut output = HashMap::with_capacity_and_hasher(capacity(size, bytes), H::default());
+        for _ in 0..size {
+            output.insert(K::deserialize_from(bytes)?, V::deserialize_from(bytes)?);
+        }
+        Ok(output)
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        let size = deserialize_count(bytes)?;
+        for _ in 0..size {
+            K::skip(bytes)?;
+            V::skip(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn size_hint(&self) -> usize {
+        8 + self.iter().map(|(key, value)| key.size_hint() + value.size_hint()).sum::<usize>()
+    }
+
+    fn from_value(value: &Value) -> Result<HashMap<K, V, H>, Error> {
+        entries(value)?.iter().map(|(key, value)| Ok((K::from_value(key)?, V::from_value(value)?))).collect()
+    }
+
+    fn to_value(&self) -> Value {
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_by_cached_key(|(key, _)| key.serialize());
+        Value::Map(entries.into_iter().map(|(key, value)| (key.to_value(), value.to_value())).collect())
+    }
+
+    fn layout() -> Layout {
+        Layout::Map(Box::new(K::layout()), Box::new(V::layout()))
+    }
+
+    fn signature() -> String {
+        format!("HashMap<{},{}>", K::signature(), V::signature())
+    }
+}
+
+impl<K: Bytes + Ord, V: Bytes> Bytes for BTreeMap<K, V> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u64).serialize_into(bytes);
+        for (key, value) in self {
+            key.serialize_into(bytes);
+            value.serialize_into(bytes);
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<BTreeMap<K, V>, Error> {
+        let size = deserialize_count(bytes)?;
+        let mut output = BTreeMap::new();
+        for _ in 0..size {
+            output.insert(K::deserialize_from(bytes)?, V::deserialize_from(bytes)?);
+        }
+        Ok(output)
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        let size = deserialize_count(bytes)?;
+        for _ in 0..size {
+            K::skip(bytes)?;
+            V::skip(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn size_hint(&self) -> usize {
+        8 + self.iter().map(|(key, value)| key.size_hint() + value.size_hint()).sum::<usize>()
+    }
+
+    fn from_value(value: &Value) -> Result<BTreeMap<K, V>, Error> {
+        entries(value)?.iter().map(|(key, value)| Ok((K::from_value(key)?, V::from_value(value)?))).collect()
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Map(self.iter().map(|(key, value)| (key.to_value(), value.to_value())).collect())
+    }
+
+    fn layout() -> Layout {
+        Layout::Map(Box::new(K::layout()), Box::new(V::layout()))
+    }
+
+    fn signature() -> String {
+        format!("BTreeMap<{},{}>", K::signature(), V::signature())
+    }
+}
+
+impl<S: Bytes + Hash + Eq, H: BuildHasher + Default> Bytes for HashSet<S, H> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u64).serialize_into(bytes);
+        serialize_sorted_into(self.iter(), bytes, Bytes::serialize_into);
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<HashSet<S, H>, Error> {
+        let size = deserialize_count(bytes)?;
+        let mut output = HashSet::with_capacity_and_hasher(capacity(size, bytes), H::default());
+        for _ in 0..size {
+            output.insert(S::deserialize_from(bytes)?);
+        }
+        Ok(output)
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        skip_elements::<S>(bytes)
+    }
+
+    fn size_hint(&self) -> usize {
+        8 + self.iter().map(Bytes::size_hint).sum::<usize>()
+    }
+
+    fn from_value(value: &Value) -> Result<HashSet<S, H>, Error> {
+        elements(value)?.iter().map(S::from_value).collect()
+    }
+
+    fn to_value(&self) -> Value {
+        Value::List(sorted_values(self.iter()))
+    }
+
+    fn layout() -> Layout {
+        Layout::Sequence(Box::new(S::layout()))
+    }
+
+    fn signature() -> String {
+        format!("HashSet<{}>", S::signature())
+    }
+}
+
+impl<S: Bytes + Ord> Bytes for BinaryHeap<S> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u64).serialize_into(bytes);
+        serialize_sorted_into(self.iter(), bytes, Bytes::serialize_into);
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<BinaryHeap<S>, Error> {
+        Ok(BinaryHeap::from(Vec::<S>::deserialize_from(bytes)?))
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        skip_elements::<S>(bytes)
+    }
+
+    fn size_hint(&self) -> usize {
+        8 + self.iter().map(Bytes::size_hint).sum::<usize>()
+    }
+
+    fn from_value(value: &Value) -> Result<BinaryHeap<S>, Error> {
+        elements(value)?.iter().map(S::from_value).collect()
+    }
+
+    fn to_value(&self) -> Value {
+        Value::List(sorted_values(self.iter()))
+    }
+
+    fn layout() -> Layout {
+        Layout::Sequence(Box::new(S::layout()))
+    }
+
+    fn signature() -> String {
+        format!("BinaryHeap<{}>", S::signature())
+    }
+}
+
+macro_rules! impl_Bytes_for_sequences {
+    ($($t:ident $(: $bound:ident)?),+) => {
+        $(
+            impl<S: Bytes $(+ $bound)?> Bytes for $t<S> {
+                fn serialize_into(&self, bytes: &mut Vec<u8>) {
+                    (self.len() as u64).serialize_into(bytes);
+                    for element in self {
+                        element.serialize_into(bytes);
+                    }
+                }
+
+                fn deserialize_from(bytes: &mut &[u8]) -> Result<$t<S>, Error> {
+                    let size = deserialize_count(bytes)?;
+                    (0..size).map(|_| S::deserialize_from(bytes)).collect()
+                }
+
+                fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+                    skip_elements::<S>(bytes)
+                }
+
+                fn size_hint(&self) -> usize {
+                    8 + self.iter().map(Bytes::size_hint).sum::<usize>()
+                }
+
+                fn from_value(value: &Value) -> Result<$t<S>, Error> {
+                    elements(value)?.iter().map(S::from_value).collect()
+                }
+
+                fn to_value(&self) -> Value {
+                    Value::List(self.iter().map(Bytes::to_value).collect())
+                }
+
+                fn layout() -> Layout {
+                    Layout::Sequence(Box::new(S::layout()))
+                }
+
+                fn signature() -> String {
+                    format!("{}<{}>", stringify!($t), S::signature())
+                }
+            }
+        )*
+    }
+}
+
+impl_Bytes_for_sequences!(BTreeSet: Ord, VecDeque);
+
+impl<S: Bytes> Bytes for Option<S> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.is_some().serialize_into(bytes);
+        if let Some(value) = self {
+            value.serialize_into(bytes);
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Option<S>, Error> {
+        Ok(if bool::deserialize_from(bytes)? {
+            Some(S::deserialize_from(bytes)?)
+        } else {
+            None
+        })
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        if bool::deserialize_from(bytes)? {
+            S::skip(bytes)?;
+        }
+        Ok(())
+    }
+
+    fn size_hint(&self) -> usize {
+        1 + self.as_ref().map_or(0, Bytes::size_hint)
+    }
+
+    fn from_value(value: &Value) -> Result<Option<S>, Error> {
+        match value {
+            Value::Null => Ok(None),
+            value => S::from_value(value).map(Some)
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        self.as_ref().map_or(Value::Null, Bytes::to_value)
+    }
+
+    fn layout() -> Layout {
+        Layout::Option(Box::new(S::layout()))
+    }
+
+    fn signature() -> String {
+        format!("Option<{}>", S::signature())
+    }
+}
+
+impl<T: Bytes, E: Bytes> Bytes for Result<T, E> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.is_ok().serialize_into(bytes);
+        match self {
+            Ok(value) => value.serialize_into(bytes),
+            Err(error) => error.serialize_into(bytes)
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Result<T, E>, Error> {
+        Ok(if bool::deserialize_from(bytes)? {
+            Ok(T::deserialize_from(bytes)?)
+        } else {
+            Err(E::deserialize_from(bytes)?)
+        })
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        if bool::deserialize_from(bytes)? {
+            T::skip(bytes)
+        } else {
+            E::skip(bytes)
+        }
+    }
+
+    fn size_hint(&self) -> usize {
+        1 + match self {
+            Ok(value) => value.size_hint(),
+            Err(error) => error.size_hint()
+        }
+    }
+
+    fn from_value(value: &Value) -> Result<Result<T, E>, Error> {
+        let value = structured(value)?;
+        match variant(&value)? {
+            ("Ok", value) => T::from_value(value).map(Ok),
+            ("Err", error) => E::from_value(error).map(Err),
+            _ => Err(unexpected("`Ok` or `Err`", &value))
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Ok(value) => Value::Variant(String::from("Ok"), Box::new(value.to_value())),
+            Err(error) => Value::Variant(String::from("Err"), Box::new(error.to_value()))
+        }
+    }
+
+    fn layout() -> Layout {
+        Layout::Result(Box::new(T::layout()), Box::new(E::layout()))
+    }
+
+    fn signature() -> String {
+        format!("Result<{},{}>", T::signature(), E::signature())
+    }
+}
+
+macro_rules! impl_Bytes_for_pointers {
+    ($($t:ident),+) => {
+        $(
+            impl<S: Bytes> Bytes for $t<S> {
+                fn serialize_into(&self, bytes: &mut Vec<u8>) {
+                    (**self).serialize_into(bytes);
+                }
+
+                fn deserialize_from(bytes: &mut &[u8]) -> Result<$t<S>, Error> {
+                    Ok($t::new(S::deserialize_from(bytes)?))
+                }
+
+                fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+                    S::skip(bytes)
+                }
+
+                fn size_hint(&self) -> usize {
+                    (**self).size_hint()
+                }
+
+                fn from_value(value: &Value) -> Result<$t<S>, Error> {
+                    S::from_value(value).map($t::new)
+                }
+
+                fn to_value(&self) -> Value {
+                    (**self).to_value()
+                }
+
+                fn layout() -> Layout {
+                    S::layout()
+                }
+
+                fn signature() -> String {
+                    format!("{}<{}>", stringify!($t), S::signature())
+                }
+            }
+        )*
+    }
+}
+
+impl_Bytes_for_pointers!(Box, Rc, Arc);
+
+impl Bytes for Cow<'_, str> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        serialize_varint(self.len() as u128, bytes);
+        bytes.extend_from_slice(self.as_bytes());
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Self, Error> {
+        Ok(Cow::Owned(String::deserialize_from(bytes)?))
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        skip_str(bytes)
+    }
+
+    fn size_hint(&self) -> usize {
+        self.len() + 1
+    }
+
+    fn from_value(value: &Value) -> Result<Self, Error> {
+        String::from_value(value).map(Cow::Owned)
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(String::from(&**self))
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("String"))
+    }
+
+    fn signature() -> String {
+        format!("Cow<{}>", String::signature())
+    }
+}
+
+impl<S: Bytes + Clone> Bytes for Cow<'_, [S]> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        (self.len() as u64).serialize_into(bytes);
+        for element in self.iter() {
+            element.serialize_into(bytes);
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Self, Error> {
+        Ok(Cow::Owned(Vec::<S>::deserialize_from(bytes)?))
+    }
+
+    fn skip(bytes: &mut &[u8]) -> Result<(), Error> {
+        skip_elements::<S>(bytes)
+    }
+
+    fn size_hint(&self) -> usize {
+        8 + self.iter().map(Bytes::size_hint).sum::<usize>()
+    }
+
+    fn from_value(value: &Value) -> Result<Self, Error> {
+        Vec::<S>::from_value(value).map(Cow::Owned)
+    }
+
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(Bytes::to_value).collect())
+    }
+
+    fn layout() -> Layout {
+        Layout::Sequence(Box::new(S::layout()))
+    }
+
+    fn signature() -> String {
+        format!("Cow<[{}]>", S::signature())
+    }
+}
+
+/// Stored as the whole seconds as `u64`, followed by the subsecond nanoseconds as `u32`.
+impl Bytes for Duration {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.as_secs().serialize_into(bytes);
+        self.subsec_nanos().serialize_into(bytes);
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Duration, Error> {
+        let secs = u64::deserialize_from(bytes)?;
+        let nanos = u32::deserialize_from(bytes)?;
+        if nanos >= 1_000_000_000 {
+            return Err(Error::new(format!("Invalid subsecond nanoseconds {}.", nanos)));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+
+    fn size_hint(&self) -> usize {
+        12
+    }
+
+    fn from_value(value: &Value) -> Result<Duration, Error> {
+        let value = structured(value)?;
+        let field = |name| value.field(name).ok_or_else(|| Error::new(format!("Missing field {} of Duration.", name)));
+        let (secs, nanos) = (u64::from_value(field("secs")?)?, u32::from_value(field("nanos")?)?);
+        if nanos >= 1_000_000_000 {
+            return Err(Error::new(format!("Invalid subsecond nanoseconds {}.", nanos)));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::Struct(vec![
+            (String::from("secs"), Value::from(self.as_secs())),
+            (String::from("nanos"), Value::from(self.subsec_nanos()))
+        ])
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("Duration"))
+    }
+
+    fn signature() -> String {
+        String::from("Duration")
+    }
+}
+
+/// Returns the seconds since the unix epoch rounded towards negative infinity, and the remaining nanoseconds.
+fn unix_time(time: &SystemTime) -> (i64, u32) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => (after.as_secs() as i64, after.subsec_nanos()),
+        Err(error) => {
+            let before = error.duration();
+            if before.subsec_nanos() == 0 {
+                (-(before.as_secs() as i64), 0)
+            } else {
+                (-(before.as_secs() as i64) - 1, 1_000_000_000 - before.subsec_nanos())
+            }
+        }
+    }
+}
+
+/// Stored relative to the unix epoch, as the seconds as `i64` rounded towards negative infinity,
+/// followed by the remaining nanoseconds as `u32`. Times before 1970 are negative.
+impl Bytes for SystemTime {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        let (secs, nanos) = unix_time(self);
+        secs.serialize_into(bytes);
+        nanos.serialize_into(bytes);
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<SystemTime, Error> {
+        let secs = i64::deserialize_from(bytes)?;
+        let nanos = u32::deserialize_from(bytes)?;
+        if nanos >= 1_000_000_000 {
+            return Err(Error::new(format!("Invalid subsecond nanoseconds {}.", nanos)));
+        }
+        let time = if secs >= 0 {
+            UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+        } else {
+            UNIX_EPOCH
+                .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+                .and_then(|time| time.checked_add(Duration::from_nanos(nanos as u64)))
+        };
+        time.ok_or_else(|| Error::new(String::from("System time out of range.")))
+    }
+
+    fn size_hint(&self) -> usize {
+        12
+    }
+
+    fn from_value(value: &Value) -> Result<SystemTime, Error> {
+        let value = structured(value)?;
+        let field = |name| value.field(name).ok_or_else(|| Error::new(format!("Missing field {} of SystemTime.", name)));
+        let mut bytes = Vec::with_capacity(12);
+        i64::from_value(field("secs")?)?.serialize_into(&mut bytes);
+        u32::from_value(field("nanos")?)?.serialize_into(&mut bytes);
+        SystemTime::deserialize_from(&mut &bytes[..])
+    }
+
+    fn to_value(&self) -> Value {
+        let (secs, nanos) = unix_time(self);
+        Value::Struct(vec![(String::from("secs"), Value::from(secs)), (String::from("nanos"), Value::from(nanos))])
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("SystemTime"))
+    }
+
+    fn signature() -> String {
+        String::from("SystemTime")
+    }
+}
+
+/// Stored as its 4 octets in network byte order.
+impl Bytes for Ipv4Addr {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.octets());
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Ipv4Addr, Error> {
+        Ok(Ipv4Addr::from(<[u8; 4]>::deserialize_from(bytes)?))
+    }
+
+    fn size_hint(&self) -> usize {
+        4
+    }
+
+    fn from_value(value: &Value) -> Result<Ipv4Addr, Error> {
+        parsed(value, "an address")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("Ipv4Addr"))
+    }
+
+    fn signature() -> String {
+        String::from("Ipv4Addr")
+    }
+}
+
+/// Stored as its 16 octets in network byte order.
+impl Bytes for Ipv6Addr {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.octets());
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Ipv6Addr, Error> {
+        Ok(Ipv6Addr::from(<[u8; 16]>::deserialize_from(bytes)?))
+    }
+
+    fn size_hint(&self) -> usize {
+        16
+    }
+
+    fn from_value(value: &Value) -> Result<Ipv6Addr, Error> {
+        parsed(value, "an address")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("Ipv6Addr"))
+    }
+
+    fn signature() -> String {
+        String::from("Ipv6Addr")
+    }
+}
+
+/// Stored as the version `4` or `6` as `u8`, followed by the address.
+impl Bytes for IpAddr {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            IpAddr::V4(address) => {
+                4u8.serialize_into(bytes);
+                address.serialize_into(bytes);
+            },
+            IpAddr::V6(address) => {
+                6u8.serialize_into(bytes);
+                address.serialize_into(bytes);
+            }
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<IpAddr, Error> {
+        match u8::deserialize_from(bytes)? {
+            4 => Ok(IpAddr::V4(Ipv4Addr::deserialize_from(bytes)?)),
+            6 => Ok(IpAddr::V6(Ipv6Addr::deserialize_from(bytes)?)),
+            version => Err(Error::new(format!("Invalid IP version {}.", version)))
+        }
+    }
+
+    fn size_hint(&self) -> usize {
+        match self {
+            IpAddr::V4(_) => 5,
+            IpAddr::V6(_) => 17
+        }
+    }
+
+    fn from_value(value: &Value) -> Result<IpAddr, Error> {
+        parsed(value, "an address")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("IpAddr"))
+    }
+
+    fn signature() -> String {
+        String::from("IpAddr")
+    }
+}
+
+/// Stored like `IpAddr`, followed by the port as `u16`.
+/// IPv6 socket addresses additionally store the flow info and the scope id as `u32`.
+impl Bytes for SocketAddr {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        self.ip().serialize_into(bytes);
+        self.port().serialize_into(bytes);
+        if let SocketAddr::V6(address) = self {
+            address.flowinfo().serialize_into(bytes);
+            address.scope_id().serialize_into(bytes);
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<SocketAddr, Error> {
+        Ok(match IpAddr::deserialize_from(bytes)? {
+            IpAddr::V4(ip) => SocketAddr::V4(SocketAddrV4::new(ip, u16::deserialize_from(bytes)?)),
+            IpAddr::V6(ip) => {
+                let port = u16::deserialize_from(bytes)?;
+                let flowinfo = u32::deserialize_from(bytes)?;
+                let scope_id = u32::deserialize_from(bytes)?;
+                SocketAddr::V6(SocketAddrV6::new(ip, port, flowinfo, scope_id))
+            }
+        })
+    }
+
+    fn size_hint(&self) -> usize {
+        match self {
+            SocketAddr::V4(_) => 7,
+            SocketAddr::V6(_) => 27
+        }
+    }
+
+    fn from_value(value: &Value) -> Result<SocketAddr, Error> {
+        parsed(value, "an address")
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("SocketAddr"))
+    }
+
+    fn signature() -> String {
+        String::from("SocketAddr")
+    }
+}
+
+/// Stored like a `String`, so paths are portable between platforms.
+/// Parts of a path that aren't valid unicode are replaced by `U+FFFD`.
+impl Bytes for PathBuf {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        Cow::<str>::serialize_into(&self.to_string_lossy(), bytes);
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<PathBuf, Error> {
+        Ok(PathBuf::from(String::deserialize_from(bytes)?))
+    }
+
+    fn size_hint(&self) -> usize {
+        self.as_os_str().len() + 1
+    }
+
+    fn from_value(value: &Value) -> Result<PathBuf, Error> {
+        String::from_value(value).map(PathBuf::from)
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string_lossy().into_owned())
+    }
+
+    fn layout() -> Layout {
+        Layout::Scalar(String::from("PathBuf"))
+    }
+
+    fn signature() -> String {
+        String::from("PathBuf")
+    }
+}
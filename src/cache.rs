@@ -0,0 +1,55 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// The encoded values of recently read entries by their key, the collection name and entry name
+/// joined by `/`. Evicts the entries read first once they take up more than `capacity` bytes.
+pub(crate) struct Cache {
+    capacity: usize,
+    size: usize,
+    values: HashMap<String, Arc<[u8]>>,
+    order: VecDeque<String>
+}
+
+impl Cache {
+    pub(crate) fn new(capacity: usize) -> Cache {
+        Cache {
+            capacity,
+            size: 0,
+            values: HashMap::new(),
+            order: VecDeque::new()
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Arc<[u8]>> {
+        self.values.get(key).cloned()
+    }
+
+    /// Keeps the value of `key`, unless it is larger than the whole cache.
+    pub(crate) fn insert(&mut self, key: &str, value: &[u8]) {
+        if value.len() > self.capacity {
+            return;
+        }
+        self.remove(key);
+        self.size += value.len();
+        self.values.insert(String::from(key), Arc::from(value));
+        self.order.push_back(String::from(key));
+        while self.size > self.capacity {
+            match self.order.pop_front() {
+                Some(oldest) => {
+                    if let Some(value) = self.values.remove(&oldest) {
+                        self.size -= value.len();
+                    }
+                },
+                None => break
+            }
+        }
+    }
+
+    /// Forgets the value of `key`, after it was written or deleted.
+    pub(crate) fn remove(&mut self, key: &str) {
+        if let Some(value) = self.values.remove(key) {
+            self.size -= value.len();
+            self.order.retain(|other| other != key);
+        }
+    }
+}
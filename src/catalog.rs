@@ -0,0 +1,216 @@
+//! The catalog of a database, a file next to the collections recording how their entries are encoded.
+//!
+//! The catalog holds the layout of the ids of every collection and the schema hashes its entries
+//! were written with, along with the layouts of every derived type by hash and the schemas of the
+//! stored types. It is updated when an entry of a type is created or updated for the first time,
+//! and after `Database::upgrade_all` and `Database::delete_all` to drop the hashes no entry has anymore.
+//! It is read by `Database::read_raw`, and checked by `Database::register`.
+
+use super::{Database, Store, Bytes, Value, Layout, Schema, Error, Durability};
+use super::layout::{self, Decoder};
+use super::record;
+use std::collections::{BTreeMap, HashSet};
+use std::collections::btree_map::Entry;
+use std::fs;
+use std::path::Path;
+
+/// The name of the catalog file inside the database.
+pub(crate) const CATALOG: &str = ".catalog";
+
+/// What the catalog records about a collection.
+#[derive(Bytes, Debug, Clone, PartialEq)]
+struct Collection {
+    /// The layout of the ids of the entries written last, since ids may change their type
+    /// until `Database::upgrade_all` renames older entries.
+    id: Layout,
+    /// The schema hashes entries were written with, oldest first.
+    hashes: Vec<u64>,
+    /// The schemas of the types entries were written with, by their hash.
+    schemas: BTreeMap<u64, Schema>
+}
+
+#[derive(Bytes, Debug, Clone, PartialEq, Default)]
+#[from(Catalog1)]
+pub(crate) struct Catalog {
+    collections: BTreeMap<String, Collection>,
+    /// The layouts of derived types by their hash, including types nested in others.
+    layouts: BTreeMap<u64, Layout>
+}
+
+/// A collection in catalogs written before schemas were recorded.
+#[derive(Bytes)]
+struct Collection1 {
+    id: Layout,
+    hashes: Vec<u64>
+}
+
+/// The catalog before schemas were recorded.
+#[derive(Bytes)]
+struct Catalog1 {
+    collections: BTreeMap<String, Collection1>,
+    layouts: BTreeMap<u64, Layout>
+}
+
+impl From<Catalog1> for Catalog {
+    fn from(catalog1: Catalog1) -> Catalog {
+        Catalog {
+            collections: catalog1.collections.into_iter()
+                .map(|(name, collection)| (name, Collection { id: collection.id, hashes: collection.hashes, schemas: BTreeMap::new() }))
+                .collect(),
+            layouts: catalog1.layouts
+        }
+    }
+}
+
+impl Catalog {
+    /// Reads the catalog of the database at `path`, which is empty if it doesn't exist yet.
+    pub(crate) fn load(path: &Path) -> Result<Catalog, Error> {
+        let path = path.join(CATALOG);
+        if !path.exists() {
+            return Ok(Catalog::default());
+        }
+        let bytes = fs::read(&path)?;
+        Catalog::deserialize_from(&mut record::open(&bytes, &path, true)?)
+    }
+
+    /// Applies `f` to the catalog of the database at `path`, writing it back if `f` returns `true`.
+    /// The catalog is replaced at once, so a failed write leaves the previous one in place.
+    pub(crate) fn update<F>(path: &Path, durability: Durability, f: F) -> Result<(), Error>
+        where F: FnOnce(&mut Catalog) -> bool
+    {
+        let mut catalog = Catalog::load(path)?;
+        if f(&mut catalog) {
+            fs::create_dir_all(path)?;
+            let temporary = path.join(format!("{}.tmp", CATALOG));
+            let file = fs::File::create(&temporary)?;
+            std::io::Write::write_all(&mut &file, &record::seal(&catalog))?;
+            durability.sync_file(&file)?;
+            fs::rename(&temporary, path.join(CATALOG))?;
+            durability.sync_directory(path)?;
+        }
+        Ok(())
+    }
+
+    /// Records the layouts of `T` and its id, returning whether anything was new.
+    pub(crate) fn record<T>(&mut self) -> bool
+        where T: Store
+    {
+        let mut changed = false;
+        let collection = self.collections.entry(String::from(T::NAME)).or_insert_with(|| {
+            changed = true;
+            Collection { id: T::Id::layout(), hashes: Vec::new(), schemas: BTreeMap::new() }
+        });
+        if !collection.hashes.contains(&T::hash()) {
+            collection.hashes.push(T::hash());
+            collection.id = T::Id::layout();
+            changed = true;
+        }
+        if let Entry::Vacant(entry) = collection.schemas.entry(T::hash()) {
+            entry.insert(T::schema());
+            changed = true;
+        }
+        let layouts = &mut self.layouts;
+        T::layout().walk(&mut |layout| {
+            if let Layout::Struct { hash, .. } | Layout::Enum { hash, .. } = layout {
+                if !layouts.contains_key(hash) {
+                    layouts.insert(*hash, layout.clone());
+                    changed = true;
+                }
+            }
+        });
+        changed
+    }
+
+    /// Drops the schema hashes of `collection` that aren't in `present`, the hashes its entries have now,
+    /// returning whether any was dropped.
+    pub(crate) fn retain(&mut self, collection: &str, present: &HashSet<u64>) -> bool {
+        let collection = match self.collections.get_mut(collection) {
+            Some(collection) => collection,
+            None => return false
+        };
+        let count = collection.hashes.len();
+        collection.hashes.retain(|hash| present.contains(hash));
+        let hashes = &collection.hashes;
+        collection.schemas.retain(|hash, _| hashes.contains(hash));
+        collection.hashes.len() != count
+    }
+
+    /// Checks that `T` reads every entry of its collection.
+    pub(crate) fn check<T>(&self) -> Result<(), Error>
+        where T: Store
+    {
+        let collection = match self.collections.get(T::NAME) {
+            Some(collection) => collection,
+            None => return Ok(())
+        };
+        let readable = T::hashes();
+        match collection.hashes.iter().find(|hash| !readable.contains(hash)) {
+            Some(hash) => Err(Error::incompatible(T::NAME, format!(
+                "Collection \"{}\" has entries with schema hash {:016x}{}, which {} can't read. List their type in `#[from(...)]` to convert them.",
+                T::NAME,
+                hash,
+                collection.schemas.get(hash).map(|schema| format!(" written by {}", schema.name)).unwrap_or_default(),
+                std::any::type_name::<T>()
+            ))),
+            None => Ok(())
+        }
+    }
+
+    fn collection(&self, name: &str) -> Result<&Collection, Error> {
+        self.collections.get(name)
+            .ok_or_else(|| Error::new(format!("The catalog has no layouts of collection \"{}\"", name)))
+    }
+
+    /// Decodes the encoded value of an entry of `collection`.
+    pub(crate) fn decode(&self, collection: &str, mut bytes: &[u8]) -> Result<Value, Error> {
+        let layout = self.collection(collection)?.hashes.last()
+            .and_then(|hash| self.layouts.get(hash))
+            .ok_or_else(|| Error::new(format!("Entries of collection \"{}\" can't be decoded without their type", collection)))?;
+        let value = Decoder::new(&self.layouts).decode(layout, &mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(Error::new(format!("{} bytes are left after decoding the entry.", bytes.len())));
+        }
+        Ok(value)
+    }
+}
+
+impl Database {
+    /// Reads the entry of `collection` with the id `id` without knowing its type, returning the
+    /// `Value` its type would. The id is given like `Bytes::from_value` takes it, like `Value::from(7u32)`.
+    ///
+    /// Ids and entries are decoded with the layouts the catalog recorded when they were written.
+    /// Entries of types that don't describe their layout, and entries written before layouts were
+    /// recorded, can't be read.
+    pub fn read_raw(&self, collection: &str, id: &Value) -> Result<Value, Error> {
+        let catalog = Catalog::load(self.path())?;
+        let mut encoded = Vec::new();
+        layout::encode(&catalog.collection(collection)?.id, id, &mut encoded)?;
+        let name = Database::encode_name(&encoded)?;
+        self.read_entry_with(collection, &name, |bytes| catalog.decode(collection, bytes))
+    }
+
+    /// Checks that `T` reads every entry of its collection, going by the schema hashes in the catalog.
+    ///
+    /// Fails with `ErrorKind::Incompatible` if entries of the collection were written with a schema hash
+    /// `T` doesn't read, neither as an older layout of its own nor through `#[from(...)]`. Registering every stored type after opening the database detects
+    /// this up front instead of on the first read.
+    pub fn register<T>(&self) -> Result<(), Error>
+        where T: Store
+    {
+        Catalog::load(self.path())?.check::<T>()
+    }
+
+    /// Returns the schemas of the types entries of `collection` were written with, oldest first.
+    /// Types written before schemas were recorded are missing.
+    pub fn schemas(&self, collection: &str) -> Result<Vec<Schema>, Error> {
+        let catalog = Catalog::load(self.path())?;
+        let collection = catalog.collection(collection)?;
+        Ok(collection.hashes.iter().filter_map(|hash| collection.schemas.get(hash).cloned()).collect())
+    }
+
+    /// Reads the entry `name` of `collection` like `read_raw`.
+    pub(crate) fn read_raw_entry(&self, collection: &str, name: &str) -> Result<Value, Error> {
+        let catalog = Catalog::load(self.path())?;
+        self.read_entry_with(collection, name, |bytes| catalog.decode(collection, bytes))
+    }
+}
@@ -0,0 +1,240 @@
+use super::{Database, Bytes, Store, Error};
+use super::record;
+use super::catalog::CATALOG;
+use super::manifest::MANIFEST;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directory inside the database that `Repair::Quarantine` moves bad files to.
+pub(crate) const QUARANTINE: &str = ".quarantine";
+
+/// What `Database::check` does with the files it finds problems with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repair {
+    /// Leaves every file as it is.
+    Report,
+    /// Moves bad files to the `.quarantine` directory of the database, keeping their collection and name.
+    Quarantine,
+    /// Deletes bad files.
+    Delete
+}
+
+/// A problem found by `Database::check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// A file next to the collections, or a directory inside a collection. Never repaired.
+    Stray,
+    /// A temporary file left behind by an interrupted write.
+    Temporary,
+    /// An entry whose name isn't an encoded id.
+    InvalidName(String),
+    /// An entry that doesn't match its checksum.
+    Corrupted(String),
+    /// An entry stored with a schema hash the type of its collection doesn't know.
+    UnknownSchema(u64),
+    /// An entry that fails to decode as the type of its collection.
+    Undecodable(String),
+    /// An entry stored under another name than the encoding of its id. Never repaired,
+    /// `Database::upgrade_all` moves it.
+    Misnamed
+}
+
+impl Problem {
+    fn repairable(&self) -> bool {
+        !matches!(self, Problem::Stray | Problem::Misnamed)
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Stray => write!(f, "not part of any collection"),
+            Problem::Temporary => write!(f, "temporary file"),
+            Problem::InvalidName(reason) => write!(f, "invalid entry name: {}", reason),
+            Problem::Corrupted(reason) => write!(f, "corrupted: {}", reason),
+            Problem::UnknownSchema(hash) => write!(f, "unknown schema hash {:016x}", hash),
+            Problem::Undecodable(reason) => write!(f, "undecodable: {}", reason),
+            Problem::Misnamed => write!(f, "name doesn't match the id, run an upgrade")
+        }
+    }
+}
+
+/// A file with a problem, and what was done about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub problem: Problem,
+    /// `Repair::Report` if the file was left as it is.
+    pub action: Repair
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.problem)?;
+        match self.action {
+            Repair::Report => Ok(()),
+            Repair::Quarantine => write!(f, " (quarantined)"),
+            Repair::Delete => write!(f, " (deleted)")
+        }
+    }
+}
+
+/// The result of `Database::check`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    /// The number of entries checked.
+    pub entries: usize,
+    pub findings: Vec<Finding>
+}
+
+impl CheckReport {
+    /// Returns whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        write!(f, "{} entries checked, {} problems found", self.entries, self.findings.len())
+    }
+}
+
+/// Decodes the value of an entry named `name`, returning the problem if there is one.
+type Decode<'a> = &'a dyn Fn(&str, &[u8]) -> Option<Problem>;
+
+impl Database {
+    /// Checks every collection of the database without knowing the stored types.
+    ///
+    /// Reports files next to the collections other than the catalog and the manifest, temporary files, including those
+    /// left behind by writes of the catalog or the manifest, entries whose names aren't encoded ids
+    /// and entries that don't match their checksum, and handles the bad ones as given by `repair`.
+    /// Use `check_collection` to decode the entries of a collection too.
+    /// The database keeps no indexes, so there are no index entries to check.
+    /// Repairs fail on databases opened read-only.
+    pub fn check(&self, repair: Repair) -> Result<CheckReport, Error> {
+        self.check_except(repair, &[])
+    }
+
+    /// Checks the database like `check` does, leaving out the collections in `skipped`.
+    pub(crate) fn check_except(&self, repair: Repair, skipped: &[&str]) -> Result<CheckReport, Error> {
+        if repair != Repair::Report {
+            self.writable()?;
+        }
+        let mut report = CheckReport::default();
+        let paths = match fs::read_dir(self.path()) {
+            Ok(paths) => paths,
+            Err(_) => return Ok(report)
+        };
+        let mut paths = paths.map(|path| path.map(|path| path.path())).collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths {
+            let name = file_name(&path);
+            if name == QUARANTINE || name == CATALOG || name == MANIFEST {
+                continue;
+            }
+            if path.is_dir() {
+                if !skipped.contains(&name.as_str()) {
+                    self.check_directory(&name, repair, None, &mut report)?;
+                }
+            } else if name == format!("{}.tmp", CATALOG) || name == format!("{}.tmp", MANIFEST) {
+                let finding = self.catalog_locked(|| self.handle(&path, "", Problem::Temporary, repair))?;
+                report.findings.push(finding);
+            } else {
+                report.findings.push(Finding { path, problem: Problem::Stray, action: Repair::Report });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Checks the collection of `T`, like `check` does, and decodes every entry as `T`.
+    ///
+    /// Also reports entries whose names don't decode as an id of `T`, entries stored with a schema
+    /// hash `T` doesn't know, entries that fail to decode, and entries not stored under their id.
+    pub fn check_collection<T>(&self, repair: Repair) -> Result<CheckReport, Error>
+        where T: Store
+    {
+        if repair != Repair::Report {
+            self.writable()?;
+        }
+        let decode = |name: &str, bytes: &[u8]| {
+            if let Err(error) = Database::decode::<T::Id>(name) {
+                return Some(Problem::InvalidName(error.to_string()));
+            }
+            match T::deserialize_from(&mut &bytes[..]) {
+                Ok(object) => match Database::encode(object.id()) {
+                    Ok(encoded) if encoded == name => None,
+                    _ => Some(Problem::Misnamed)
+                },
+                Err(error) => match u64::deserialize_from(&mut &bytes[..]) {
+                    Ok(hash) if !T::hashes().contains(&hash) => Some(Problem::UnknownSchema(hash)),
+                    _ => Some(Problem::Undecodable(error.to_string()))
+                }
+            }
+        };
+        let mut report = CheckReport::default();
+        self.check_directory(T::NAME, repair, Some(&decode), &mut report)?;
+        Ok(report)
+    }
+
+    fn check_directory(&self, collection: &str, repair: Repair, decode: Option<Decode>, report: &mut CheckReport) -> Result<(), Error> {
+        let paths = match fs::read_dir(self.path().join(collection)) {
+            Ok(paths) => paths,
+            Err(_) => return Ok(())
+        };
+        let mut paths = paths.map(|path| path.map(|path| path.path())).collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        for path in paths {
+            let name = file_name(&path);
+            if path.is_dir() {
+                report.findings.push(Finding { path, problem: Problem::Stray, action: Repair::Report });
+                continue;
+            }
+            let key = format!("{}/{}", collection, name);
+            let finding = self.write_locked(&key, || {
+                let problem = if name.ends_with(".tmp") {
+                    Problem::Temporary
+                } else if let Err(error) = Database::decode_name(&name) {
+                    Problem::InvalidName(error.to_string())
+                } else {
+                    report.entries += 1;
+                    let bytes = fs::read(&path)?;
+                    let problem = match record::verify(&bytes, self.checksums()) {
+                        Ok(value) => decode.and_then(|decode| decode(&name, value)),
+                        Err(reason) => Some(Problem::Corrupted(reason))
+                    };
+                    match problem {
+                        Some(problem) => problem,
+                        None => return Ok(None)
+                    }
+                };
+                self.handle(&path, collection, problem, repair).map(Some)
+            })?;
+            report.findings.extend(finding);
+        }
+        Ok(())
+    }
+
+    /// Handles the file at `path` of `collection`, which has `problem`, as given by `repair`.
+    fn handle(&self, path: &Path, collection: &str, problem: Problem, repair: Repair) -> Result<Finding, Error> {
+        let action = if problem.repairable() { repair } else { Repair::Report };
+        match action {
+            Repair::Report => {},
+            Repair::Quarantine => {
+                let directory = self.path().join(QUARANTINE).join(collection);
+                fs::create_dir_all(&directory)?;
+                fs::rename(path, directory.join(file_name(path)))?;
+            },
+            Repair::Delete => fs::remove_file(path)?
+        }
+        Ok(Finding { path: path.to_path_buf(), problem, action })
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
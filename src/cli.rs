@@ -0,0 +1,498 @@
+//! The `database` command-line tool.
+//!
+//! The `database` binary knows no stored types, so it shows entries as their raw bytes and guesses
+//! what their ids are. Build your own tool with `Cli` and register your types to show them decoded:
+//!
+//! ```no_run
+//! # use database::{Bytes, Store, Cli};
+//! # #[derive(Bytes, Store, Debug)]
+//! # struct Person { #[id] name: String, age: u16 }
+//! fn main() {
+//!     let code = Cli::new().register::<Person>().run(std::env::args().skip(1));
+//!     std::process::exit(code);
+//! }
+//! ```
+
+use super::{Database, Options, Store, Bytes, Schema, Query, Format, Conflict, Repair, CheckReport, Error};
+use super::bytes::deserialize_str;
+use super::json;
+use super::shell::{self, Statement};
+use std::fmt::{Debug, Write as _};
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::marker::PhantomData;
+
+const USAGE: &str = "\
+Usage: database <command> <path> [arguments]
+
+<path> is the directory of an existing database.
+
+Commands:
+    collections <path>
+        Lists the collections of the database at <path> and their number of entries.
+    count <path> <collection>
+        Prints the number of entries of <collection>.
+    schema <path> <collection>
+        Shows the declaration of the type registered for <collection>, with its schema hash. Without
+        a registered type, shows every type the catalog of the database records for <collection>.
+    list <path> <collection>
+        Lists the entries of <collection> with their ids and the schema hashes they are stored with.
+        Ids are guessed to be strings or unsigned integers unless the type of <collection> is registered.
+    show <path> <collection> <entry>
+        Shows the value of the entry named <entry>. Values of unregistered types are shown as JSON if
+        the catalog of the database describes them, or else as bytes.
+    delete <path> <collection> <entry>...
+        Deletes the entries named <entry>.
+    export <path> <collection> (json | csv) [<file>]
+        Writes every entry of <collection> to <file>, or to the standard output.
+    import <path> <collection> (json | csv) [--skip | --overwrite] [<file>]
+        Reads entries from <file>, or from the standard input, and stores them. Fails without storing
+        anything if an id is taken, unless taken ids are skipped or overwritten.
+    query <path> <statement>...
+        Runs a statement like `select persons where age > 30 order by age limit 10`.
+        Statements other than `count` without conditions need the type of their collection to be registered.
+    shell <path>
+        Reads statements from the standard input until `exit`. Type `help` to list them.
+    check <path> [--quarantine | --delete]
+        Checks every collection for stray and temporary files, invalid entry names and entries
+        that don't match their checksum, and decodes the entries of registered types. Bad entries
+        are moved to <path>/.quarantine with --quarantine, or deleted with --delete.";
+
+/// The exit code of commands that succeed.
+const SUCCESS: i32 = 0;
+/// The exit code of commands that fail, or checks that find problems.
+const FAILURE: i32 = 1;
+/// The exit code of invalid command lines.
+const USAGE_ERROR: i32 = 2;
+
+/// The command-line tool, with the types it shows entries of.
+#[derive(Default)]
+pub struct Cli {
+    types: Vec<Box<dyn Registered>>
+}
+
+/// A type registered with `Cli::register`.
+trait Registered {
+    fn name(&self) -> &'static str;
+    fn schema(&self) -> Schema;
+    fn id(&self, bytes: &[u8]) -> Result<String, Error>;
+    fn value(&self, bytes: &[u8]) -> Result<String, Error>;
+    fn select(&self, database: &Database, query: &Query) -> Result<Vec<String>, Error>;
+    fn count(&self, database: &Database, query: &Query) -> Result<usize, Error>;
+    fn delete(&self, database: &Database, query: &Query) -> Result<usize, Error>;
+    fn export(&self, database: &Database, writer: &mut dyn Write, format: Format) -> Result<usize, Error>;
+    fn import(&self, database: &Database, reader: &mut dyn Read, format: Format, conflict: Conflict) -> Result<usize, Error>;
+    fn check_collection(&self, database: &Database, repair: Repair) -> Result<CheckReport, Error>;
+}
+
+struct Type<T>(PhantomData<fn() -> T>);
+
+impl<T> Registered for Type<T>
+    where T: Store + Debug, T::Id: Debug
+{
+    fn name(&self) -> &'static str {
+        T::NAME
+    }
+
+    fn schema(&self) -> Schema {
+        T::schema()
+    }
+
+    fn id(&self, bytes: &[u8]) -> Result<String, Error> {
+        Ok(format!("{:?}", T::Id::deserialize_from(&mut &bytes[..])?))
+    }
+
+    fn value(&self, bytes: &[u8]) -> Result<String, Error> {
+        Ok(format!("{:#?}", T::deserialize_from(&mut &bytes[..])?))
+    }
+
+    fn select(&self, database: &Database, query: &Query) -> Result<Vec<String>, Error> {
+        Ok(database.select::<T>(query)?.iter().map(|object| format!("{:#?}", object)).collect())
+    }
+
+    fn count(&self, database: &Database, query: &Query) -> Result<usize, Error> {
+        database.count::<T>(query)
+    }
+
+    fn delete(&self, database: &Database, query: &Query) -> Result<usize, Error> {
+        let objects = database.select::<T>(query)?;
+        for object in &objects {
+            database.delete::<T>(object.id())?;
+        }
+        Ok(objects.len())
+    }
+
+    fn export(&self, database: &Database, writer: &mut dyn Write, format: Format) -> Result<usize, Error> {
+        database.export::<T, _>(writer, format)
+    }
+
+    fn import(&self, database: &Database, reader: &mut dyn Read, format: Format, conflict: Conflict) -> Result<usize, Error> {
+        database.import::<T, _>(reader, format, conflict)
+    }
+
+    fn check_collection(&self, database: &Database, repair: Repair) -> Result<CheckReport, Error> {
+        database.check_collection::<T>(repair)
+    }
+}
+
+impl Cli {
+    /// Creates a tool that knows no types.
+    pub fn new() -> Cli {
+        Cli::default()
+    }
+
+    /// Registers `T`, so the entries of its collection are shown decoded.
+    pub fn register<T>(mut self) -> Cli
+        where T: Store + Debug + 'static, T::Id: Debug
+    {
+        self.types.retain(|registered| registered.name() != T::NAME);
+        self.types.push(Box::new(Type::<T>(PhantomData)));
+        self
+    }
+
+    /// Runs the command given by `args`, without the name of the binary, and returns the exit code.
+    pub fn run<I>(&self, args: I) -> i32
+        where I: IntoIterator<Item = String>
+    {
+        self.run_with(args, &mut io::stdin().lock(), &mut io::stdout(), &mut io::stderr())
+    }
+
+    /// Runs the command given by `args` like `run`, reading the statements of `shell` from `input`,
+    /// and writing its output to `out` and errors to `err`.
+    pub fn run_with<I>(&self, args: I, input: &mut dyn BufRead, out: &mut dyn Write, err: &mut dyn Write) -> i32
+        where I: IntoIterator<Item = String>
+    {
+        let args = args.into_iter().collect::<Vec<_>>();
+        let (command, path, args) = match args.as_slice() {
+            [command] if is_help(command) => {
+                let _ = writeln!(out, "{}", USAGE);
+                return SUCCESS;
+            },
+            [command, path, args @ ..] => (command.as_str(), path, args),
+            _ => return usage(err)
+        };
+        let options = Options::new().create_if_missing(false).read_only(!writes(command, args));
+        let database = match Database::open(path, options) {
+            Ok(database) => database,
+            Err(error) => {
+                let _ = writeln!(err, "{}", error);
+                return FAILURE;
+            }
+        };
+        let result = match (command, args) {
+            ("collections", []) => collections(&database, out),
+            ("count", [collection]) => count(&database, collection, out),
+            ("schema", [collection]) => self.schema(&database, collection, out),
+            ("list", [collection]) => self.list(&database, collection, out),
+            ("show", [collection, entry]) => self.show(&database, collection, entry, out),
+            ("delete", [collection, entries @ ..]) if !entries.is_empty() => delete(&database, collection, entries, out),
+            ("export", [collection, format, file @ ..]) if file.len() <= 1 => match self::format(format) {
+                Some(format) => self.export(&database, collection, format, file.first(), out),
+                None => return usage(err)
+            },
+            ("import", [collection, format, rest @ ..]) => match (self::format(format), import_arguments(rest)) {
+                (Some(format), Some((conflict, Some(file)))) => fs::File::open(file)
+                    .map_err(Error::from)
+                    .and_then(|mut file| self.import(&database, collection, format, conflict, &mut file, out)),
+                (Some(format), Some((conflict, None))) => self.import(&database, collection, format, conflict, input, out),
+                _ => return usage(err)
+            },
+            ("query", statement) if !statement.is_empty() => shell::parse(&statement.join(" "))
+                .and_then(|statement| self.execute(&database, statement, out))
+                .map(|_| SUCCESS),
+            ("shell", []) => self.shell(&database, input, out, err),
+            ("check", flags) => match repair(flags) {
+                Some(repair) => self.check(&database, repair, out),
+                None => return usage(err)
+            },
+            _ => return usage(err)
+        };
+        match result {
+            Ok(code) => code,
+            Err(error) => {
+                let _ = writeln!(err, "{}", error);
+                FAILURE
+            }
+        }
+    }
+
+    fn registered(&self, collection: &str) -> Option<&dyn Registered> {
+        self.types.iter().find(|registered| registered.name() == collection).map(Box::as_ref)
+    }
+
+    fn typed(&self, collection: &str) -> Result<&dyn Registered, Error> {
+        self.registered(collection)
+            .ok_or_else(|| Error::new(format!("No type is registered for collection \"{}\"", collection)))
+    }
+
+    fn schema(&self, database: &Database, collection: &str, out: &mut dyn Write) -> Result<i32, Error> {
+        match self.registered(collection) {
+            Some(registered) => writeln!(out, "{}", registered.schema())?,
+            None => for (i, schema) in database.schemas(collection)?.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "{}", schema)?;
+            }
+        }
+        Ok(SUCCESS)
+    }
+
+    /// Checks the collections of registered types with their type, and the others without.
+    fn check(&self, database: &Database, repair: Repair, out: &mut dyn Write) -> Result<i32, Error> {
+        let names = self.types.iter().map(|registered| registered.name()).collect::<Vec<_>>();
+        let mut report = database.check_except(repair, &names)?;
+        for registered in &self.types {
+            let checked = registered.check_collection(database, repair)?;
+            report.entries += checked.entries;
+            report.findings.extend(checked.findings);
+        }
+        report.findings.sort_by(|a, b| a.path.cmp(&b.path));
+        writeln!(out, "{}", report)?;
+        Ok(if report.is_ok() { SUCCESS } else { FAILURE })
+    }
+
+    fn export(&self, database: &Database, collection: &str, format: Format, file: Option<&String>, out: &mut dyn Write) -> Result<i32, Error> {
+        let registered = self.typed(collection)?;
+        match file {
+            Some(file) => {
+                let exported = registered.export(database, &mut fs::File::create(file)?, format)?;
+                writeln!(out, "Exported {} entries", exported)?;
+            },
+            None => {
+                registered.export(database, out, format)?;
+            }
+        }
+        Ok(SUCCESS)
+    }
+
+    fn import(&self, database: &Database, collection: &str, format: Format, conflict: Conflict, input: &mut dyn Read, out: &mut dyn Write) -> Result<i32, Error> {
+        let imported = self.typed(collection)?.import(database, input, format, conflict)?;
+        writeln!(out, "Imported {} entries", imported)?;
+        Ok(SUCCESS)
+    }
+
+    /// Runs the statements read from `input`, reporting failed ones to `err`.
+    fn shell(&self, database: &Database, input: &mut dyn BufRead, out: &mut dyn Write, err: &mut dyn Write) -> Result<i32, Error> {
+        let mut line = String::new();
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                return Ok(SUCCESS);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match shell::parse(&line).and_then(|statement| self.execute(database, statement, out)) {
+                Ok(true) => {},
+                Ok(false) => return Ok(SUCCESS),
+                Err(error) => writeln!(err, "{}", error)?
+            }
+        }
+    }
+
+    /// Runs a statement, returning whether the shell should go on.
+    fn execute(&self, database: &Database, statement: Statement, out: &mut dyn Write) -> Result<bool, Error> {
+        match statement {
+            Statement::Select(collection, query) => {
+                let records = self.typed(&collection)?.select(database, &query)?;
+                for record in &records {
+                    writeln!(out, "{}", record)?;
+                }
+                writeln!(out, "{} records", records.len())?;
+            },
+            Statement::Count(collection, query) => {
+                let count = match self.registered(&collection) {
+                    None if query == Query::new() => entries(database, &collection)?.len(),
+                    _ => self.typed(&collection)?.count(database, &query)?
+                };
+                writeln!(out, "{}", count)?;
+            },
+            Statement::Get(collection, query) => {
+                let records = self.typed(&collection)?.select(database, &query)?;
+                match records.first() {
+                    Some(record) => writeln!(out, "{}", record)?,
+                    None => return Err(Error::new(format!("No entry of \"{}\" has this id", collection)))
+                }
+            },
+            Statement::Delete(collection, query) => {
+                let deleted = self.typed(&collection)?.delete(database, &query)?;
+                writeln!(out, "Deleted {} entries", deleted)?;
+            },
+            Statement::Collections => {
+                collections(database, out)?;
+            },
+            Statement::Help => writeln!(out, "{}", shell::HELP)?,
+            Statement::Exit => return Ok(false)
+        }
+        Ok(true)
+    }
+
+    fn list(&self, database: &Database, collection: &str, out: &mut dyn Write) -> Result<i32, Error> {
+        let registered = self.registered(collection);
+        let current = registered.map(|registered| registered.schema().hash);
+        for name in entries(database, collection)? {
+            let id = match Database::decode_name(&name) {
+                Ok(bytes) => match registered {
+                    Some(registered) => registered.id(&bytes).unwrap_or_else(|error| format!("<{}>", error)),
+                    None => guess_id(&bytes)
+                },
+                Err(error) => format!("<{}>", error)
+            };
+            let schema = database.read_entry_with(collection, &name, |bytes| Ok(u64::deserialize_from(&mut &bytes[..]).ok()));
+            let schema = match schema {
+                Ok(Some(hash)) if current == Some(hash) => format!("{:016x} (current)", hash),
+                Ok(Some(hash)) => format!("{:016x}", hash),
+                Ok(None) => String::from("-"),
+                Err(error) => format!("<{}>", error)
+            };
+            writeln!(out, "{}  {}  schema {}", name, id, schema)?;
+        }
+        Ok(SUCCESS)
+    }
+
+    fn show(&self, database: &Database, collection: &str, entry: &str, out: &mut dyn Write) -> Result<i32, Error> {
+        let value = match self.registered(collection) {
+            Some(registered) => database.read_entry_with(collection, entry, |bytes| registered.value(bytes))?,
+            None => match database.read_raw_entry(collection, entry) {
+                Ok(value) => json::to_string(&value),
+                Err(_) => database.read_entry_with(collection, entry, |bytes| Ok(hex_dump(bytes)))?
+            }
+        };
+        writeln!(out, "{}", value)?;
+        Ok(SUCCESS)
+    }
+}
+
+fn is_help(arg: &str) -> bool {
+    matches!(arg, "help" | "--help" | "-h")
+}
+
+fn usage(err: &mut dyn Write) -> i32 {
+    let _ = writeln!(err, "{}", USAGE);
+    USAGE_ERROR
+}
+
+fn format(format: &str) -> Option<Format> {
+    match format {
+        "json" => Some(Format::Json),
+        "csv" => Some(Format::Csv),
+        _ => None
+    }
+}
+
+/// Returns the conflict mode and the file given to `import`.
+fn import_arguments(args: &[String]) -> Option<(Conflict, Option<&String>)> {
+    let mut conflict = Conflict::Fail;
+    let mut file = None;
+    for arg in args {
+        match arg.as_str() {
+            "--skip" if conflict == Conflict::Fail => conflict = Conflict::Skip,
+            "--overwrite" if conflict == Conflict::Fail => conflict = Conflict::Overwrite,
+            flag if flag.starts_with("--") => return None,
+            _ if file.is_none() => file = Some(arg),
+            _ => return None
+        }
+    }
+    Some((conflict, file))
+}
+
+fn repair(flags: &[String]) -> Option<Repair> {
+    match flags {
+        [] => Some(Repair::Report),
+        [flag] if flag == "--quarantine" => Some(Repair::Quarantine),
+        [flag] if flag == "--delete" => Some(Repair::Delete),
+        _ => None
+    }
+}
+
+fn collections(database: &Database, out: &mut dyn Write) -> Result<i32, Error> {
+    for collection in directories(database)? {
+        let entries = entries(database, &collection)?.len();
+        writeln!(out, "{}: {} entries", collection, entries)?;
+    }
+    Ok(SUCCESS)
+}
+
+fn count(database: &Database, collection: &str, out: &mut dyn Write) -> Result<i32, Error> {
+    writeln!(out, "{}", entries(database, collection)?.len())?;
+    Ok(SUCCESS)
+}
+
+fn delete(database: &Database, collection: &str, entries: &[String], out: &mut dyn Write) -> Result<i32, Error> {
+    for entry in entries {
+        database.delete_entry(collection, entry)?;
+        writeln!(out, "Deleted {}/{}", collection, entry)?;
+    }
+    Ok(SUCCESS)
+}
+
+/// Returns whether `command` with `args` changes the database, which is opened read-only otherwise.
+fn writes(command: &str, args: &[String]) -> bool {
+    match command {
+        "delete" | "import" | "shell" => true,
+        "query" => matches!(shell::parse(&args.join(" ")), Ok(Statement::Delete(..))),
+        "check" => !args.is_empty(),
+        _ => false
+    }
+}
+
+/// Returns the sorted names of the collections, leaving out the quarantine and other hidden directories.
+fn directories(database: &Database) -> Result<Vec<String>, Error> {
+    let mut names = Vec::new();
+    if let Ok(paths) = fs::read_dir(database.path()) {
+        for path in paths {
+            let path = path?.path();
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            if path.is_dir() && !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Returns the sorted names of the entries of `collection`, like `Database::entry_names`,
+/// failing if the collection doesn't exist.
+fn entries(database: &Database, collection: &str) -> Result<Vec<String>, Error> {
+    if !database.path().join(collection).is_dir() {
+        return Err(Error::new(format!("Collection \"{}\" doesn't exist", collection)));
+    }
+    database.entry_names(collection)
+}
+
+/// Shows an encoded id of an unknown type as a string if it is one, or else as an unsigned integer.
+fn guess_id(bytes: &[u8]) -> String {
+    let mut rest = bytes;
+    if let Ok(string) = deserialize_str(&mut rest) {
+        if rest.is_empty() {
+            return format!("{:?}", string);
+        }
+    }
+    if bytes.len() <= 16 && bytes.len().is_power_of_two() {
+        let mut value = [0; 16];
+        value[..bytes.len()].copy_from_slice(bytes);
+        return u128::from_le_bytes(value).to_string();
+    }
+    format!("0x{}", bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+}
+
+/// Shows `bytes` as rows of 16 hexadecimal bytes with their offsets.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut output = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        if row > 0 {
+            output.push('\n');
+        }
+        let _ = write!(output, "{:08x} ", row * 16);
+        for byte in chunk {
+            let _ = write!(output, " {:02x}", byte);
+        }
+    }
+    output
+}
+
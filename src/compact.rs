@@ -0,0 +1,362 @@
+use super::{Bytes, Layout, Error};
+use super::bytes::{serialize_varint, deserialize_varint, serialize_sorted_into, capacity, check_count};
+use std::collections::{HashMap, BTreeMap, HashSet, BTreeSet, VecDeque};
+use std::hash::{Hash, BuildHasher};
+use std::convert::TryFrom;
+
+/// The compact encoding used for fields marked with `#[varint]`.
+///
+/// Integers are stored as LEB128 varints, signed integers are zigzag encoded first so small
+/// negative values stay small. Collections store their length as varint and their elements
+/// in compact form. Types without a more compact encoding, like floats, strings or derived
+/// types, use their `Bytes` encoding.
+///
+/// The compact signature differs from the `Bytes` signature whenever the encoding does,
+/// so marking a field with `#[varint]` changes the schema hash of the containing type.
+pub trait Compact: Bytes {
+    /// Appends the compact encoding of the value to `bytes`.
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>);
+    /// Decodes a compactly encoded value from the front of `bytes` and advances it past the value.
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<Self, Error> where Self: Sized;
+    fn compact_signature() -> String;
+
+    /// Describes the compact encoding like `Bytes::layout` does the regular one.
+    /// Defaults to `Layout::Opaque` holding the compact signature.
+    fn compact_layout() -> Layout where Self: Sized {
+        Layout::Opaque(Self::compact_signature())
+    }
+
+    /// Returns the compact encoding of the value.
+    fn serialize_compact(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size_hint());
+        self.serialize_compact_into(&mut bytes);
+        bytes
+    }
+}
+
+fn deserialize_len(bytes: &mut &[u8]) -> Result<usize, Error> {
+    let len = deserialize_varint(bytes)?;
+    check_count(len, bytes)?;
+    usize::try_from(len).map_err(|_| Error::new(format!("Length {} is too large.", len)))
+}
+
+macro_rules! impl_Compact_for_unsigned {
+    ($($t:ty),+) => {
+        $(
+            impl Compact for $t {
+                fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+                    serialize_varint(*self as u128, bytes);
+                }
+
+                fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<$t, Error> {
+                    let value = deserialize_varint(bytes)?;
+                    <$t>::try_from(value).map_err(|_| Error::new(format!(
+                        "Value {} doesn't fit into {}.", value, stringify!($t)
+                    )))
+                }
+
+                fn compact_signature() -> String {
+                    format!("~{}", stringify!($t))
+                }
+
+                fn compact_layout() -> Layout {
+                    Layout::Scalar(format!("~{}", stringify!($t)))
+                }
+            }
+        )*
+    }
+}
+
+impl_Compact_for_unsigned!(u16, u32, u64, u128, usize);
+
+macro_rules! impl_Compact_for_signed {
+    ($($t:ty),+) => {
+        $(
+            impl Compact for $t {
+                fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+                    let value = *self as i128;
+                    serialize_varint(((value << 1) ^ (value >> 127)) as u128, bytes);
+                }
+
+                fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<$t, Error> {
+                    let zigzag = deserialize_varint(bytes)?;
+                    let value = (zigzag >> 1) as i128 ^ -((zigzag & 1) as i128);
+                    <$t>::try_from(value).map_err(|_| Error::new(format!(
+                        "Value {} doesn't fit into {}.", value, stringify!($t)
+                    )))
+                }
+
+                fn compact_signature() -> String {
+                    format!("~{}", stringify!($t))
+                }
+
+                fn compact_layout() -> Layout {
+                    Layout::Scalar(format!("~{}", stringify!($t)))
+                }
+            }
+        )*
+    }
+}
+
+impl_Compact_for_signed!(i16, i32, i64, i128, isize);
+
+// A varint wouldn't be any smaller for these types.
+macro_rules! impl_Compact_as_Bytes {
+    ($($t:ty),+) => {
+        $(
+            impl Compact for $t {
+                fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+                    self.serialize_into(bytes);
+                }
+
+                fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<$t, Error> {
+                    <$t>::deserialize_from(bytes)
+                }
+
+                fn compact_signature() -> String {
+                    <$t>::signature()
+                }
+
+                fn compact_layout() -> Layout {
+                    <$t>::layout()
+                }
+            }
+        )*
+    }
+}
+
+impl_Compact_as_Bytes!(u8, i8, bool, f32, f64, String, ());
+
+impl Compact for char {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        (*self as u32).serialize_compact_into(bytes);
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<char, Error> {
+        let value = u32::deserialize_compact_from(bytes)?;
+        char::from_u32(value).ok_or_else(|| Error::new(format!("Invalid char {:#x}.", value)))
+    }
+
+    fn compact_signature() -> String {
+        String::from("~char")
+    }
+
+    fn compact_layout() -> Layout {
+        Layout::Scalar(String::from("~char"))
+    }
+}
+
+macro_rules! impl_Compact_for_sequences {
+    ($($t:ident $(: $bound:ident)?),+) => {
+        $(
+            impl<S: Compact $(+ $bound)?> Compact for $t<S> {
+                fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+                    serialize_varint(self.len() as u128, bytes);
+                    for element in self {
+                        element.serialize_compact_into(bytes);
+                    }
+                }
+
+                fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<$t<S>, Error> {
+                    let len = deserialize_len(bytes)?;
+                    (0..len).map(|_| S::deserialize_compact_from(bytes)).collect()
+                }
+
+                fn compact_signature() -> String {
+                    format!("~{}<{}>", stringify!($t), S::compact_signature())
+                }
+
+                fn compact_layout() -> Layout {
+                    Layout::CompactSequence(Box::new(S::compact_layout()))
+                }
+            }
+        )*
+    }
+}
+
+impl_Compact_for_sequences!(Vec, VecDeque, BTreeSet: Ord);
+
+impl<S: Compact + Hash + Eq, H: BuildHasher + Default> Compact for HashSet<S, H> {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        serialize_varint(self.len() as u128, bytes);
+        serialize_sorted_into(self.iter(), bytes, Compact::serialize_compact_into);
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<HashSet<S, H>, Error> {
+        let len = deserialize_len(bytes)?;
+        let mut output = HashSet::with_capacity_and_hasher(capacity(len as u64, bytes), H::default());
+        for _ in 0..len {
+            output.insert(S::deserialize_compact_from(bytes)?);
+        }
+        Ok(output)
+    }
+
+    fn compact_signature() -> String {
+        format!("~HashSet<{}>", S::compact_signature())
+    }
+
+    fn compact_layout() -> Layout {
+        Layout::CompactSequence(Box::new(S::compact_layout()))
+    }
+}
+
+impl<K: Compact + Hash + Eq, V: Compact, H: BuildHasher + Default> Compact for HashMap<K, V, H> {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        serialize_varint(self.len() as u128, bytes);
+        serialize_sorted_into(self.iter(), bytes, |(key, value), bytes| {
+            key.serialize_compact_into(bytes);
+            value.serialize_compact_into(bytes);
+        });
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<HashMap<K, V, H>, Error> {
+        let len = deserialize_len(bytes)?;
+        let mut output = HashMap::with_capacity_and_hasher(capacity(len as u64, bytes), H::default());
+        for _ in 0..len {
+            output.insert(K::deserialize_compact_from(bytes)?, V::deserialize_compact_from(bytes)?);
+        }
+        Ok(output)
+    }
+
+    fn compact_signature() -> String {
+        format!("~HashMap<{},{}>", K::compact_signature(), V::compact_signature())
+    }
+
+    fn compact_layout() -> Layout {
+        Layout::CompactMap(Box::new(K::compact_layout()), Box::new(V::compact_layout()))
+    }
+}
+
+impl<K: Compact + Ord, V: Compact> Compact for BTreeMap<K, V> {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        serialize_varint(self.len() as u128, bytes);
+        for (key, value) in self {
+            key.serialize_compact_into(bytes);
+            value.serialize_compact_into(bytes);
+        }
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<BTreeMap<K, V>, Error> {
+        let len = deserialize_len(bytes)?;
+        let mut output = BTreeMap::new();
+        for _ in 0..len {
+            output.insert(K::deserialize_compact_from(bytes)?, V::deserialize_compact_from(bytes)?);
+        }
+        Ok(output)
+    }
+
+    fn compact_signature() -> String {
+        format!("~BTreeMap<{},{}>", K::compact_signature(), V::compact_signature())
+    }
+
+    fn compact_layout() -> Layout {
+        Layout::CompactMap(Box::new(K::compact_layout()), Box::new(V::compact_layout()))
+    }
+}
+
+impl<S: Compact, const N: usize> Compact for [S; N] {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        for element in self {
+            element.serialize_compact_into(bytes);
+        }
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<[S; N], Error> {
+        let mut output = Vec::with_capacity(N);
+        for _ in 0..N {
+            output.push(S::deserialize_compact_from(bytes)?);
+        }
+        Ok(<[S; N]>::try_from(output).unwrap_or_else(|_| unreachable!()))
+    }
+
+    fn compact_signature() -> String {
+        format!("~[{};{}]", S::compact_signature(), N)
+    }
+
+    fn compact_layout() -> Layout {
+        Layout::Array(Box::new(S::compact_layout()), N)
+    }
+}
+
+impl<S: Compact> Compact for Option<S> {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        self.is_some().serialize_into(bytes);
+        if let Some(value) = self {
+            value.serialize_compact_into(bytes);
+        }
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<Option<S>, Error> {
+        Ok(if bool::deserialize_from(bytes)? {
+            Some(S::deserialize_compact_from(bytes)?)
+        } else {
+            None
+        })
+    }
+
+    fn compact_signature() -> String {
+        format!("~Option<{}>", S::compact_signature())
+    }
+
+    fn compact_layout() -> Layout {
+        Layout::Option(Box::new(S::compact_layout()))
+    }
+}
+
+impl<S: Compact> Compact for Box<S> {
+    fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+        (**self).serialize_compact_into(bytes);
+    }
+
+    fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<Box<S>, Error> {
+        Ok(Box::new(S::deserialize_compact_from(bytes)?))
+    }
+
+    fn compact_signature() -> String {
+        format!("~Box<{}>", S::compact_signature())
+    }
+
+    fn compact_layout() -> Layout {
+        S::compact_layout()
+    }
+}
+
+macro_rules! impl_Compact_for_tuples {
+    ($(($($t:ident $i:tt),+)),+) => {
+        $(
+            impl<$($t: Compact),+> Compact for ($($t,)+) {
+                fn serialize_compact_into(&self, bytes: &mut Vec<u8>) {
+                    $(self.$i.serialize_compact_into(bytes);)+
+                }
+
+                fn deserialize_compact_from(bytes: &mut &[u8]) -> Result<($($t,)+), Error> {
+                    Ok(($($t::deserialize_compact_from(bytes)?,)+))
+                }
+
+                fn compact_signature() -> String {
+                    let signatures: &[String] = &[$($t::compact_signature()),+];
+                    format!("~({})", signatures.join(","))
+                }
+
+                fn compact_layout() -> Layout {
+                    Layout::Tuple(vec![$($t::compact_layout()),+])
+                }
+            }
+        )*
+    }
+}
+
+impl_Compact_for_tuples!(
+    (A 0),
+    (A 0, B 1),
+    (A 0, B 1, C 2),
+    (A 0, B 1, C 2, D 3),
+    (A 0, B 1, C 2, D 3, E 4),
+    (A 0, B 1, C 2, D 3, E 4, F 5),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10),
+    (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+);
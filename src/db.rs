@@ -0,0 +1,688 @@
+use super::{Error, Bytes, Store, View, Projection, Count, Options};
+use super::record;
+use super::catalog::{Catalog, CATALOG};
+use super::manifest::Manifest;
+use super::layout;
+use super::cache::Cache;
+use std::path::{Path, PathBuf};
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::prelude::*;
+use std::sync::Mutex;
+use std::sync::Condvar;
+use std::collections::{HashMap, HashSet};
+
+//const BASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE: &str = "0123456789abcdef";
+
+#[derive(Debug)]
+enum Operation {
+    Write,
+    Read(u32)
+}
+
+/// The `Database` struct contains everything used for a database.
+pub struct Database {
+    path: PathBuf,
+    blocked: (Mutex<HashMap<String, Operation>>, Condvar),
+    /// The collections and schema hashes known to be in the catalog.
+    recorded: Mutex<HashSet<(&'static str, u64)>>,
+    options: Options,
+    cache: Mutex<Cache>,
+    /// Whether the manifest records that every entry has a checksum header.
+    checksums: bool
+}
+
+impl Database {
+    /// Creates a new database. The data is stored in the "data" directory at the project root, which is created automatically if it doesn't exist already.
+    /// The directory isn't checked to be a database in a known format, use `Database::open` for that.
+    pub fn new<P>(path: P) -> Database
+        where P: AsRef<Path>
+    {
+        Database::with_options(path.as_ref(), Options::new(), false)
+    }
+
+    /// Opens the database at `path` with `options`.
+    ///
+    /// Creates the directory and writes the manifest if the database doesn't exist or is an empty directory,
+    /// unless `options` forbid it. Directories of databases written before manifests were added, which hold
+    /// a catalog or collections of entries, get one unless opened read-only. Other directories aren't databases.
+    /// Fails with `ErrorKind::UnsupportedFormat` if the manifest records an on-disk format this version doesn't read.
+    ///
+    /// Entries of databases created by `open` always have a checksum header, so entries without one are
+    /// reported as corrupted, while other databases may hold entries written before checksums were added.
+    pub fn open<P>(path: P, options: Options) -> Result<Database, Error>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref();
+        let manifest = match Manifest::load(path)? {
+            Some(manifest) => {
+                manifest.check(path)?;
+                manifest
+            },
+            None if path.is_dir() && Database::holds_database(path)? => {
+                let manifest = Manifest::adopted();
+                if !options.read_only {
+                    manifest.write(path, options.durability)?;
+                }
+                manifest
+            },
+            None if path.is_dir() && fs::read_dir(path)?.next().is_some() => {
+                return Err(Error::new(format!("\"{}\" isn't a database", path.display())));
+            },
+            None if path.exists() && !path.is_dir() => return Err(Error::new(format!("\"{}\" isn't a directory", path.display()))),
+            None if options.create_if_missing && !options.read_only => {
+                let manifest = Manifest::current();
+                manifest.write(path, options.durability)?;
+                manifest
+            },
+            None => return Err(Error::new(format!("The database \"{}\" doesn't exist", path.display())))
+        };
+        Ok(Database::with_options(path, options, manifest.checksums()))
+    }
+
+    /// Returns whether the directory at `path` holds a database written before manifests were added,
+    /// which has a catalog or a collection directory holding entries.
+    fn holds_database(path: &Path) -> Result<bool, Error> {
+        if path.join(CATALOG).exists() {
+            return Ok(true);
+        }
+        for collection in fs::read_dir(path)? {
+            let collection = collection?;
+            let name = collection.file_name();
+            match name.to_str() {
+                Some(name) if !name.starts_with('.') && collection.path().is_dir() => {},
+                _ => continue
+            }
+            for entry in fs::read_dir(collection.path())? {
+                let entry = entry?;
+                let is_entry = entry.file_name().to_str()
+                    .is_some_and(|name| !name.is_empty() && Database::decode_name(name).is_ok());
+                if is_entry && entry.path().is_file() {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn with_options(path: &Path, options: Options, checksums: bool) -> Database {
+        Database {
+            path: path.to_path_buf(),
+            blocked: Default::default(),
+            recorded: Default::default(),
+            cache: Mutex::new(Cache::new(options.cache_size)),
+            options,
+            checksums
+        }
+    }
+    /*
+    fn encode<I>(id: &I) -> Result<String, Error>
+        where I: Bytes
+    {
+        let mut output = String::new();
+        let mut sextets = Vec::<u8>::new();
+        let bytes = id.serialize();
+        
+        for (i, &byte) in bytes.iter().enumerate() {
+            match i % 3 {
+                0 => {
+                    sextets.push(byte & 0b00111111);
+                    sextets.push((byte & 0b11000000) >> 6);
+                },
+                1 => {
+                    let last = sextets.pop().unwrap();
+                    sextets.push(last | ((byte & 0b00001111) << 2));
+                    sextets.push((byte & 0b11110000) >> 4);
+                },
+                2 => {
+                    let last = sextets.pop().unwrap();
+                    sextets.push(last | ((byte & 0b00000011) << 4));
+                    sextets.push((byte & 0b11111100) >> 2);
+                },
+                _ => unreachable!()
+            }
+        };
+        if sextets.len() > 128 {
+            return Err(Error::new(format!("Id exceeds maximum length")));
+        }
+        for &sextet in &sextets {
+            output.push(BASE.chars().skip(sextet as usize).next().expect("Alphabet out of range"));
+        }
+
+        Ok(output)
+    }
+    
+    fn decode<I>(string: &str) -> Result<I, Error>
+        where I: Bytes
+    {
+        let mut bytes = Vec::<u8>::new();
+        
+        for (i, c) in string.chars().enumerate() {
+            let byte = BASE.find(c).ok_or_else(|| Error::new(format!("Invalid character")))? as u8;
+            match i % 4 {
+                0 => {
+                    bytes.push(byte);
+                },
+                1 => {
+                    let last = bytes.pop().unwrap();
+                    bytes.push(last | (byte << 6));
+                    bytes.push(byte >> 2);
+                },
+                2 => {
+                    let last = bytes.pop().unwrap();
+                    bytes.push(last | (byte << 4));
+                    bytes.push(byte >> 4);
+                },
+                3 => {
+                    let last = bytes.pop().unwrap();
+                    bytes.push(last | (byte << 2));
+                },
+                _ => unreachable!()
+            }
+        };
+
+        bytes.reverse();
+        Ok(I::deserialize(&mut bytes)?)
+    }
+    */
+
+    pub(crate) fn encode<I>(id: &I) -> Result<String, Error>
+        where I: Bytes
+    {
+        Database::encode_name(&id.serialize())
+    }
+
+    /// Returns the entry name of an encoded id.
+    pub(crate) fn encode_name(bytes: &[u8]) -> Result<String, Error> {
+        let mut output = String::new();
+        for byte in bytes {
+            output.push(BASE.chars().nth((byte / BASE.len() as u8) as usize).unwrap());
+            output.push(BASE.chars().nth((byte % BASE.len() as u8) as usize).unwrap());
+
+            if output.len() > 128 {
+                return Err(Error::new(String::from("Id is too long.")));
+            }
+        }
+
+        if output.is_empty() {
+            return Err(Error::new(String::from("Id is too short.")));
+        }
+
+        Ok(output)
+    }
+    
+    pub(crate) fn decode<I>(string: &str) -> Result<I, Error>
+        where I: Bytes
+    {
+        I::deserialize_from(&mut &Database::decode_name(string)?[..])
+    }
+
+    /// Returns the encoded id an entry name stands for.
+    pub(crate) fn decode_name(string: &str) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::<u8>::new();
+        let mut chars = string.chars();
+
+        while let Some(c1) = chars.next() {
+            let c2 = chars.next().ok_or_else(|| Error::new(String::from("Invalid entry name.")))?;
+
+            let b1 = BASE.find(c1).ok_or_else(|| Error::new(String::from("Invalid character in entry name.")))? as u8;
+            let b2 = BASE.find(c2).ok_or_else(|| Error::new(String::from("Invalid character in entry name.")))? as u8;
+        
+            bytes.push(b1 * 16 + b2);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Returns the directory of the database.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns whether every entry has to start with a checksum header.
+    pub(crate) fn checksums(&self) -> bool {
+        self.checksums
+    }
+
+    /// Runs `f` while the catalog isn't written.
+    pub(crate) fn catalog_locked<R, F>(&self, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        let _recorded = self.recorded.lock().unwrap();
+        f()
+    }
+
+    /// Fails if the database is opened read-only.
+    pub(crate) fn writable(&self) -> Result<(), Error> {
+        if self.options.read_only {
+            return Err(Error::read_only(&self.path));
+        }
+        Ok(())
+    }
+
+    /// Runs `f` while holding the write lock of `key`, the collection name and entry name joined by `/`.
+    /// The cached value of the entry is dropped, since `f` may change its file.
+    pub(crate) fn write_locked<R, F>(&self, key: &str, f: F) -> R
+        where F: FnOnce() -> R
+    {
+        // Acquire lock.
+        let (lock, condvar) = &self.blocked;
+        let mut guard = lock.lock().unwrap();
+        // Wait while key is blocked.
+        while (*guard).contains_key(key) {
+            guard = condvar.wait(guard).unwrap();
+        }
+        // If key isn't locked, insert it into the locked set and release lock.
+        guard.insert(String::from(key), Operation::Write);
+        drop(guard);
+
+        let output = f();
+        self.cache.lock().unwrap().remove(key);
+
+        // Acquire lock again and remove key from blocked list.
+        let mut guard = lock.lock().unwrap();
+        guard.remove(key);
+        drop(guard);
+        condvar.notify_all();
+
+        output
+    }
+
+    pub fn exists<T>(&self, object: &T) -> Result<bool, Error>
+        where T: Store
+    {
+        let key = format!("{}/{}", T::NAME, self.entry_name::<T>(object.id())?);
+        let path = self.path.clone().join(&key);
+        Ok(path.exists())
+    }
+
+    /// Returns the name of the entry stored under `id` in the id encoding used before strings were
+    /// length prefixed, if there is one and no entry is stored under the current name.
+    /// Reads, updates and deletes find such entries until `upgrade_all` moves them.
+    fn legacy_entry<T>(&self, id: &T::Id) -> Result<Option<String>, Error>
+        where T: Store
+    {
+        let legacy = match T::Id::layout().legacy() {
+            Some(legacy) => legacy,
+            None => return Ok(None)
+        };
+        let directory = self.path.join(T::NAME);
+        if directory.join(Database::encode(id)?).exists() {
+            return Ok(None);
+        }
+        let mut encoded = Vec::new();
+        layout::encode(&legacy, &id.to_value(), &mut encoded)?;
+        let name = Database::encode_name(&encoded)?;
+        Ok(Some(name).filter(|name| directory.join(name).exists()))
+    }
+
+    /// Returns the name of the entry stored under `id`, the encoded id unless it is stored under a legacy name.
+    pub(crate) fn entry_name<T>(&self, id: &T::Id) -> Result<String, Error>
+        where T: Store
+    {
+        match self.legacy_entry::<T>(id)? {
+            Some(name) => Ok(name),
+            None => Database::encode(id)
+        }
+    }
+
+    /// Records the layouts of `T` in the catalog, unless they are known to be there already.
+    fn record_layout<T>(&self) -> Result<(), Error>
+        where T: Store
+    {
+        let mut recorded = self.recorded.lock().unwrap();
+        if !recorded.contains(&(T::NAME, T::hash())) {
+            Catalog::update(&self.path, self.options.durability, |catalog| catalog.record::<T>())?;
+            recorded.insert((T::NAME, T::hash()));
+        }
+        Ok(())
+    }
+
+    /// Drops the schema hashes no entry of `collection` has anymore from the catalog, so that
+    /// `register` accepts types that don't read the layouts entries were upgraded from.
+    fn prune_schemas(&self, collection: &str) -> Result<(), Error> {
+        let mut present = HashSet::new();
+        for name in self.entry_names(collection)? {
+            if let Some(hash) = self.read_entry_with(collection, &name, |bytes| Ok(u64::deserialize_from(&mut &bytes[..]).ok()))? {
+                present.insert(hash);
+            }
+        }
+        let mut recorded = self.recorded.lock().unwrap();
+        Catalog::update(&self.path, self.options.durability, |catalog| catalog.retain(collection, &present))?;
+        recorded.retain(|(name, _)| *name != collection);
+        Ok(())
+    }
+
+    /// Returns the id after the largest id of the entries of `T`.
+    /// Files whose names aren't ids of `T` are skipped, and left for `check` to report.
+    fn next_id<T>(&self) -> Result<T::Id, Error>
+        where T: Store, T::Id: Count
+    {
+        let mut output = Default::default();
+        for encoded in self.entry_names(T::NAME)? {
+            if let Ok(value) = Database::decode::<T::Id>(&encoded) {
+                if value >= output {
+                    output = value.next();
+                }
+            }
+        }
+        Ok(output)
+    }
+
+    fn create_id<T>(&self, object: &T, id: &T::Id) -> Result<(), Error>
+        where T: Store
+    {
+        let key = format!("{}/{}", T::NAME, Database::encode(id)?);
+        let path = self.path.clone().join(&key);
+        let mut directory = path.clone();
+        directory.pop();
+        self.writable()?;
+        self.record_layout::<T>()?;
+
+        self.write_locked(&key, || if path.exists() {
+            // Return error if file exists.
+            Err(Error::new(format!("Entry \"{}\" already exists", key)))
+        } else {
+            // Do the create.
+            if !directory.exists() {
+                fs::create_dir_all(&directory)?;
+                self.options.durability.sync_directory(&self.path)?;
+            }
+            let mut file = File::create(path)?;
+            file.write_all(&record::seal(object))?;
+            file.flush()?;
+            self.options.durability.sync_file(&file)?;
+            self.options.durability.sync_directory(&directory)?;
+            Ok(())
+        })
+    }
+
+    pub fn create_auto<T>(&self, object: &T) -> Result<(), Error>
+        where T: Store, T::Id: Count
+    {
+        self.create_id(object, &self.next_id::<T>()?)
+    }
+    
+    /// Creates an entry in the database.
+    pub fn create<T>(&self, object: &T) -> Result<(), Error>
+        where T: Store
+    {
+        if let Some(name) = self.legacy_entry::<T>(object.id())? {
+            return Err(Error::new(format!("Entry \"{}/{}\" already exists", T::NAME, name)));
+        }
+        self.create_id(object, object.id())
+    }
+
+    fn read_encoded<T>(&self, encoded: String) -> Result<T, Error>
+        where T: Store
+    {
+        self.read_encoded_with::<T, _, _>(encoded, |bytes| T::deserialize_from(&mut &bytes[..]))
+    }
+
+    /// Reads the encoded value of an entry and passes it to `decode` while holding the read lock.
+    /// Fails with `ErrorKind::Corrupted` if the entry doesn't match its checksum.
+    fn read_encoded_with<T, R, F>(&self, encoded: String, decode: F) -> Result<R, Error>
+        where T: Store, F: FnOnce(&[u8]) -> Result<R, Error>
+    {
+        self.read_entry_with(T::NAME, &encoded, decode)
+    }
+
+    /// Returns the key of the entry `name` of `collection`, the collection name and entry name joined by `/`.
+    /// Fails if either names something other than an entry inside the database, such as a parent directory.
+    fn entry_key(collection: &str, name: &str) -> Result<String, Error> {
+        if collection.is_empty() || collection.starts_with('.') || collection.contains(['/', '\\']) {
+            return Err(Error::new(format!("Invalid collection name \"{}\"", collection)));
+        }
+        if name.is_empty() || Database::decode_name(name).is_err() {
+            return Err(Error::new(format!("Invalid entry name \"{}\"", name)));
+        }
+        Ok(format!("{}/{}", collection, name))
+    }
+
+    /// Returns the sorted names of the entry files of `collection`, leaving out temporary files, directories
+    /// and files whose names aren't entry names.
+    pub(crate) fn entry_names(&self, collection: &str) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+        if let Ok(paths) = fs::read_dir(self.path.join(collection)) {
+            for path in paths {
+                let path = path?.path();
+                match path.file_name().and_then(|name| name.to_str()) {
+                    Some(name) if path.is_file() && Database::entry_key(collection, name).is_ok() => names.push(String::from(name)),
+                    _ => {}
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Reads the encoded value of the entry `name` of `collection`, like `read_encoded_with`.
+    pub(crate) fn read_entry_with<R, F>(&self, collection: &str, name: &str, decode: F) -> Result<R, Error>
+        where F: FnOnce(&[u8]) -> Result<R, Error>
+    {
+        let key = Database::entry_key(collection, name)?;
+        let path = self.path.clone().join(&key);
+
+        // Acquire lock.
+        let (lock, condvar) = &self.blocked;
+        let mut guard = lock.lock().unwrap();
+
+        // Wait while key is blocked.
+        let mut readers = 0;
+        while match (*guard).get(&key) {
+            Some(operation) => match operation {
+                Operation::Read(r) => {
+                    readers = *r;
+                    false
+                },
+                Operation::Write => true
+            },
+            None => false
+        } {
+            guard = condvar.wait(guard).unwrap();
+        }
+        // If key isn't locked, insert it into the locked set and release lock.
+        guard.insert(key.clone(), Operation::Read(readers + 1));
+        drop(guard);
+
+        let cached = self.cache.lock().unwrap().get(&key);
+        let output = (|| if let Some(value) = cached {
+            decode(&value)
+        } else if !path.exists() {
+            // Return error if file doesn't exist.
+            Err(Error::new(format!("Entry \"{}\" doesn't exist", key)))
+        } else {
+            // Do the read
+            let bytes = fs::read(&path)?;
+            let value = record::open(&bytes, &path, self.checksums)?;
+            if self.options.cache_size > 0 {
+                self.cache.lock().unwrap().insert(&key, value);
+            }
+            decode(value)
+        })();
+        
+        // Acquire lock again and decrease readers.
+        let mut guard = lock.lock().unwrap();
+        let updated_readers = match (*guard).get(&key) {
+            Some(operation) => match operation {
+                Operation::Read(r) => *r,
+                Operation::Write => panic!("Operation is write but was reading")
+            },
+            None => panic!("Key not found but should be there")
+        } - 1;
+        if updated_readers == 0 {
+            guard.remove(&key);
+        } else {
+            guard.insert(key, Operation::Read(updated_readers));
+        }
+        drop(guard);
+        condvar.notify_all();
+
+        output
+    }
+
+    /// Reads an entry from the database.
+    pub fn read<T>(&self, id: &T::Id) -> Result<T, Error>
+        where T: Store
+    {        
+        self.read_encoded(self.entry_name::<T>(id)?)
+    }
+
+    /// Reads an entry without decoding it into an owned value, passing its view to `f`.
+    /// The view borrows from the bytes read, so strings and vectors aren't copied.
+    pub fn read_with<T, R>(&self, id: &T::Id, f: impl FnOnce(T::Borrowed<'_>) -> R) -> Result<R, Error>
+        where T: Store + View
+    {
+        self.read_encoded_with::<T, _, _>(self.entry_name::<T>(id)?, |bytes| Ok(f(T::view_from(&mut &bytes[..])?)))
+    }
+
+    /// Reads some of the fields of an entry into the projection `P`, skipping over the others.
+    pub fn read_fields<P>(&self, id: &<P::Source as Store>::Id) -> Result<P, Error>
+        where P: Projection, P::Source: Store
+    {
+        self.read_encoded_with::<P::Source, _, _>(self.entry_name::<P::Source>(id)?, |bytes| P::project_from(&mut &bytes[..]))
+    }
+
+    /// Reads some of the fields of all entries into the projection `P`.
+    pub fn read_all_fields<P>(&self) -> Result<Vec<P>, Error>
+        where P: Projection, P::Source: Store
+    {
+        let mut result = Vec::new();
+        for encoded in self.entry_names(<P::Source as Store>::NAME)? {
+            result.push(self.read_encoded_with::<P::Source, _, _>(encoded, |bytes| P::project_from(&mut &bytes[..]))?);
+        }
+
+        Ok(result)
+    }
+
+    /// Reads all entries from the database.
+    pub fn read_all<T>(&self) -> Result<Vec<T>, Error>
+        where T: Store
+    {
+        let mut result = Vec::new();
+        for encoded in self.entry_names(T::NAME)? {
+            result.push(self.read_encoded(encoded)?);
+        }
+
+        Ok(result)
+    }
+    
+    /// Updates an entry in the database.
+    /// Entries stored under a legacy name are moved to the name of their id.
+    pub fn update<T>(&self, object: &T) -> Result<(), Error>
+        where T: Store
+    {
+        let key = format!("{}/{}", T::NAME, Database::encode(object.id())?);
+        let path = self.path.clone().join(&key);
+        let mut directory = path.clone();
+        directory.pop();
+        self.writable()?;
+        if let Some(legacy) = self.legacy_entry::<T>(object.id())? {
+            self.create_id(object, object.id())?;
+            return self.delete_encoded::<T>(legacy);
+        }
+        self.record_layout::<T>()?;
+
+        self.write_locked(&key, || if !path.exists() {
+            // Return error if file doesn't exist.
+            Err(Error::new(format!("Entry \"{}\" doesn't exist", key)))
+        } else {
+            // Do the update.
+            if !directory.exists() {
+                fs::create_dir_all(directory)?;
+            }
+            let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+            file.write_all(&record::seal(object))?;
+            file.flush()?;
+            self.options.durability.sync_file(&file)?;
+            Ok(())
+        })
+    }
+
+    /// Rewrites every entry of a collection in the current format of `T`.
+    /// Entries that were decoded using `#[from(...)]` or `#[default]` are stored with the current
+    /// schema, and entries stored under a name from an older id encoding are moved to the name of their id,
+    /// unless an entry is stored under that name already, which was written later and is kept.
+    /// Entries written before checksums were added get one. Temporary files and files whose names aren't
+    /// entry names are left for `check` to report. Afterwards the catalog only keeps the schema hashes
+    /// entries still have, so types without `#[from(...)]` for the older ones can be registered.
+    pub fn upgrade_all<T>(&self) -> Result<(), Error>
+        where T: Store
+    {
+        self.writable()?;
+        for encoded in self.entry_names(T::NAME)? {
+            let object: T = self.read_encoded(encoded.clone())?;
+            let name = Database::encode(object.id())?;
+            if name == encoded {
+                self.update(&object)?;
+            } else {
+                if !self.path.join(T::NAME).join(&name).exists() {
+                    self.create_id(&object, object.id())?;
+                }
+                self.delete_encoded::<T>(encoded)?;
+            }
+        }
+
+        self.prune_schemas(T::NAME)
+    }
+
+    /// Tries to create an entry, updates it if it already exists.
+    pub fn create_or_update<T>(&self, object: &T) -> Result<(), Error>
+        where T: Store
+    {
+        if self.exists(object)? {
+            self.update(object)?;
+        } else {
+            self.create(object)?;
+        }
+
+        Ok(())
+    }
+
+    fn delete_encoded<T>(&self, encoded: String) -> Result<(), Error>
+        where T: Store
+    {
+        self.delete_entry(T::NAME, &encoded)
+    }
+
+    /// Deletes the entry `name` of `collection`.
+    pub(crate) fn delete_entry(&self, collection: &str, name: &str) -> Result<(), Error> {
+        let key = Database::entry_key(collection, name)?;
+        let path = self.path.clone().join(&key);
+        self.writable()?;
+
+        self.write_locked(&key, || if !path.exists() {
+            // Return error if file doesn't exist.
+            Err(Error::new(format!("Entry \"{}\" doesn't exist", key)))
+        } else {
+            // Do the delete.
+            fs::remove_file(path)?;
+            self.options.durability.sync_directory(&self.path.join(collection))?;
+            Ok(())
+        })
+    }
+
+    /// Deletes an entry from the database.
+    pub fn delete<T>(&self, id: &T::Id) -> Result<(), Error>
+        where T: Store
+    {
+        self.delete_encoded::<T>(self.entry_name::<T>(id)?)
+    }
+
+    /// Delete all entries from the database.
+    /// Temporary files and files whose names aren't entry names are left for `check` to report.
+    /// The schema hashes of the deleted entries are dropped from the catalog.
+    pub fn delete_all<T>(&self) -> Result<(), Error>
+        where T: Store
+    {
+        self.writable()?;
+        for encoded in self.entry_names(T::NAME)? {
+            self.delete_encoded::<T>(encoded)?;
+        }
+
+        self.prune_schemas(T::NAME)
+    }
+
+}
\ No newline at end of file
@@ -1,17 +1,78 @@
 use std::{fmt, io, error};
+use std::path::{Path, PathBuf};
 
 /// Used to propagate database errors.
 #[derive(Debug)]
 pub struct Error {
+    kind: ErrorKind,
     description: String
 }
 
+/// The kind of an `Error`, for errors callers may want to handle on their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An entry file doesn't match its checksum, because of a partial write or damaged storage.
+    Corrupted(PathBuf),
+    /// A type doesn't read the entries of its collection, which were written by another type.
+    /// Holds the name of the collection.
+    Incompatible(String),
+    /// The database is in an on-disk format this version doesn't read, going by its manifest.
+    UnsupportedFormat,
+    /// A write to a database opened with `Options::read_only`.
+    ReadOnly,
+    /// Every other error.
+    Other
+}
+
 impl Error {
     pub fn new(description: String) -> Error {
         Error {
+            kind: ErrorKind::Other,
+            description
+        }
+    }
+
+    /// Creates an error for the corrupted entry file at `path`.
+    pub fn corrupted<P>(path: P, reason: String) -> Error
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref().to_path_buf();
+        Error {
+            description: format!("Entry file \"{}\" is corrupted: {}.", path.display(), reason),
+            kind: ErrorKind::Corrupted(path)
+        }
+    }
+
+    /// Creates an error for a type that doesn't read the entries of `collection`.
+    pub fn incompatible(collection: &str, description: String) -> Error {
+        Error {
+            kind: ErrorKind::Incompatible(String::from(collection)),
             description
         }
     }
+
+    /// Creates an error for a database in an on-disk format this version doesn't read.
+    pub fn unsupported_format(description: String) -> Error {
+        Error {
+            kind: ErrorKind::UnsupportedFormat,
+            description
+        }
+    }
+
+    /// Creates an error for a write to the read-only database at `path`.
+    pub fn read_only<P>(path: P) -> Error
+        where P: AsRef<Path>
+    {
+        Error {
+            kind: ErrorKind::ReadOnly,
+            description: format!("The database \"{}\" is opened read-only.", path.as_ref().display())
+        }
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
 }
 
 impl fmt::Display for Error {
@@ -30,4 +91,4 @@ impl From<io::Error> for Error {
     fn from(error: io::Error) -> Error {
         Error::new(format!("IO Error: {}", error))
     }
-}
\ No newline at end of file
+}
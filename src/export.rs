@@ -0,0 +1,209 @@
+use super::{Database, Store, Query, Value, Error};
+use super::json;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// The formats of `Database::export` and `Database::import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// An array holding an object per record, one record per line.
+    Json,
+    /// A header with the stored fields of the type, as listed in `Bytes::FIELDS`, followed by a row
+    /// per record. Nested values are written as JSON, and missing values as empty cells.
+    Csv
+}
+
+/// What `Database::import` does with records whose id is taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    /// Keeps the stored record.
+    Skip,
+    /// Replaces the stored record.
+    Overwrite,
+    /// Fails before importing anything.
+    Fail
+}
+
+impl Database {
+    /// Writes every entry of `T` to `writer`, ordered by their entry names, and returns their number.
+    pub fn export<T, W>(&self, mut writer: W, format: Format) -> Result<usize, Error>
+        where T: Store, W: Write
+    {
+        let records = self.select::<T>(&Query::new())?;
+        let mut output = String::new();
+        match format {
+            Format::Json => {
+                output.push('[');
+                for (i, record) in records.iter().enumerate() {
+                    output.push_str(if i == 0 { "\n" } else { ",\n" });
+                    json::write(&record.to_value(), &mut output);
+                }
+                output.push_str(if records.is_empty() { "]\n" } else { "\n]\n" });
+            },
+            Format::Csv => {
+                if T::FIELDS.is_empty() {
+                    return Err(Error::new(format!("Collection \"{}\" can't be exported as CSV, its type has no fields.", T::NAME)));
+                }
+                write_row(T::FIELDS.iter().map(|name| Some(String::from(*name))), &mut output);
+                for record in &records {
+                    let value = record.to_value();
+                    write_row(T::FIELDS.iter().map(|name| value.field(name).and_then(cell)), &mut output);
+                }
+            }
+        }
+        writer.write_all(output.as_bytes())?;
+        Ok(records.len())
+    }
+
+    /// Reads records of `T` from `reader` and stores them, returning the number of records stored.
+    /// Every record is read before the first is stored, so invalid input doesn't import anything.
+    /// Input holding several records with the same id is invalid.
+    pub fn import<T, R>(&self, mut reader: R, format: Format, conflict: Conflict) -> Result<usize, Error>
+        where T: Store, R: Read
+    {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        let values = match format {
+            Format::Json => match json::parse(&input)? {
+                Value::List(values) => values,
+                _ => return Err(Error::new(String::from("Expected a JSON array of records.")))
+            },
+            Format::Csv => read_rows(&input)?
+        };
+        let records = values.iter()
+            .enumerate()
+            .map(|(i, value)| T::from_value(value).map_err(|error| Error::new(format!("Record {}: {}", i + 1, error))))
+            .collect::<Result<Vec<T>, _>>()?;
+        let mut names = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            if let Some(first) = names.insert(Database::encode(record.id())?, i) {
+                return Err(Error::new(format!("Records {} and {} have the same id.", first + 1, i + 1)));
+            }
+        }
+
+        if conflict == Conflict::Fail {
+            for record in &records {
+                if self.exists(record)? {
+                    return Err(Error::new(format!("Entry \"{}/{}\" already exists", T::NAME, Database::encode(record.id())?)));
+                }
+            }
+        }
+        let mut stored = 0;
+        for record in &records {
+            if conflict == Conflict::Skip && self.exists(record)? {
+                continue;
+            }
+            self.create_or_update(record)?;
+            stored += 1;
+        }
+        Ok(stored)
+    }
+}
+
+/// Returns the text of the CSV cell holding `value`, or `None` for an empty cell.
+fn cell(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(string) => Some(string.clone()),
+        Value::Float(float) if !float.is_finite() => Some(float.to_string()),
+        Value::Variant(name, fields) if **fields == Value::Null => Some(name.clone()),
+        value => Some(json::to_string(value))
+    }
+}
+
+/// Appends a row of cells, quoting cells that hold separators or quotes, and empty strings.
+fn write_row<I>(cells: I, output: &mut String)
+    where I: Iterator<Item = Option<String>>
+{
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        match cell {
+            None => {},
+            Some(cell) if cell.is_empty() || cell.contains([',', '"', '\n', '\r']) => {
+                output.push('"');
+                output.push_str(&cell.replace('"', "\"\""));
+                output.push('"');
+            },
+            Some(cell) => output.push_str(&cell)
+        }
+    }
+    output.push('\n');
+}
+
+/// Reads the rows of a CSV document after its header as maps from the names in the header to the cells.
+/// Empty cells that aren't quoted become `Value::Null`, the others `Value::String`.
+fn read_rows(input: &str) -> Result<Vec<Value>, Error> {
+    let mut rows = parse_csv(input)?.into_iter();
+    let header = match rows.next() {
+        Some(header) => header.into_iter().map(Option::unwrap_or_default).collect::<Vec<_>>(),
+        None => return Ok(Vec::new())
+    };
+    rows.enumerate()
+        .map(|(i, row)| {
+            if row.len() != header.len() {
+                return Err(Error::new(format!("Row {} has {} cells but the header has {}.", i + 2, row.len(), header.len())));
+            }
+            Ok(Value::Map(header.iter()
+                .zip(row)
+                .map(|(name, cell)| (Value::String(name.clone()), cell.map_or(Value::Null, Value::String)))
+                .collect()))
+        })
+        .collect()
+}
+
+/// Splits a CSV document into rows of cells, `None` for empty cells that aren't quoted.
+/// Empty lines are left out.
+fn parse_csv(input: &str) -> Result<Vec<Vec<Option<String>>>, Error> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut chars = input.chars().peekable();
+    loop {
+        // Read one cell.
+        let mut cell = String::new();
+        let mut quoted = false;
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            quoted = true;
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        cell.push('"');
+                    },
+                    Some('"') => break,
+                    Some(c) => cell.push(c),
+                    None => return Err(Error::new(format!("Unterminated quoted cell in row {}.", rows.len() + 1)))
+                }
+            }
+        }
+        while let Some(&c) = chars.peek() {
+            if c == ',' || c == '\n' || c == '\r' {
+                break;
+            }
+            if quoted {
+                return Err(Error::new(format!("Unexpected character after a quoted cell in row {}.", rows.len() + 1)));
+            }
+            cell.push(c);
+            chars.next();
+        }
+        row.push(if cell.is_empty() && !quoted { None } else { Some(cell) });
+
+        match chars.next() {
+            Some(',') => {},
+            end => {
+                if end == Some('\r') && chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+                if row.len() > 1 || row[0].is_some() {
+                    rows.push(row);
+                }
+                row = Vec::new();
+                if end.is_none() {
+                    return Ok(rows);
+                }
+            }
+        }
+    }
+}
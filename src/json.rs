@@ -0,0 +1,277 @@
+//! Reading and writing `Value`s as JSON, used by `Database::export` and `Database::import`.
+//!
+//! Structs become objects, enum variants without fields their name and other variants an object
+//! holding the fields under the name. Maps with keys other than strings use the JSON of their keys
+//! as object keys. Floats that aren't finite are written as the strings `"NaN"`, `"inf"` and `"-inf"`.
+
+use super::{Value, Error};
+use std::fmt::Write;
+
+/// Appends `value` as JSON to `output`.
+pub(crate) fn write(value: &Value, output: &mut String) {
+    match value {
+        Value::Null => output.push_str("null"),
+        Value::Bool(value) => output.push_str(if *value { "true" } else { "false" }),
+        Value::Int(value) => { let _ = write!(output, "{}", value); },
+        Value::UInt(value) => { let _ = write!(output, "{}", value); },
+        Value::Float(value) if value.is_finite() => { let _ = write!(output, "{:?}", value); },
+        Value::Float(value) => write_string(&value.to_string(), output),
+        Value::String(value) => write_string(value, output),
+        Value::List(elements) => {
+            output.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write(element, output);
+            }
+            output.push(']');
+        },
+        Value::Map(entries) => {
+            output.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                match key {
+                    Value::String(key) => write_string(key, output),
+                    key => write_string(&to_string(key), output)
+                }
+                output.push(':');
+                write(value, output);
+            }
+            output.push('}');
+        },
+        Value::Struct(fields) => {
+            output.push('{');
+            for (i, (name, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write_string(name, output);
+                output.push(':');
+                write(value, output);
+            }
+            output.push('}');
+        },
+        Value::Variant(name, fields) if **fields == Value::Null => write_string(name, output),
+        Value::Variant(name, fields) => {
+            output.push('{');
+            write_string(name, output);
+            output.push(':');
+            write(fields, output);
+            output.push('}');
+        },
+        Value::Bytes(bytes) => write(&Value::List(bytes.iter().map(|&byte| Value::from(byte)).collect()), output)
+    }
+}
+
+/// Returns `value` as JSON.
+pub(crate) fn to_string(value: &Value) -> String {
+    let mut output = String::new();
+    write(value, &mut output);
+    output
+}
+
+fn write_string(string: &str, output: &mut String) {
+    output.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(output, "\\u{:04x}", c as u32); },
+            c => output.push(c)
+        }
+    }
+    output.push('"');
+}
+
+/// How deeply arrays and objects may be nested, so that documents can't exhaust the stack.
+const MAX_DEPTH: usize = 128;
+
+/// Parses a JSON document. Objects become `Value::Map`s with string keys, and numbers
+/// `Value::UInt`, `Value::Int` or `Value::Float` depending on their sign and fraction.
+/// Fails if arrays and objects are nested more than `MAX_DEPTH` levels deep.
+pub(crate) fn parse(input: &str) -> Result<Value, Error> {
+    let mut parser = Parser { input: input.as_bytes(), position: 0, depth: 0 };
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.position < parser.input.len() {
+        return Err(parser.error("the end of the document"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    position: usize,
+    /// The number of arrays and objects the parser is inside of.
+    depth: usize
+}
+
+impl Parser<'_> {
+    fn error(&self, expected: &str) -> Error {
+        Error::new(format!("Invalid JSON at byte {}, expected {}.", self.position, expected))
+    }
+
+    fn whitespace(&mut self) {
+        while self.position < self.input.len() && self.input[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.whitespace();
+        self.input.get(self.position).copied()
+    }
+
+    fn consume(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Steps into an array or object, failing if that nests them too deeply.
+    fn enter(&mut self) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::new(format!("Invalid JSON at byte {}, arrays and objects are nested too deeply.", self.position)));
+        }
+        self.position += 1;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, Error> {
+        if self.input[self.position..].starts_with(keyword.as_bytes()) {
+            self.position += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("a value"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some(b'n') => self.keyword("null", Value::Null),
+            Some(b't') => self.keyword("true", Value::Bool(true)),
+            Some(b'f') => self.keyword("false", Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b'[') => {
+                self.enter()?;
+                let mut elements = Vec::new();
+                if !self.consume(b']') {
+                    loop {
+                        elements.push(self.value()?);
+                        if self.consume(b']') {
+                            break;
+                        }
+                        if !self.consume(b',') {
+                            return Err(self.error("`,` or `]`"));
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Ok(Value::List(elements))
+            },
+            Some(b'{') => {
+                self.enter()?;
+                let mut entries = Vec::new();
+                if !self.consume(b'}') {
+                    loop {
+                        if self.peek() != Some(b'"') {
+                            return Err(self.error("a key"));
+                        }
+                        let key = self.string()?;
+                        if !self.consume(b':') {
+                            return Err(self.error("`:`"));
+                        }
+                        entries.push((Value::String(key), self.value()?));
+                        if self.consume(b'}') {
+                            break;
+                        }
+                        if !self.consume(b',') {
+                            return Err(self.error("`,` or `}`"));
+                        }
+                    }
+                }
+                self.depth -= 1;
+                Ok(Value::Map(entries))
+            },
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            _ => Err(self.error("a value"))
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Error> {
+        let start = self.position;
+        while self.position < self.input.len() && b"+-0123456789.eE".contains(&self.input[self.position]) {
+            self.position += 1;
+        }
+        let number = std::str::from_utf8(&self.input[start..self.position]).unwrap_or_default();
+        let invalid = || Error::new(format!("Invalid JSON number {}.", number));
+        if number.contains(['.', 'e', 'E']) {
+            number.parse().map(Value::Float).map_err(|_| invalid())
+        } else if number.starts_with('-') {
+            number.parse().map(Value::Int).map_err(|_| invalid())
+        } else {
+            number.parse().map(Value::UInt).map_err(|_| invalid())
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        // Skip the opening quote.
+        self.position += 1;
+        let mut output = Vec::new();
+        loop {
+            let byte = *self.input.get(self.position).ok_or_else(|| self.error("the end of the string"))?;
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.input.get(self.position).ok_or_else(|| self.error("an escape sequence"))?;
+                    self.position += 1;
+                    match escaped {
+                        b'"' | b'\\' | b'/' => output.push(escaped),
+                        b'b' => output.push(0x08),
+                        b'f' => output.push(0x0c),
+                        b'n' => output.push(b'\n'),
+                        b'r' => output.push(b'\r'),
+                        b't' => output.push(b'\t'),
+                        b'u' => {
+                            let mut code = self.hex()?;
+                            // Characters outside the basic plane are written as surrogate pairs.
+                            if (0xd800..0xdc00).contains(&code) && self.input[self.position..].starts_with(b"\\u") {
+                                self.position += 2;
+                                let low = self.hex()?;
+                                if !(0xdc00..=0xdfff).contains(&low) {
+                                    return Err(self.error("a low surrogate"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            let c = char::from_u32(code).ok_or_else(|| self.error("a valid character"))?;
+                            output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        },
+                        _ => return Err(self.error("an escape sequence"))
+                    }
+                },
+                byte => output.push(byte)
+            }
+        }
+        String::from_utf8(output).map_err(|_| self.error("UTF-8"))
+    }
+
+    fn hex(&mut self) -> Result<u32, Error> {
+        let digits = self.input.get(self.position..self.position + 4).ok_or_else(|| self.error("four hex digits"))?;
+        let code = std::str::from_utf8(digits).ok()
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("four hex digits"))?;
+        self.position += 4;
+        Ok(code)
+    }
+}
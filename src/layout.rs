@@ -0,0 +1,374 @@
+//! Descriptions of how values are encoded, so entries can be decoded without their types.
+//!
+//! `Bytes::layout` describes the encoding of a type, and the database records the layouts of the
+//! entries it writes in its catalog. `Decoder` and `encode` turn encoded values into `Value`s and back
+//! following a layout, looking up older layouts of nested types in the catalog by their hash.
+
+use super::{Bytes, Compact, Value, Error, LegacyString};
+use super::bytes::{serialize_varint, deserialize_varint, deserialize_count, check_count};
+use super::value::{structured, variant, unexpected};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// The stored fields of a struct or a variant with their layouts, in declaration order.
+pub type FieldLayouts = Vec<(String, Layout)>;
+
+/// How a type is encoded, returned by `Bytes::layout` and recorded in the catalog of the database.
+#[derive(Bytes, Debug, Clone, PartialEq)]
+pub enum Layout {
+    /// A type implemented by this crate that decodes on its own, named like `u32` or `IpAddr`.
+    /// Integers and chars in the compact encoding of `Compact` are named with a leading `~`, like `~u32`.
+    Scalar(String),
+    /// A length followed by the elements, like a `Vec` or a set.
+    Sequence(Box<Layout>),
+    /// A sequence in the compact encoding, with its length as varint.
+    CompactSequence(Box<Layout>),
+    /// A length followed by the keys and values.
+    Map(Box<Layout>, Box<Layout>),
+    /// A map in the compact encoding, with its length as varint.
+    CompactMap(Box<Layout>, Box<Layout>),
+    /// A fixed number of elements.
+    Array(Box<Layout>, usize),
+    Tuple(Vec<Layout>),
+    Option(Box<Layout>),
+    Result(Box<Layout>, Box<Layout>),
+    /// A derived struct, stored as its hash followed by its stored fields.
+    Struct {
+        name: String,
+        hash: u64,
+        fields: FieldLayouts
+    },
+    /// A derived enum, stored as its hash, the index of the variant and its fields.
+    /// Unit variants have no fields at all, as opposed to an empty list.
+    Enum {
+        name: String,
+        hash: u64,
+        variants: Vec<(String, Option<FieldLayouts>)>
+    },
+    /// The derived type with this hash that contains itself, laid out by the enclosing layout.
+    Recursive(u64),
+    /// A type that doesn't describe its encoding, named by its signature.
+    /// Values of such types can't be decoded without the type.
+    Opaque(String)
+}
+
+impl Layout {
+    /// Calls `f` with this layout and every layout of a derived type nested in it.
+    pub(crate) fn walk<'a>(&'a self, f: &mut dyn FnMut(&'a Layout)) {
+        f(self);
+        match self {
+            Layout::Sequence(element) | Layout::CompactSequence(element) | Layout::Array(element, _) | Layout::Option(element) => element.walk(f),
+            Layout::Map(first, second) | Layout::CompactMap(first, second) | Layout::Result(first, second) => {
+                first.walk(f);
+                second.walk(f);
+            },
+            Layout::Tuple(elements) => elements.iter().for_each(|element| element.walk(f)),
+            Layout::Struct { fields, .. } => fields.iter().for_each(|(_, field)| field.walk(f)),
+            Layout::Enum { variants, .. } => variants.iter()
+                .flat_map(|(_, fields)| fields.iter().flatten())
+                .for_each(|(_, field)| field.walk(f)),
+            Layout::Scalar(_) | Layout::Recursive(_) | Layout::Opaque(_) => {}
+        }
+    }
+
+    /// Returns the layout values had before strings were length prefixed, if it differs from this one.
+    /// `Database` finds entries stored under ids in that encoding until `upgrade_all` moves them.
+    /// Layouts holding derived types are left out, since their hashes changed along with the encoding.
+    pub(crate) fn legacy(&self) -> Option<Layout> {
+        fn within(layout: &Layout, changed: &mut bool) -> Option<Layout> {
+            let mut boxed = |layout: &Layout| within(layout, changed).map(Box::new);
+            Some(match layout {
+                Layout::Scalar(name) if name == "String" || name == "PathBuf" => {
+                    *changed = true;
+                    Layout::Scalar(String::from("LegacyString"))
+                },
+                Layout::Scalar(_) => layout.clone(),
+                Layout::Sequence(element) => Layout::Sequence(boxed(element)?),
+                Layout::CompactSequence(element) => Layout::CompactSequence(boxed(element)?),
+                Layout::Array(element, length) => Layout::Array(boxed(element)?, *length),
+                Layout::Option(element) => Layout::Option(boxed(element)?),
+                Layout::Map(key, value) => Layout::Map(boxed(key)?, boxed(value)?),
+                Layout::CompactMap(key, value) => Layout::CompactMap(boxed(key)?, boxed(value)?),
+                Layout::Result(ok, err) => Layout::Result(boxed(ok)?, boxed(err)?),
+                Layout::Tuple(elements) => Layout::Tuple(elements.iter().map(|element| within(element, changed)).collect::<Option<_>>()?),
+                Layout::Struct { .. } | Layout::Enum { .. } | Layout::Recursive(_) | Layout::Opaque(_) => return None
+            })
+        }
+        let mut changed = false;
+        within(self, &mut changed).filter(|_| changed)
+    }
+}
+
+thread_local! {
+    static LAYOUTS_IN_PROGRESS: std::cell::RefCell<Vec<&'static str>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Computes the layout of a derived type, used by the derive macros.
+/// A type that contains itself refers back to itself by `Layout::Recursive` instead of recursing forever.
+#[doc(hidden)]
+pub fn guard_layout<T, F>(layout: F) -> Layout
+    where T: Bytes, F: FnOnce() -> Layout
+{
+    let key = std::any::type_name::<T>();
+    if LAYOUTS_IN_PROGRESS.with(|in_progress| in_progress.borrow().contains(&key)) {
+        return Layout::Recursive(T::hash());
+    }
+    LAYOUTS_IN_PROGRESS.with(|in_progress| in_progress.borrow_mut().push(key));
+    let output = layout();
+    LAYOUTS_IN_PROGRESS.with(|in_progress| in_progress.borrow_mut().pop());
+    output
+}
+
+// Scalars are decoded and encoded by their own implementations, through their `Value`s.
+macro_rules! scalars {
+    ($($name:literal => $t:ty),+; $($compact:literal => $c:ty),+) => {
+        fn decode_scalar(name: &str, bytes: &mut &[u8]) -> Result<Value, Error> {
+            match name {
+                $($name => <$t as Bytes>::deserialize_from(bytes).map(|value| value.to_value()),)+
+                $($compact => <$c as Compact>::deserialize_compact_from(bytes).map(|value| value.to_value()),)+
+                name => Err(Error::new(format!("Unknown scalar type {}.", name)))
+            }
+        }
+
+        fn encode_scalar(name: &str, value: &Value, bytes: &mut Vec<u8>) -> Result<(), Error> {
+            match name {
+                $($name => <$t as Bytes>::from_value(value)?.serialize_into(bytes),)+
+                $($compact => <$c as Bytes>::from_value(value)?.serialize_compact_into(bytes),)+
+                name => return Err(Error::new(format!("Unknown scalar type {}.", name)))
+            }
+            Ok(())
+        }
+    }
+}
+
+scalars!(
+    "()" => (), "bool" => bool, "char" => char,
+    "u8" => u8, "u16" => u16, "u32" => u32, "u64" => u64, "u128" => u128, "usize" => u64,
+    "i8" => i8, "i16" => i16, "i32" => i32, "i64" => i64, "i128" => i128, "isize" => i64,
+    "f32" => f32, "f64" => f64, "String" => String, "LegacyString" => LegacyString,
+    "Duration" => Duration, "SystemTime" => SystemTime, "PathBuf" => PathBuf,
+    "Ipv4Addr" => Ipv4Addr, "Ipv6Addr" => Ipv6Addr, "IpAddr" => IpAddr, "SocketAddr" => SocketAddr;
+    "~u16" => u16, "~u32" => u32, "~u64" => u64, "~u128" => u128, "~usize" => u64,
+    "~i16" => i16, "~i32" => i32, "~i64" => i64, "~i128" => i128, "~isize" => i64, "~char" => char
+);
+
+/// Returns the innermost of the `enclosing` layouts of derived types with hash `hash`.
+fn enclosing<'a>(enclosing: &[&'a Layout], hash: u64) -> Result<&'a Layout, Error> {
+    enclosing.iter()
+        .rev()
+        .find(|layout| matches!(layout, Layout::Struct { hash: current, .. } | Layout::Enum { hash: current, .. } if *current == hash))
+        .copied()
+        .ok_or_else(|| Error::new(format!("No enclosing type has the hash {:016x}.", hash)))
+}
+
+/// Decodes values following their layouts.
+pub(crate) struct Decoder<'a> {
+    /// Layouts of derived types by their hash, used for values stored with an older layout.
+    known: &'a BTreeMap<u64, Layout>,
+    /// The layouts of the derived types being decoded, for `Layout::Recursive`.
+    enclosing: Vec<&'a Layout>
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(known: &'a BTreeMap<u64, Layout>) -> Decoder<'a> {
+        Decoder { known, enclosing: Vec::new() }
+    }
+
+    /// Returns the layout of the derived type with hash `hash`, which is `layout` or an older one.
+    fn resolve(&self, layout: &'a Layout, hash: u64) -> Result<&'a Layout, Error> {
+        match layout {
+            Layout::Struct { hash: current, .. } | Layout::Enum { hash: current, .. } if *current == hash => Ok(layout),
+            Layout::Struct { name, .. } | Layout::Enum { name, .. } => self.known.get(&hash)
+                .ok_or_else(|| Error::new(format!("Unknown schema hash {:016x} of {}.", hash, name))),
+            _ => unreachable!()
+        }
+    }
+
+    /// Decodes a value laid out as `layout` from the front of `bytes` and advances it past the value.
+    pub(crate) fn decode(&mut self, layout: &'a Layout, bytes: &mut &[u8]) -> Result<Value, Error> {
+        match layout {
+            Layout::Scalar(name) => decode_scalar(name, bytes),
+            Layout::Sequence(element) => {
+                let len = deserialize_count(bytes)?;
+                (0..len).map(|_| self.decode(element, bytes)).collect::<Result<_, _>>().map(Value::List)
+            },
+            Layout::CompactSequence(element) => {
+                let len = deserialize_varint(bytes)?;
+                check_count(len, bytes)?;
+                (0..len).map(|_| self.decode(element, bytes)).collect::<Result<_, _>>().map(Value::List)
+            },
+            Layout::Map(key, value) | Layout::CompactMap(key, value) => {
+                let len = match layout {
+                    Layout::Map(_, _) => u64::deserialize_from(bytes)? as u128,
+                    _ => deserialize_varint(bytes)?
+                };
+                check_count(len, bytes)?;
+                (0..len)
+                    .map(|_| Ok((self.decode(key, bytes)?, self.decode(value, bytes)?)))
+                    .collect::<Result<_, _>>()
+                    .map(Value::Map)
+            },
+            Layout::Array(element, len) => (0..*len).map(|_| self.decode(element, bytes)).collect::<Result<_, _>>().map(Value::List),
+            Layout::Tuple(elements) => elements.iter().map(|element| self.decode(element, bytes)).collect::<Result<_, _>>().map(Value::List),
+            Layout::Option(inner) => if bool::deserialize_from(bytes)? {
+                self.decode(inner, bytes)
+            } else {
+                Ok(Value::Null)
+            },
+            Layout::Result(ok, err) => if bool::deserialize_from(bytes)? {
+                Ok(Value::Variant(String::from("Ok"), Box::new(self.decode(ok, bytes)?)))
+            } else {
+                Ok(Value::Variant(String::from("Err"), Box::new(self.decode(err, bytes)?)))
+            },
+            Layout::Struct { .. } | Layout::Enum { .. } => {
+                let hash = u64::deserialize_from(bytes)?;
+                let layout = self.resolve(layout, hash)?;
+                self.enclosing.push(layout);
+                let value = self.decode_fields(layout, bytes);
+                self.enclosing.pop();
+                value
+            },
+            Layout::Recursive(hash) => self.decode(enclosing(&self.enclosing, *hash)?, bytes),
+            Layout::Opaque(signature) => Err(Error::new(format!("Values of type {} can't be decoded without the type.", signature)))
+        }
+    }
+
+    /// Decodes the fields of a derived type after its hash.
+    fn decode_fields(&mut self, layout: &'a Layout, bytes: &mut &[u8]) -> Result<Value, Error> {
+        let mut struct_value = |fields: &'a [(String, Layout)], bytes: &mut &[u8]| fields.iter()
+            .map(|(name, field)| Ok((name.clone(), self.decode(field, bytes)?)))
+            .collect::<Result<_, Error>>()
+            .map(Value::Struct);
+        match layout {
+            Layout::Struct { fields, .. } => struct_value(fields, bytes),
+            Layout::Enum { name, variants, .. } => {
+                let index = u32::deserialize_from(bytes)?;
+                let (variant_name, fields) = variants.get(index as usize)
+                    .ok_or_else(|| Error::new(format!("Invalid variant {} of {}.", index, name)))?;
+                let fields = match fields {
+                    Some(fields) => struct_value(fields, bytes)?,
+                    None => Value::Null
+                };
+                Ok(Value::Variant(variant_name.clone(), Box::new(fields)))
+            },
+            // `resolve` only returns layouts of derived types, and catalogs only hold those.
+            _ => Err(Error::new(String::from("Expected the layout of a derived type.")))
+        }
+    }
+}
+
+/// Appends `value` encoded as laid out by `layout`, the reverse of `Decoder::decode`.
+/// Derived types are encoded in their current layout.
+pub(crate) fn encode(layout: &Layout, value: &Value, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    encode_within(layout, value, bytes, &mut Vec::new())
+}
+
+fn encode_within<'a>(layout: &'a Layout, value: &Value, bytes: &mut Vec<u8>, enclosing: &mut Vec<&'a Layout>) -> Result<(), Error> {
+    let list = |value: &Value| match &*structured(value)? {
+        Value::List(elements) => Ok(elements.clone()),
+        value => Err(unexpected("a list", value))
+    };
+    let entries = |value: &Value| match &*structured(value)? {
+        Value::Map(entries) => Ok(entries.clone()),
+        value => Err(unexpected("a map", value))
+    };
+    match layout {
+        Layout::Scalar(name) => encode_scalar(name, value, bytes)?,
+        Layout::Sequence(element) | Layout::CompactSequence(element) => {
+            let elements = list(value)?;
+            match layout {
+                Layout::Sequence(_) => (elements.len() as u64).serialize_into(bytes),
+                _ => serialize_varint(elements.len() as u128, bytes)
+            }
+            for element_value in &elements {
+                encode_within(element, element_value, bytes, enclosing)?;
+            }
+        },
+        Layout::Map(key, value_layout) | Layout::CompactMap(key, value_layout) => {
+            let entries = entries(value)?;
+            match layout {
+                Layout::Map(_, _) => (entries.len() as u64).serialize_into(bytes),
+                _ => serialize_varint(entries.len() as u128, bytes)
+            }
+            for (key_value, entry_value) in &entries {
+                encode_within(key, key_value, bytes, enclosing)?;
+                encode_within(value_layout, entry_value, bytes, enclosing)?;
+            }
+        },
+        Layout::Array(element, len) => {
+            let elements = list(value)?;
+            if elements.len() != *len {
+                return Err(Error::new(format!("Expected {} elements but found {}.", len, elements.len())));
+            }
+            for element_value in &elements {
+                encode_within(element, element_value, bytes, enclosing)?;
+            }
+        },
+        Layout::Tuple(layouts) => {
+            let elements = list(value)?;
+            if elements.len() != layouts.len() {
+                return Err(Error::new(format!("Expected {} elements but found {}.", layouts.len(), elements.len())));
+            }
+            for (element, element_value) in layouts.iter().zip(&elements) {
+                encode_within(element, element_value, bytes, enclosing)?;
+            }
+        },
+        Layout::Option(inner) => {
+            (*value != Value::Null).serialize_into(bytes);
+            if *value != Value::Null {
+                encode_within(inner, value, bytes, enclosing)?;
+            }
+        },
+        Layout::Result(ok, err) => {
+            let value = structured(value)?;
+            match variant(&value)? {
+                ("Ok", value) => {
+                    true.serialize_into(bytes);
+                    encode_within(ok, value, bytes, enclosing)?;
+                },
+                ("Err", value) => {
+                    false.serialize_into(bytes);
+                    encode_within(err, value, bytes, enclosing)?;
+                },
+                (name, _) => return Err(Error::new(format!("Unknown variant {} of Result.", name)))
+            }
+        },
+        Layout::Struct { name, hash, fields } => {
+            let value = structured(value)?;
+            hash.serialize_into(bytes);
+            enclosing.push(layout);
+            for (field, field_layout) in fields {
+                let field_value = value.field(field).ok_or_else(|| Error::new(format!("Missing field {} of {}.", field, name)))?;
+                encode_within(field_layout, field_value, bytes, enclosing)?;
+            }
+            enclosing.pop();
+        },
+        Layout::Enum { name, hash, variants } => {
+            let value = structured(value)?;
+            let (variant_name, fields_value) = variant(&value)?;
+            let (index, (_, fields)) = variants.iter()
+                .enumerate()
+                .find(|(_, (name, _))| name == variant_name)
+                .ok_or_else(|| Error::new(format!("Unknown variant {} of {}.", variant_name, name)))?;
+            hash.serialize_into(bytes);
+            (index as u32).serialize_into(bytes);
+            enclosing.push(layout);
+            let fields_value = structured(fields_value)?;
+            for (field, field_layout) in fields.iter().flatten() {
+                let field_value = fields_value.field(field)
+                    .ok_or_else(|| Error::new(format!("Missing field {} of {}::{}.", field, name, variant_name)))?;
+                encode_within(field_layout, field_value, bytes, enclosing)?;
+            }
+            enclosing.pop();
+        },
+        Layout::Recursive(hash) => {
+            let layout = self::enclosing(enclosing, *hash)?;
+            encode_within(layout, value, bytes, enclosing)?;
+        },
+        Layout::Opaque(signature) => return Err(Error::new(format!("Values of type {} can't be encoded without the type.", signature)))
+    }
+    Ok(())
+}
+
@@ -1,26 +1,73 @@
-mod database;
+// Lets the derive macros refer to this crate as `::database` from within it too.
+extern crate self as database;
+
+mod db;
 mod store;
 mod bytes;
+mod compact;
+mod view;
+mod projection;
+mod value;
+mod schema;
+mod layout;
+mod catalog;
+mod manifest;
+mod options;
+mod cache;
+mod json;
+mod query;
+mod export;
+#[cfg(feature = "serde")]
+mod with_serde;
+mod record;
+mod check;
+mod cli;
+mod shell;
 mod count;
 mod error;
 
-pub use crate::database::Database;
+pub use db::Database;
+pub use options::{Options, Durability};
 pub use store::Store;
-pub use bytes::Bytes;
+pub use bytes::{Bytes, LegacyString};
+pub use compact::Compact;
+pub use view::{View, Sequence, SequenceIter};
+pub use projection::Projection;
+pub use value::Value;
+pub use schema::{Schema, Field, Variant};
+pub use layout::{Layout, FieldLayouts};
+pub use query::{Query, Filter, Comparison, Direction};
+pub use export::{Format, Conflict};
+pub use check::{Repair, Problem, Finding, CheckReport};
+pub use cli::Cli;
+#[cfg(feature = "serde")]
+pub use with_serde::{Serde, as_bytes};
+#[doc(hidden)]
+pub use bytes::guard_signature;
+#[doc(hidden)]
+pub use layout::guard_layout;
+#[doc(hidden)]
+pub use value::{structured, variant};
 pub use count::Count;
-pub use error::Error;
+pub use error::{Error, ErrorKind};
 pub use store_derive::Store;
 pub use bytes_derive::Bytes;
 
 #[cfg(test)]
 mod tests {
-    use super::{Database, Store, Bytes};
+    use super::{Database, Options, Durability, Store, Bytes, Compact, View, Projection, LegacyString, Error, ErrorKind};
+    use super::{Repair, Problem, Value, Query, Comparison, Direction, Format, Conflict, Schema, Field};
+    use super::record::{crc32c, seal};
     use std::thread;
     use std::sync::Arc;
+    use std::rc::Rc;
+    use std::borrow::Cow;
+    use std::collections::{HashMap, BTreeMap, HashSet, BTreeSet, VecDeque, BinaryHeap};
     use std::time::Instant;
 
     #[derive(Bytes, Store, PartialEq, Debug, Clone)]
     #[from(Person2)]
+    #[projection(PersonAge(age), derive(Debug, PartialEq))]
     struct Person {
         #[id] name: String,
         age: u16,
@@ -53,6 +100,113 @@ mod tests {
         }
     }
     
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    #[rename(Note)]
+    struct LegacyNote {
+        #[id] title: LegacyString,
+        text: LegacyString
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    #[from(LegacyNote)]
+    struct Note {
+        #[id] title: String,
+        text: String
+    }
+
+    /// `Note` after the legacy entries were upgraded and `#[from(LegacyNote)]` was dropped.
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    #[rename(Note)]
+    struct UpgradedNote {
+        #[id] title: String,
+        text: String
+    }
+
+    impl From<LegacyNote> for Note {
+        fn from(legacy: LegacyNote) -> Note {
+            Note {
+                title: legacy.title.into(),
+                text: legacy.text.into()
+            }
+        }
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Counter {
+        #[id] #[varint] id: u64,
+        #[varint] delta: i32,
+        #[varint] samples: Vec<u32>,
+        ratio: f32
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    #[varint]
+    struct CompactResident {
+        #[id] id: u32,
+        address: Address,
+        history: Vec<(i64, Option<u16>)>,
+        state: Status
+    }
+
+    // Implements only the new API of `Bytes`, and is used through the shims of the old API too.
+    #[derive(PartialEq, Debug)]
+    struct OldStyle(u16);
+
+    impl Bytes for OldStyle {
+        fn serialize_into(&self, bytes: &mut Vec<u8>) {
+            bytes.extend_from_slice(&self.0.to_le_bytes());
+        }
+
+        fn deserialize_from(bytes: &mut &[u8]) -> Result<OldStyle, Error> {
+            match bytes {
+                [low, high, rest @ ..] => {
+                    let output = OldStyle(u16::from_le_bytes([*low, *high]));
+                    *bytes = rest;
+                    Ok(output)
+                },
+                _ => Err(Error::new(String::from("Missing byte.")))
+            }
+        }
+
+        fn signature() -> String {
+            String::from("OldStyle")
+        }
+    }
+
+    // Implements only the old API, which the defaults of the new methods are implemented by.
+    #[derive(PartialEq, Debug)]
+    struct OlderStyle(u8);
+
+    impl Bytes for OlderStyle {
+        fn serialize(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn deserialize(bytes: &mut Vec<u8>) -> Result<OlderStyle, Error> {
+            bytes.pop().map(OlderStyle).ok_or_else(|| Error::new(String::from("Missing byte.")))
+        }
+
+        fn signature() -> String {
+            String::from("OlderStyle")
+        }
+    }
+
+    // Implements neither method of a pair.
+    struct Unencoded;
+
+    impl Bytes for Unencoded {
+        fn signature() -> String {
+            String::from("Unencoded")
+        }
+    }
+    #[derive(Bytes, PartialEq, Debug)]
+    struct WithOldStyle {
+        before: String,
+        old: OldStyle,
+        older: OlderStyle,
+        after: Vec<u8>
+    }
+
     impl Person {
         pub fn new(name: &'static str, age: u16) -> Person {
             Person {
@@ -80,6 +234,142 @@ mod tests {
         #[id] id: u32
     }
 
+    #[derive(Bytes, Store)]
+    #[rename(Employee)]
+    struct Employee1 {
+        #[id] name: String,
+    }
+
+    #[derive(Bytes, Store)]
+    #[rename(Employee)]
+    struct Employee2 {
+        #[id] name: String,
+        age: u16,
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Employee {
+        #[id] name: String,
+        #[default = 18] age: u16,
+        #[default] tags: Vec<String>,
+        #[skip] visits: u32,
+        #[skip] #[default = true] active: bool,
+    }
+
+    #[derive(Bytes, PartialEq, Debug)]
+    enum Status {
+        Active,
+        Suspended { until: u64 },
+        Renamed(String, u16),
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Account {
+        #[id] id: u32,
+        status: Status,
+        balance: i64,
+    }
+
+    #[derive(Bytes, Store)]
+    #[rename(Shape)]
+    enum Shape1 {
+        Point { #[id] id: u32 },
+        Circle(#[id] u32, u64),
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    #[from(Shape1)]
+    enum Shape {
+        Point { #[id] id: u32 },
+        Circle(#[id] u32, u64),
+        Square(#[id] u32, u64),
+    }
+
+    impl From<Shape1> for Shape {
+        fn from(shape1: Shape1) -> Shape {
+            match shape1 {
+                Shape1::Point { id } => Shape::Point { id },
+                Shape1::Circle(id, radius) => Shape::Circle(id, radius)
+            }
+        }
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct UserId(#[id] u64);
+
+    #[derive(Bytes, PartialEq, Debug)]
+    struct Marker;
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Blob {
+        #[id] hash: [u8; 32],
+        pair: (u8, String),
+        marker: Marker,
+    }
+
+    #[derive(Bytes, PartialEq, Debug)]
+    struct Chunk<const N: usize>([u8; N]);
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Wrapper<T> {
+        #[id] id: u32,
+        value: T,
+        values: Vec<T>,
+    }
+
+    #[derive(Bytes, PartialEq, Debug)]
+    struct Address1 {
+        street: String,
+    }
+
+    #[derive(Bytes, PartialEq, Debug)]
+    #[from(Address1)]
+    struct Address {
+        street: String,
+        zip: u32,
+    }
+
+    impl From<Address1> for Address {
+        fn from(address1: Address1) -> Address {
+            Address {
+                street: address1.street,
+                zip: 0
+            }
+        }
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Resident {
+        #[id] name: String,
+        address: Address,
+        previous: Vec<Address>,
+        status: Status,
+        age: u16,
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    struct Profile {
+        #[id] id: u32,
+        nickname: Option<String>,
+        partner: Option<Box<Profile>>,
+        shared: Arc<Vec<u8>>,
+    }
+
+    #[derive(Bytes, Store, PartialEq, Debug)]
+    #[view(ArticleView, derive(Debug, PartialEq))]
+    #[projection(ArticleSummary(views, title), derive(Debug, PartialEq))]
+    #[projection(ArticleAddress(address, cached))]
+    struct Article {
+        #[id] id: u32,
+        title: String,
+        tags: Vec<String>,
+        body: Vec<u8>,
+        summary: Option<String>,
+        #[varint] views: u64,
+        address: Address,
+        #[skip] cached: bool,
+    }
+
     impl AutoNumber {
         pub fn new() -> AutoNumber {
             AutoNumber {
@@ -106,6 +396,286 @@ mod tests {
         println!("{} -> {:?} -> {}", number, serialized, deserialized);
     }
 
+    fn roundtrip<T: Bytes>(value: &T) -> T {
+        let mut serialized = value.serialize();
+        serialized.reverse();
+        let deserialized = T::deserialize(&mut serialized).unwrap();
+        assert!(serialized.is_empty());
+        deserialized
+    }
+
+    #[test]
+    fn wrappers() {
+        assert_eq!(roundtrip(&Some(5u8)), Some(5u8));
+        assert_eq!(roundtrip(&None::<u8>), None);
+        assert_eq!(roundtrip(&Rc::new(String::from("rc"))), Rc::new(String::from("rc")));
+        assert_eq!(roundtrip(&Cow::Borrowed("cow")), Cow::<str>::Owned(String::from("cow")));
+        assert_eq!(roundtrip(&Cow::Borrowed(&[1u16, 2][..])), Cow::<[u16]>::Owned(vec![1, 2]));
+        assert_ne!(Option::<u8>::signature(), u8::signature());
+        assert_ne!(Box::<u8>::signature(), u8::signature());
+        assert_ne!(Rc::<u8>::signature(), Arc::<u8>::signature());
+        assert_ne!(Cow::<str>::signature(), String::signature());
+
+        let database = Database::new("data/wrappers");
+        let profile = Profile {
+            id: 1,
+            nickname: None,
+            partner: Some(Box::new(Profile {
+                id: 2,
+                nickname: Some(String::from("Pete")),
+                partner: None,
+                shared: Arc::new(vec![1, 2, 3])
+            })),
+            shared: Arc::new(Vec::new())
+        };
+        database.create(&profile).unwrap();
+        assert_eq!(database.read::<Profile>(&1).unwrap(), profile);
+        database.delete_all::<Profile>().unwrap();
+    }
+
+    #[test]
+    fn collections() {
+        let mut first = HashMap::new();
+        let mut second = HashMap::new();
+        for i in 0..32u8 {
+            first.insert(format!("key {}", i), vec![i]);
+            second.insert(format!("key {}", 31 - i), vec![31 - i]);
+        }
+        assert_eq!(first.serialize(), second.serialize());
+        assert_eq!(roundtrip(&first), first);
+
+        let set = (0..32u16).collect::<HashSet<_>>();
+        assert_eq!(set.serialize(), (0..32u16).rev().collect::<HashSet<_>>().serialize());
+        assert_eq!(roundtrip(&set), set);
+
+        let map = (0..8u32).map(|i| (i, i.to_string())).collect::<BTreeMap<_, _>>();
+        assert_eq!(roundtrip(&map), map);
+        let set = (0..8i64).collect::<BTreeSet<_>>();
+        assert_eq!(roundtrip(&set), set);
+        let deque = (0..8u8).collect::<VecDeque<_>>();
+        assert_eq!(roundtrip(&deque), deque);
+        let heap = vec![3u8, 1, 2].into_iter().collect::<BinaryHeap<_>>();
+        assert_eq!(roundtrip(&heap).into_sorted_vec(), vec![1, 2, 3]);
+        let array = [[1u8, 2], [3, 4], [5, 6]];
+        assert_eq!(roundtrip(&array), array);
+        assert_ne!(HashSet::<u8>::signature(), BTreeSet::<u8>::signature());
+
+        // Counts beyond the remaining bytes fail at once, even for elements encoded in no bytes.
+        let huge = u64::MAX.serialize();
+        assert!(Vec::<()>::deserialize_from(&mut &huge[..]).is_err());
+        assert!(VecDeque::<()>::deserialize_from(&mut &huge[..]).is_err());
+        assert!(HashMap::<(), ()>::deserialize_from(&mut &huge[..]).is_err());
+        assert!(Vec::<()>::skip(&mut &huge[..]).is_err());
+        assert!(Vec::<()>::deserialize_compact_from(&mut &[0xff, 0xff, 0xff, 0xff, 0x0f][..]).is_err());
+        let sequence = super::Layout::Sequence(Box::new(super::Layout::Tuple(Vec::new())));
+        assert!(super::layout::Decoder::new(&BTreeMap::new()).decode(&sequence, &mut &huge[..]).is_err());
+        assert_eq!(Vec::<()>::deserialize_from(&mut &[2, 0, 0, 0, 0, 0, 0, 0, 9, 9][..]).unwrap(), vec![(), ()]);
+    }
+
+    #[test]
+    fn scalars_tuples_results() {
+        assert_eq!(roundtrip(&'ß'), 'ß');
+        assert_eq!(roundtrip(&usize::MAX), usize::MAX);
+        assert_eq!(roundtrip(&isize::MIN), isize::MIN);
+        assert_eq!(12usize.serialize(), 12u64.serialize());
+        assert_eq!(roundtrip(&()), ());
+        assert!(().serialize().is_empty());
+
+        let single = (String::from("one"),);
+        assert_eq!(roundtrip(&single), single);
+        assert_eq!(<(u8,)>::signature(), "(u8,)");
+        assert_eq!(<(u8, bool)>::signature(), "(u8,bool)");
+        let twelve = (1u8, 2u16, 3u32, 4u64, 5i8, 6i16, 7i32, 8i64, 'x', true, String::from("eleven"), vec![12u8]);
+        assert_eq!(roundtrip(&twelve), twelve);
+
+        let ok: Result<u32, String> = Ok(7);
+        let err: Result<u32, String> = Err(String::from("failed"));
+        assert_eq!(roundtrip(&ok), ok);
+        assert_eq!(roundtrip(&err), err);
+
+        let mut bytes = 0xD800u32.serialize();
+        bytes.reverse();
+        assert!(char::deserialize(&mut bytes).is_err());
+    }
+
+    #[test]
+    fn time_network_paths() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+        use std::path::PathBuf;
+
+        let duration = Duration::new(90, 500);
+        assert_eq!(roundtrip(&duration), duration);
+        let now = SystemTime::now();
+        assert_eq!(roundtrip(&now), now);
+        let before_epoch = UNIX_EPOCH - Duration::new(1, 250);
+        assert_eq!(roundtrip(&before_epoch), before_epoch);
+        let mut expected = (-2i64).serialize();
+        expected.append(&mut 999_999_750u32.serialize());
+        assert_eq!(before_epoch.serialize(), expected);
+
+        let ipv4 = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(ipv4.serialize(), vec![4, 192, 168, 0, 1]);
+        assert_eq!(roundtrip(&ipv4), ipv4);
+        let ipv6 = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        assert_eq!(roundtrip(&ipv6), ipv6);
+        let socket = SocketAddr::new(ipv4, 8080);
+        assert_eq!(roundtrip(&socket), socket);
+        let socket = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, 443, 7, 3));
+        assert_eq!(roundtrip(&socket), socket);
+
+        let path = PathBuf::from("data/persons/some file");
+        assert_eq!(roundtrip(&path), path);
+    }
+
+    #[test]
+    fn strings() {
+        let string = String::from("nul \0 inside");
+        assert_eq!(roundtrip(&string), string);
+        assert_eq!(String::from("ab").serialize(), vec![2, b'a', b'b']);
+        let long = "x".repeat(300);
+        assert_eq!(&long.serialize()[..2], &[0xac, 0x02]);
+        assert_eq!(roundtrip(&long), long);
+        assert_ne!(String::signature(), LegacyString::signature());
+
+        let mut truncated = String::from("abc").serialize();
+        truncated.pop();
+        truncated.reverse();
+        assert!(String::deserialize(&mut truncated).is_err());
+
+        let _ = std::fs::remove_dir_all("data/strings");
+        let database = Database::new("data/strings");
+        let legacy = |title: &str, text: &str| LegacyNote {
+            title: LegacyString(String::from(title)),
+            text: LegacyString(String::from(text))
+        };
+        let note = |title: &str, text: &str| Note { title: String::from(title), text: String::from(text) };
+        database.create(&legacy("todo", "migrate")).unwrap();
+        // The id is encoded differently now, but the entry is still found under its legacy name.
+        assert_eq!(database.read::<Note>(&String::from("todo")).unwrap(), note("todo", "migrate"));
+        assert!(database.exists(&note("todo", "")).unwrap());
+        assert!(database.create(&note("todo", "again")).is_err());
+        assert_eq!(database.read_all::<Note>().unwrap(), vec![note("todo", "migrate")]);
+        assert_eq!(database.select::<Note>(&Query::new().id("todo")).unwrap(), vec![note("todo", "migrate")]);
+        database.upgrade_all::<Note>().unwrap();
+        assert_eq!(database.read::<Note>(&String::from("todo")).unwrap().text, "migrate");
+        assert!(database.read::<LegacyNote>(&LegacyString(String::from("todo"))).is_err());
+
+        // Updates move entries to their current name, and deletes find them too.
+        database.create(&legacy("moved", "old")).unwrap();
+        database.update(&note("moved", "new")).unwrap();
+        assert!(database.read::<LegacyNote>(&LegacyString(String::from("moved"))).is_err());
+        assert_eq!(database.read::<Note>(&String::from("moved")).unwrap().text, "new");
+        database.create(&legacy("gone", "")).unwrap();
+        database.delete::<Note>(&String::from("gone")).unwrap();
+        assert_eq!(database.read_all::<Note>().unwrap().len(), 2);
+
+        // Upgrades keep the entry under the current name, and leave other files alone.
+        database.create(&legacy("todo", "stale")).unwrap();
+        std::fs::write(std::path::Path::new("data/strings/notes/00.tmp"), []).unwrap();
+        database.upgrade_all::<Note>().unwrap();
+        assert_eq!(database.read::<Note>(&String::from("todo")).unwrap().text, "migrate");
+        assert!(std::path::Path::new("data/strings/notes/00.tmp").exists());
+        database.delete_all::<Note>().unwrap();
+        assert_eq!(std::fs::read_dir("data/strings/notes").unwrap().count(), 1);
+        std::fs::remove_dir_all("data/strings").unwrap();
+    }
+
+    #[test]
+    fn varints() {
+        assert_eq!(0u64.serialize_compact(), vec![0]);
+        assert_eq!(300u32.serialize_compact(), vec![0xac, 0x02]);
+        assert_eq!((-1i32).serialize_compact(), vec![1]);
+        assert_eq!(1i64.serialize_compact(), vec![2]);
+        assert_eq!(u128::MAX.serialize_compact().len(), 19);
+        for &value in &[0i64, -1, 63, -64, 64, i64::MIN, i64::MAX] {
+            assert_eq!(i64::deserialize_compact_from(&mut &value.serialize_compact()[..]).unwrap(), value);
+        }
+        assert!(u16::deserialize_compact_from(&mut &300u32.serialize_compact()[..]).is_ok());
+        assert!(u16::deserialize_compact_from(&mut &70000u32.serialize_compact()[..]).is_err());
+
+        let counter = Counter { id: 5, delta: -3, samples: vec![1, 2, 3], ratio: 0.5 };
+        // Hash, one byte each for id, delta and the length, three samples and the float.
+        assert_eq!(counter.serialize().len(), 8 + 3 + 3 + 4);
+        assert_eq!(roundtrip(&counter), counter);
+        assert_ne!(Counter::signature(), String::from("u64i32Vec<u32>f32"));
+
+        let resident = CompactResident {
+            id: 1,
+            address: Address { street: String::from("Main Street 1"), zip: 8000 },
+            history: vec![(-5, Some(7)), (1 << 40, None)],
+            state: Status::Suspended { until: 3 }
+        };
+        assert_eq!(roundtrip(&resident), resident);
+
+        let database = Database::new("data/varints");
+        database.create(&counter).unwrap();
+        database.create(&resident).unwrap();
+        assert_eq!(database.read::<Counter>(&5).unwrap(), counter);
+        assert_eq!(database.read::<CompactResident>(&1).unwrap(), resident);
+        database.delete_all::<Counter>().unwrap();
+        database.delete_all::<CompactResident>().unwrap();
+    }
+
+    #[test]
+    fn slices_and_shims() {
+        let value = (7u32, String::from("seven"), vec![Some(7u8), None]);
+        let mut bytes = value.serialize();
+        assert_eq!(value.size_hint(), bytes.len());
+        bytes.extend_from_slice(&[1, 2, 3]);
+        let mut remaining = &bytes[..];
+        assert_eq!(<(u32, String, Vec<Option<u8>>)>::deserialize_from(&mut remaining).unwrap(), value);
+        assert_eq!(remaining, &[1, 2, 3]);
+        assert!(u64::deserialize_from(&mut &[1, 2, 3][..]).is_err());
+
+        let mut serialized = Vec::new();
+        5u8.serialize_into(&mut serialized);
+        String::from("ab").serialize_into(&mut serialized);
+        assert_eq!(serialized, vec![5, 2, b'a', b'b']);
+
+        let with_old_style = WithOldStyle {
+            before: String::from("before"),
+            old: OldStyle(513),
+            older: OlderStyle(9),
+            after: vec![1, 2]
+        };
+        let bytes = with_old_style.serialize();
+        let mut remaining = &bytes[..];
+        assert_eq!(WithOldStyle::deserialize_from(&mut remaining).unwrap(), with_old_style);
+        assert!(remaining.is_empty());
+        assert_eq!(roundtrip(&with_old_style), with_old_style);
+
+        // The old API decodes from the back of a reversed `Vec`, leaving what follows the value.
+        let mut stack = OldStyle(513).serialize();
+        String::from("next").serialize_into(&mut stack);
+        stack.reverse();
+        assert_eq!(OldStyle::deserialize(&mut stack).unwrap(), OldStyle(513));
+        let remaining = stack.clone();
+        assert!(OldStyle::deserialize(&mut vec![1]).is_err());
+        assert_eq!(String::deserialize(&mut stack).unwrap(), "next");
+        assert!(stack.is_empty());
+        assert!(u64::deserialize(&mut stack).is_err());
+        let mut truncated = remaining[1..].to_vec();
+        assert!(String::deserialize(&mut truncated).is_err());
+        assert_eq!(truncated, &remaining[1..]);
+
+        // Types implementing only the old API are read through the new one, and leave what follows.
+        let mut serialized = Vec::new();
+        OlderStyle(4).serialize_into(&mut serialized);
+        serialized.push(5);
+        let mut remaining = &serialized[..];
+        assert_eq!(OlderStyle::deserialize_from(&mut remaining).unwrap(), OlderStyle(4));
+        assert_eq!(remaining, &[5]);
+        assert!(OlderStyle::deserialize_from(&mut &[][..]).is_err());
+
+        // Types implementing neither method of a pair panic instead of overflowing the stack.
+        let panic = std::panic::catch_unwind(|| Unencoded.serialize()).unwrap_err();
+        assert!(panic.downcast_ref::<String>().unwrap().contains("has to implement `serialize_into` or `serialize`"));
+        let panic = std::panic::catch_unwind(|| Unencoded::deserialize_from(&mut &[0][..]).map(drop)).unwrap_err();
+        assert!(panic.downcast_ref::<String>().unwrap().contains("has to implement `deserialize_from` or `deserialize`"));
+        assert_eq!(OlderStyle(1).serialize(), vec![1]);
+    }
+
     #[test]
     fn basics() {
         let database = Database::new("data/basics");
@@ -131,6 +701,123 @@ mod tests {
         database.delete::<Person>(&String::from("Peter")).expect("Database delete failed");
     }
 
+    #[test]
+    fn defaults() {
+        let database = Database::new("data/defaults");
+        database.create(&Employee1 { name: String::from("Anna") }).unwrap();
+        database.create(&Employee2 { name: String::from("Beat"), age: 33 }).unwrap();
+        let anna: Employee = database.read(&String::from("Anna")).unwrap();
+        assert_eq!(anna.age, 18);
+        assert!(anna.tags.is_empty());
+        let beat: Employee = database.read(&String::from("Beat")).unwrap();
+        assert_eq!(beat.age, 33);
+        assert!(beat.tags.is_empty());
+        database.delete_all::<Employee>().unwrap();
+    }
+
+    #[test]
+    fn skip() {
+        let database = Database::new("data/skip");
+        let carla = Employee {
+            name: String::from("Carla"),
+            age: 40,
+            tags: vec![String::from("admin")],
+            visits: 12,
+            active: false
+        };
+        database.create(&carla).unwrap();
+        let carla_read: Employee = database.read(&carla.name).unwrap();
+        assert_eq!(carla_read.tags, carla.tags);
+        assert_eq!(carla_read.visits, 0);
+        assert!(carla_read.active);
+        assert_eq!(Employee::signature(), Employee2::signature() + &Vec::<String>::signature());
+        database.delete_all::<Employee>().unwrap();
+    }
+
+    #[test]
+    fn enums() {
+        let database = Database::new("data/enums");
+        let accounts = vec![
+            Account { id: 1, status: Status::Active, balance: -20 },
+            Account { id: 2, status: Status::Suspended { until: 1234 }, balance: 0 },
+            Account { id: 3, status: Status::Renamed(String::from("Peter"), 2), balance: 10 },
+        ];
+        for account in &accounts {
+            database.create(account).unwrap();
+        }
+        for account in &accounts {
+            assert_eq!(&database.read::<Account>(&account.id).unwrap(), account);
+        }
+        database.delete_all::<Account>().unwrap();
+        assert_ne!(Status::signature(), Shape::signature());
+
+        database.create(&Shape1::Circle(1, 5)).unwrap();
+        database.create(&Shape::Square(2, 3)).unwrap();
+        assert_eq!(database.read::<Shape>(&1).unwrap(), Shape::Circle(1, 5));
+        assert_eq!(database.read::<Shape>(&2).unwrap(), Shape::Square(2, 3));
+        database.delete_all::<Shape>().unwrap();
+    }
+
+    #[test]
+    fn tuples_units_generics() {
+        let database = Database::new("data/tuples-units-generics");
+        database.create(&UserId(7)).unwrap();
+        assert_eq!(database.read::<UserId>(&7).unwrap(), UserId(7));
+        database.delete_all::<UserId>().unwrap();
+
+        let blob = Blob { hash: [3; 32], pair: (1, String::from("one")), marker: Marker };
+        database.create(&blob).unwrap();
+        assert_eq!(database.read::<Blob>(&[3; 32]).unwrap(), blob);
+        database.delete_all::<Blob>().unwrap();
+
+        let wrapper = Wrapper { id: 1, value: String::from("a"), values: vec![String::from("b")] };
+        database.create(&wrapper).unwrap();
+        assert_eq!(database.read::<Wrapper<String>>(&1).unwrap(), wrapper);
+        assert_ne!(Wrapper::<String>::signature(), Wrapper::<u8>::signature());
+        database.delete_all::<Wrapper<String>>().unwrap();
+
+        // Every length of a const generic type has a hash of its own.
+        assert_ne!(Chunk::<2>::hash(), Chunk::<3>::hash());
+        assert_eq!(roundtrip(&Chunk([1, 2, 3])), Chunk([1, 2, 3]));
+        assert!(Chunk::<2>::deserialize_from(&mut &Chunk([1, 2, 3]).serialize()[..]).is_err());
+    }
+
+    #[test]
+    fn nested() {
+        let database = Database::new("data/nested");
+        let resident = Resident {
+            name: String::from("Peter"),
+            address: Address { street: String::from("Main Street 1"), zip: 8000 },
+            previous: vec![
+                Address { street: String::from("Side Street 2"), zip: 3000 },
+                Address { street: String::from("Lake Road 3"), zip: 6000 },
+            ],
+            status: Status::Suspended { until: 12 },
+            age: 25
+        };
+        database.create(&resident).unwrap();
+        assert_eq!(database.read::<Resident>(&resident.name).unwrap(), resident);
+        database.delete_all::<Resident>().unwrap();
+
+        // Nested values keep their own hash, so an outdated nested value is migrated on its own.
+        let mut serialized = Resident::hash().serialize();
+        serialized.append(&mut String::from("Maria").serialize());
+        serialized.append(&mut Address1 { street: String::from("Old Street 4") }.serialize());
+        serialized.append(&mut Vec::<Address>::new().serialize());
+        serialized.append(&mut Status::Active.serialize());
+        serialized.append(&mut 54u16.serialize());
+        serialized.reverse();
+        let maria = Resident::deserialize(&mut serialized).unwrap();
+        assert_eq!(maria.address, Address { street: String::from("Old Street 4"), zip: 0 });
+        assert_eq!(maria.age, 54);
+        assert!(serialized.is_empty());
+
+        let mut serialized = Status::Active.serialize();
+        serialized.reverse();
+        let error = Address::deserialize(&mut serialized).unwrap_err();
+        assert_eq!(error.to_string(), "Hash not matching for Address.");
+    }
+
     #[test]
     fn read_all() {
         let database = Database::new("data/read-all");
@@ -138,30 +825,629 @@ mod tests {
         database.create(&Person::new("Maria", 54)).unwrap();
         database.create(&Person::new("Josef", 51)).unwrap();
         assert_eq!(database.read_all::<Person>().unwrap().len(), 3);
+
+        // Temporary files and directories aren't entries.
+        let persons = std::path::Path::new("data/read-all").join(Person::NAME);
+        std::fs::write(persons.join("00.tmp"), []).unwrap();
+        std::fs::create_dir_all(persons.join("nested")).unwrap();
+        assert_eq!(database.read_all::<Person>().unwrap().len(), 3);
+        std::fs::remove_file(persons.join("00.tmp")).unwrap();
+        std::fs::remove_dir(persons.join("nested")).unwrap();
         database.delete_all::<Person>().unwrap();
     }
 
+    #[test]
+    fn views() {
+        let database = Database::new("data/views");
+        let article = Article {
+            id: 1,
+            title: String::from("Views"),
+            tags: vec![String::from("zero"), String::from("copy")],
+            body: vec![1, 2, 3],
+            summary: None,
+            views: 300,
+            address: Address { street: String::from("Main Street 1"), zip: 8000 },
+            cached: true
+        };
+        database.create(&article).unwrap();
+        let title = database.read_with::<Article, _>(&1, |view| view.title.len()).unwrap();
+        assert_eq!(title, 5);
+        database.read_with::<Article, _>(&1, |view| {
+            assert_eq!(view.tags.iter().collect::<Vec<_>>(), vec!["zero", "copy"]);
+            assert_eq!(view.body.as_slice(), &[1, 2, 3]);
+            assert_eq!(view.views, 300);
+            assert_eq!(view, ArticleView {
+                id: 1,
+                title: "Views",
+                tags: view.tags,
+                body: view.body,
+                summary: None,
+                views: 300,
+                address: Address { street: String::from("Main Street 1"), zip: 8000 }
+            });
+        }).unwrap();
+        assert!(database.read_with::<Article, _>(&2, |_| ()).is_err());
+        database.delete_all::<Article>().unwrap();
+
+        // Types without `#[view(...)]` are viewed as owned values, older layouts included.
+        database.create(&Person2 { name: String::from("Peter"), age: 25, text: String::new() }).unwrap();
+        assert_eq!(database.read_with::<Person, _>(&String::from("Peter"), |person| person.age).unwrap(), 25);
+        database.delete_all::<Person>().unwrap();
+
+        let bytes = Person::new("Peter", 25).serialize();
+        assert!(Article::view_from(&mut &bytes[..]).is_err());
+        let bytes = (String::from("text"), vec![Some(1u8), None]).serialize();
+        let mut remaining = &bytes[..];
+        let (text, options) = <(String, Vec<Option<u8>>)>::view_from(&mut remaining).unwrap();
+        assert!(remaining.is_empty());
+        assert_eq!(text, "text");
+        assert_eq!(options.len(), 2);
+        assert_eq!(options.to_vec(), vec![Some(1), None]);
+        assert!(Vec::<String>::view_from(&mut &bytes[..3]).is_err());
+    }
+
+    #[test]
+    fn projections() {
+        let database = Database::new("data/projections");
+        for id in 0..3 {
+            database.create(&Article {
+                id,
+                title: format!("Article {}", id),
+                tags: vec![String::from("projection")],
+                body: vec![0; 1000],
+                summary: Some(String::from("Skipped")),
+                views: 100 + id as u64,
+                address: Address { street: String::from("Main Street 1"), zip: 8000 },
+                cached: true
+            }).unwrap();
+        }
+        let summary = database.read_fields::<ArticleSummary>(&1).unwrap();
+        assert_eq!(summary, ArticleSummary { views: 101, title: String::from("Article 1") });
+        let address = database.read_fields::<ArticleAddress>(&2).unwrap();
+        assert_eq!(address.address.zip, 8000);
+        assert!(!address.cached);
+        let mut summaries = database.read_all_fields::<ArticleSummary>().unwrap();
+        summaries.sort_by_key(|summary| summary.views);
+        assert_eq!(summaries.iter().map(|summary| summary.views).collect::<Vec<_>>(), vec![100, 101, 102]);
+        database.delete_all::<Article>().unwrap();
+
+        // Older layouts are decoded in full and projected afterwards.
+        database.create(&Person2 { name: String::from("Peter"), age: 25, text: String::from("Text") }).unwrap();
+        assert_eq!(database.read_fields::<PersonAge>(&String::from("Peter")).unwrap(), PersonAge { age: 25 });
+        database.delete_all::<Person>().unwrap();
+
+        let mut bytes = Person::new("Peter", 25).serialize();
+        bytes.extend_from_slice(&[1, 2, 3]);
+        let mut remaining = &bytes[..];
+        assert_eq!(PersonAge::project_from(&mut remaining).unwrap(), PersonAge { age: 25 });
+        assert_eq!(remaining, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn skipping() {
+        let mut map = HashMap::new();
+        map.insert(String::from("key"), vec![Some(1u32), None]);
+        let mut bytes = Vec::new();
+        (String::from("text"), 7u64, [1u8, 2, 3], 'c', Box::new(-1i16)).serialize_into(&mut bytes);
+        map.serialize_into(&mut bytes);
+        Ok::<_, u8>(Status::Renamed(String::from("name"), 2)).serialize_into(&mut bytes);
+        Resident {
+            name: String::from("Peter"),
+            address: Address { street: String::from("Main Street 1"), zip: 8000 },
+            previous: Vec::new(),
+            status: Status::Active,
+            age: 25
+        }.serialize_into(&mut bytes);
+        Address1 { street: String::from("Old Street 4") }.serialize_into(&mut bytes);
+        bytes.push(42);
+
+        let mut remaining = &bytes[..];
+        <(String, u64, [u8; 3], char, Box<i16>)>::skip(&mut remaining).unwrap();
+        HashMap::<String, Vec<Option<u32>>>::skip(&mut remaining).unwrap();
+        Result::<Status, u8>::skip(&mut remaining).unwrap();
+        Resident::skip(&mut remaining).unwrap();
+        // Outdated values are skipped by decoding them.
+        Address::skip(&mut remaining).unwrap();
+        assert_eq!(remaining, &[42]);
+        assert!(String::skip(&mut &[5, b'a'][..]).is_err());
+        assert!(Status::skip(&mut &Address1 { street: String::new() }.serialize()[..]).is_err());
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(b""), 0);
+
+        let database = Database::new("data/checksums");
+        let path = std::path::Path::new("data/checksums/persons/055065746572");
+        database.create(&Person::new("Peter", 25)).unwrap();
+        assert_eq!(database.read::<Person>(&String::from("Peter")).unwrap().age, 25);
+
+        let mut bytes = std::fs::read(path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        std::fs::write(path, &bytes).unwrap();
+        let error = database.read::<Person>(&String::from("Peter")).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Corrupted(path.to_path_buf()));
+        assert!(error.to_string().contains("055065746572"));
+        assert!(database.read_with::<Person, _>(&String::from("Peter"), |_| ()).is_err());
+
+        std::fs::write(path, &bytes[..10]).unwrap();
+        assert!(matches!(database.read::<Person>(&String::from("Peter")).unwrap_err().kind(), ErrorKind::Corrupted(_)));
+
+        // A flipped bit in the magic damages the header instead of turning off verification.
+        let mut bytes = seal(&Person::new("Peter", 25));
+        bytes[6] ^= 1;
+        std::fs::write(path, &bytes).unwrap();
+        assert!(matches!(database.read::<Person>(&String::from("Peter")).unwrap_err().kind(), ErrorKind::Corrupted(_)));
+        bytes[6] ^= 1;
+        bytes[1] ^= 4;
+        std::fs::write(path, &bytes).unwrap();
+        assert!(matches!(database.read::<Person>(&String::from("Peter")).unwrap_err().kind(), ErrorKind::Corrupted(_)));
+        assert!(!database.check(Repair::Report).unwrap().is_ok());
+
+        // Entries without a header are read as they are and get one when upgraded.
+        let legacy = Person::new("Peter", 26).serialize();
+        std::fs::write(path, &legacy).unwrap();
+        assert_eq!(database.read::<Person>(&String::from("Peter")).unwrap().age, 26);
+        database.upgrade_all::<Person>().unwrap();
+        assert!(std::fs::read(path).unwrap().len() > legacy.len());
+        assert_eq!(database.read::<Person>(&String::from("Peter")).unwrap().age, 26);
+        database.delete_all::<Person>().unwrap();
+    }
+
+    #[test]
+    fn check() {
+        let _ = std::fs::remove_dir_all("data/check");
+        let database = Database::new("data/check");
+        let persons = std::path::Path::new("data/check/persons");
+        database.create(&Person::new("Peter", 25)).unwrap();
+        database.create(&Person::new("Anna", 31)).unwrap();
+        let anna = persons.join(Database::encode(&String::from("Anna")).unwrap());
+        let mut bytes = std::fs::read(&anna).unwrap();
+        bytes[20] ^= 0xff;
+        std::fs::write(&anna, bytes).unwrap();
+        let emil = persons.join(Database::encode(&String::from("Emil")).unwrap());
+        std::fs::write(&emil, seal(&Employee1 { name: String::from("Emil") })).unwrap();
+        let carl = persons.join(Database::encode(&String::from("Carl")).unwrap());
+        std::fs::write(&carl, seal(&Person::new("Karl", 40))).unwrap();
+        std::fs::write(persons.join("zz"), []).unwrap();
+        std::fs::write(persons.join("0a.tmp"), []).unwrap();
+        std::fs::create_dir_all(persons.join("sub")).unwrap();
+        std::fs::write("data/check/notes.txt", []).unwrap();
+        std::fs::write("data/check/.catalog.tmp", []).unwrap();
+
+        let problems = |report: &super::CheckReport| report.findings.iter()
+            .map(|finding| (finding.path.file_name().unwrap().to_str().unwrap().to_string(), finding.problem.clone()))
+            .collect::<Vec<_>>();
+
+        let report = database.check(Repair::Report).unwrap();
+        assert_eq!(report.entries, 4);
+        let found = problems(&report);
+        assert_eq!(found.len(), 6);
+        assert!(found.contains(&(String::from("notes.txt"), Problem::Stray)));
+        assert!(found.contains(&(String::from(".catalog.tmp"), Problem::Temporary)));
+        assert!(found.contains(&(String::from("sub"), Problem::Stray)));
+        assert!(found.contains(&(String::from("0a.tmp"), Problem::Temporary)));
+        assert!(found.iter().any(|(name, problem)| name == "zz" && matches!(problem, Problem::InvalidName(_))));
+        assert!(found.iter().any(|(name, problem)| anna.ends_with(name) && matches!(problem, Problem::Corrupted(_))));
+
+        let report = database.check_collection::<Person>(Repair::Quarantine).unwrap();
+        let found = problems(&report);
+        assert_eq!(found.len(), 6);
+        assert!(found.iter().any(|(name, problem)| emil.ends_with(name) && *problem == Problem::UnknownSchema(Employee1::hash())));
+        assert!(found.iter().any(|(name, problem)| carl.ends_with(name) && *problem == Problem::Misnamed));
+        assert!(!anna.exists() && !emil.exists() && carl.exists());
+        assert!(std::path::Path::new("data/check/.quarantine/persons/zz").exists());
+
+        let report = database.check_collection::<Person>(Repair::Delete).unwrap();
+        let found = problems(&report);
+        assert_eq!(found.len(), 2);
+        assert!(found.contains(&(String::from("sub"), Problem::Stray)));
+        assert!(found.iter().any(|(name, problem)| carl.ends_with(name) && *problem == Problem::Misnamed));
+
+        database.check(Repair::Delete).unwrap();
+        assert!(!std::path::Path::new("data/check/.catalog.tmp").exists());
+        assert!(std::path::Path::new("data/check/notes.txt").exists());
+        std::fs::remove_dir_all("data/check").unwrap();
+    }
+
+    #[test]
+    fn values() {
+        let account = Account { id: 7, status: Status::Renamed(String::from("Ann"), 2), balance: -5 };
+        assert_eq!(account.to_value(), Value::Struct(vec![
+            (String::from("id"), Value::UInt(7)),
+            (String::from("status"), Value::Variant(String::from("Renamed"), Box::new(Value::Struct(vec![
+                (String::from("0"), Value::String(String::from("Ann"))),
+                (String::from("1"), Value::UInt(2))
+            ])))),
+            (String::from("balance"), Value::Int(-5))
+        ]));
+        assert_eq!(Status::Active.to_value(), Value::Variant(String::from("Active"), Box::new(Value::Null)));
+        assert_eq!(account.to_value().field("status.0"), Some(&Value::from("Ann")));
+        let employee = Employee { name: String::from("Emil"), age: 30, tags: Vec::new(), visits: 2, active: true };
+        assert_eq!(employee.to_value().field("age"), Some(&Value::UInt(30)));
+        assert_eq!(employee.to_value().field("active"), None);
+
+        let mut map = HashMap::new();
+        map.insert(2u8, None);
+        map.insert(1u8, Some('x'));
+        assert_eq!(map.to_value(), Value::Map(vec![
+            (Value::UInt(1), Value::from('x')),
+            (Value::UInt(2), Value::Null)
+        ]));
+        assert_eq!((1.5f32, Marker).to_value(), Value::List(vec![Value::Float(1.5), Value::Struct(Vec::new())]));
+        assert_eq!(OldStyle(3).to_value(), Value::Bytes(OldStyle(3).serialize()));
+
+        assert_eq!(Value::Int(30).compare(&Value::UInt(30)), Some(std::cmp::Ordering::Equal));
+        assert_eq!(Value::Int(-1).compare(&Value::UInt(0)), Some(std::cmp::Ordering::Less));
+        assert_eq!(Value::Float(30.5).compare(&Value::UInt(30)), Some(std::cmp::Ordering::Greater));
+        assert_eq!(Value::from("30").compare(&Value::UInt(30)), None);
+    }
+
+    #[test]
+    fn queries() {
+        let database = Database::new("data/queries");
+        database.delete_all::<Person>().unwrap();
+        for (name, age) in &[("Jakob", 56), ("Maria", 24), ("Josef", 31), ("Anna", 31)] {
+            database.create(&Person::new(name, *age)).unwrap();
+        }
+        let names = |persons: Vec<Person>| persons.into_iter().map(|person| person.name).collect::<Vec<_>>();
+
+        let query = Query::new().filter("age", Comparison::Greater, 30).order_by("age", Direction::Descending);
+        assert_eq!(names(database.select(&query).unwrap()), vec!["Jakob", "Anna", "Josef"]);
+        assert_eq!(names(database.select(&query.limit(1)).unwrap()), vec!["Jakob"]);
+        let query = Query::new()
+            .filter("age", Comparison::LessOrEqual, 31)
+            .filter("name", Comparison::NotEqual, "Anna")
+            .order_by("name", Direction::Ascending);
+        assert_eq!(names(database.select(&query).unwrap()), vec!["Josef", "Maria"]);
+        assert_eq!(names(database.select(&Query::new().id("Maria")).unwrap()), vec!["Maria"]);
+        assert_eq!(database.count::<Person>(&Query::new().id("Peter")).unwrap(), 0);
+        assert_eq!(database.count::<Person>(&Query::new().filter("height", Comparison::Equal, 1)).unwrap(), 0);
+        assert_eq!(database.count::<Person>(&Query::new().id(31)).unwrap(), 0);
+        assert_eq!(database.count::<Person>(&Query::new()).unwrap(), 4);
+        assert_eq!(database.count::<Person>(&Query::new().limit(3)).unwrap(), 3);
+
+        // Temporary files aren't entries.
+        let stray = std::path::Path::new("data/queries").join(Person::NAME).join("00.tmp");
+        std::fs::write(&stray, []).unwrap();
+        assert_eq!(database.count::<Person>(&Query::new()).unwrap(), 4);
+        assert_eq!(database.select::<Person>(&Query::new()).unwrap().len(), 4);
+        std::fs::remove_file(stray).unwrap();
+        database.delete_all::<Person>().unwrap();
+
+        // NaN and records without the field sort in a total order.
+        database.delete_all::<Counter>().unwrap();
+        for (id, ratio, samples) in [(1, 0.5, vec![3, 1]), (2, f32::NAN, vec![2]), (3, -1.0, vec![1, 2]), (4, 2.0, vec![])] {
+            database.create(&Counter { id, delta: 0, samples, ratio }).unwrap();
+        }
+        let ids = |query: &Query| database.select::<Counter>(query).unwrap().into_iter().map(|counter| counter.id).collect::<Vec<_>>();
+        assert_eq!(ids(&Query::new().order_by("ratio", Direction::Ascending)), vec![3, 1, 4, 2]);
+        assert_eq!(ids(&Query::new().order_by("ratio", Direction::Descending)), vec![2, 4, 1, 3]);
+        assert_eq!(ids(&Query::new().order_by("samples.1", Direction::Ascending)), vec![2, 4, 1, 3]);
+        assert_eq!(ids(&Query::new().order_by("samples.1", Direction::Descending)), vec![3, 1, 2, 4]);
+        assert_eq!(Value::Float(f64::NAN).total_cmp(&Value::UInt(u128::MAX)), std::cmp::Ordering::Greater);
+        assert_eq!(Value::Float(-0.5).total_cmp(&Value::Int(0)), std::cmp::Ordering::Less);
+        assert_eq!(Value::Int(-3).total_cmp(&Value::Float(-2.5)), std::cmp::Ordering::Less);
+        assert_eq!(Value::Float(2.0).total_cmp(&Value::UInt(2)), std::cmp::Ordering::Equal);
+        assert_eq!(Value::String(String::new()).total_cmp(&Value::UInt(7)), std::cmp::Ordering::Greater);
+        database.delete_all::<Counter>().unwrap();
+    }
+
+    #[test]
+    fn exports() {
+        let _ = std::fs::remove_dir_all("data/exports");
+        let database = Database::new("data/exports");
+        let ann = Resident {
+            name: String::from("Ann, \"the\" first"),
+            address: Address { street: String::from("Main"), zip: 8000 },
+            previous: vec![Address { street: String::new(), zip: 1 }],
+            status: Status::Suspended { until: 7 },
+            age: 31
+        };
+        let bob = Resident {
+            name: String::from("Bob"),
+            address: Address { street: String::from("[Side]"), zip: 8001 },
+            previous: Vec::new(),
+            status: Status::Active,
+            age: 45
+        };
+        database.create(&ann).unwrap();
+        database.create(&bob).unwrap();
+        assert_eq!(Resident::FIELDS, &["name", "address", "previous", "status", "age"]);
+
+        for format in &[Format::Json, Format::Csv] {
+            let mut exported = Vec::new();
+            assert_eq!(database.export::<Resident, _>(&mut exported, *format).unwrap(), 2);
+            database.delete_all::<Resident>().unwrap();
+            assert_eq!(database.import::<Resident, _>(&exported[..], *format, Conflict::Fail).unwrap(), 2);
+            assert_eq!(database.read::<Resident>(&ann.name).unwrap(), ann);
+            assert_eq!(database.read::<Resident>(&bob.name).unwrap(), bob);
+        }
+
+        let mut csv = Vec::new();
+        database.export::<Resident, _>(&mut csv, Format::Csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("name,address,previous,status,age\n"));
+        assert!(csv.contains("Bob,\"{\"\"street\"\":\"\"[Side]\"\",\"\"zip\"\":8001}\",[],Active,45\n"));
+
+        let changed = "name,address,previous,status,age\nBob,\"{\"\"street\"\":\"\"Hill\"\",\"\"zip\"\":1}\",[],Active,46\nCid,\"{\"\"street\"\":\"\"\"\",\"\"zip\"\":2}\",[],\"{\"\"Renamed\"\":[\"\"Cy\"\",2]}\",20\n";
+        assert!(database.import::<Resident, _>(changed.as_bytes(), Format::Csv, Conflict::Fail).is_err());
+        assert!(database.read::<Resident>(&String::from("Cid")).is_err());
+        assert_eq!(database.import::<Resident, _>(changed.as_bytes(), Format::Csv, Conflict::Skip).unwrap(), 1);
+        assert_eq!(database.read::<Resident>(&String::from("Bob")).unwrap().age, 45);
+        assert_eq!(database.read::<Resident>(&String::from("Cid")).unwrap().status, Status::Renamed(String::from("Cy"), 2));
+        assert_eq!(database.import::<Resident, _>(changed.as_bytes(), Format::Csv, Conflict::Overwrite).unwrap(), 2);
+        assert_eq!(database.read::<Resident>(&String::from("Bob")).unwrap().address.street, "Hill");
+
+        let error = database.import::<Resident, _>(&b"[{\"name\":\"Dan\"}]"[..], Format::Json, Conflict::Fail).unwrap_err();
+        assert_eq!(error.to_string(), "Record 1: Missing field address of Resident.");
+
+        // Input with an id twice, nested too deeply or with invalid surrogates imports nothing.
+        let twice = format!("name,address,previous,status,age\nDan,\"{{\"\"street\"\":\"\"\"\",\"\"zip\"\":3}}\",[],Active,1\n{}", &changed[33..]);
+        let twice = twice.replace("Cid", "Dan");
+        for conflict in [Conflict::Fail, Conflict::Overwrite] {
+            let error = database.import::<Resident, _>(twice.as_bytes(), Format::Csv, conflict).unwrap_err();
+            assert_eq!(error.to_string(), "Records 1 and 3 have the same id.");
+        }
+        assert!(database.read::<Resident>(&String::from("Dan")).is_err());
+        assert_eq!(database.read::<Resident>(&String::from("Bob")).unwrap().age, 46);
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        assert!(database.import::<Resident, _>(deep.as_bytes(), Format::Json, Conflict::Fail).unwrap_err().to_string().contains("nested too deeply"));
+        let nested = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(database.import::<Resident, _>(nested.as_bytes(), Format::Json, Conflict::Fail).unwrap_err().to_string().starts_with("Record 1:"));
+        assert_eq!(super::json::parse("\"\\ud83d\\ude00\"").unwrap(), Value::from("\u{1f600}"));
+        assert!(super::json::parse("\"\\ud83d\\u0041\"").is_err());
+        let employee = Employee::from_value(&Value::Map(vec![(Value::from("name"), Value::from("Emil"))])).unwrap();
+        assert_eq!((employee.age, employee.active), (18, true));
+        assert!(database.export::<Shape, _>(Vec::new(), Format::Csv).is_err());
+        std::fs::remove_dir_all("data/exports").unwrap();
+    }
+
+    #[test]
+    fn schemas() {
+        let field = |name: &str, ty: &str, attributes: &[&str]| Field {
+            name: String::from(name),
+            ty: String::from(ty),
+            attributes: attributes.iter().map(|attribute| String::from(*attribute)).collect()
+        };
+
+        let schema = Employee::schema();
+        assert_eq!(schema, Schema {
+            name: String::from("Employee"),
+            collection: String::from("employees"),
+            id: Some(String::from("name")),
+            hash: Employee::hash(),
+            fields: vec![
+                field("name", "String", &["id"]),
+                field("age", "u16", &["default = 18"]),
+                field("tags", "Vec<String>", &["default"]),
+                field("visits", "u32", &["skip"]),
+                field("active", "bool", &["skip", "default = true"])
+            ],
+            variants: Vec::new()
+        });
+        assert!(schema.field("tags").unwrap().has("default"));
+        assert!(!schema.field("active").unwrap().is_stored());
+        assert_eq!(schema.to_string(), format!("\
+// Collection \"employees\", hash {:016x}
+struct Employee {{
+    #[id] name: String,
+    #[default = 18] age: u16,
+    #[default] tags: Vec<String>,
+    #[skip] visits: u32,
+    #[skip] #[default = true] active: bool,
+}}", Employee::hash()));
+
+        let schema = CompactResident::schema();
+        assert_eq!(schema.field("id").unwrap().attributes, vec!["id", "varint"]);
+        assert_eq!(schema.field("history").unwrap().ty, "Vec<(i64, Option<u16>)>");
+        assert_eq!(Counter::schema().field("ratio").unwrap().attributes, Vec::<String>::new());
+        assert_eq!(Blob::schema().field("hash").unwrap().ty, "[u8;32]");
+        assert_eq!(Wrapper::<u8>::schema().field("values").unwrap().ty, "Vec<T>");
+
+        let schema = UserId::schema();
+        assert_eq!(schema.id, Some(String::from("0")));
+        assert!(schema.to_string().ends_with("struct UserId(#[id] u64);"));
+
+        // The variants of `Shape` name their ids differently.
+        let schema = Shape::schema();
+        assert_eq!(schema.id, None);
+        assert_eq!(schema.variant("Circle").unwrap().fields, vec![field("0", "u32", &["id"]), field("1", "u64", &[])]);
+        assert!(schema.to_string().ends_with("\
+enum Shape {
+    Point {
+        #[id] id: u32,
+    },
+    Circle(#[id] u32, u64),
+    Square(#[id] u32, u64),
+}"));
+    }
+
+    #[test]
+    fn raw() {
+        let _ = std::fs::remove_dir_all("data/raw");
+        let database = Database::new("data/raw");
+        let resident = Resident {
+            name: String::from("Ann"),
+            address: Address { street: String::from("Main"), zip: 8000 },
+            previous: vec![Address { street: String::new(), zip: 1 }],
+            status: Status::Renamed(String::from("Anna"), 2),
+            age: 31
+        };
+        database.create(&resident).unwrap();
+        assert_eq!(database.read_raw("residents", &Value::from("Ann")).unwrap(), resident.to_value());
+
+        let compact = CompactResident {
+            id: 3,
+            address: Address { street: String::from("Side"), zip: 8001 },
+            history: vec![(-5, Some(300)), (7, None)],
+            state: Status::Suspended { until: 99 }
+        };
+        database.create(&compact).unwrap();
+        assert_eq!(database.read_raw("compact-residents", &Value::from(3u32)).unwrap(), compact.to_value());
+        let counter = Counter { id: 1000, delta: -3, samples: vec![1, 128, 70000], ratio: 0.5 };
+        database.create(&counter).unwrap();
+        assert_eq!(database.read_raw("counters", &Value::from(1000u64)).unwrap(), counter.to_value());
+
+        let profile = Profile {
+            id: 1,
+            nickname: None,
+            partner: Some(Box::new(Profile { id: 2, nickname: Some(String::from("Bo")), partner: None, shared: Arc::new(vec![1]) })),
+            shared: Arc::new(vec![2, 3])
+        };
+        database.create(&profile).unwrap();
+        assert_eq!(database.read_raw("profiles", &Value::from(1u32)).unwrap(), profile.to_value());
+        let blob = Blob { hash: [7; 32], pair: (1, String::from("one")), marker: Marker };
+        database.create(&blob).unwrap();
+        assert_eq!(database.read_raw("blobs", &blob.hash.to_value()).unwrap(), blob.to_value());
+
+        // Entries written with an older layout are decoded with it.
+        let circle = Shape1::Circle(1, 5);
+        database.create(&circle).unwrap();
+        database.create(&Shape::Square(2, 3)).unwrap();
+        assert_eq!(database.read_raw("shapes", &Value::from(1u32)).unwrap(), circle.to_value());
+        assert_eq!(database.read_raw("shapes", &Value::from(2u32)).unwrap(), Shape::Square(2, 3).to_value());
+
+        assert!(database.read_raw("shapes", &Value::from(4u32)).is_err());
+        assert!(database.read_raw("shapes", &Value::from("one")).is_err());
+        assert!(database.read_raw("cars", &Value::from(1u32)).is_err());
+        std::fs::remove_dir_all("data/raw").unwrap();
+    }
+
+    #[test]
+    fn register() {
+        let _ = std::fs::remove_dir_all("data/register");
+        let database = Database::new("data/register");
+        database.register::<Person2>().unwrap();
+        database.create(&Person2 { name: String::from("Peter"), age: 25, text: String::new() }).unwrap();
+        database.register::<Person>().unwrap();
+        database.create(&Person { name: String::from("Anna"), age: 31 }).unwrap();
+        // Person2 doesn't read entries written by Person, converting them needs `#[from(Person)]`.
+        let error = database.register::<Person2>().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Incompatible(String::from("persons")));
+        assert!(error.to_string().contains(&format!("{:016x} written by Person,", Person::hash())));
+        assert_eq!(database.schemas("persons").unwrap(), vec![Person2::schema(), Person::schema()]);
+
+        // Employee reads the layouts before its `#[default]` fields were added.
+        database.create(&Employee1 { name: String::from("Anna") }).unwrap();
+        database.create(&Employee2 { name: String::from("Beat"), age: 33 }).unwrap();
+        assert_eq!(Employee::hashes(), vec![Employee::hash(), Employee2::hash(), Employee1::hash()]);
+        database.register::<Employee>().unwrap();
+        assert!(database.register::<Employee2>().is_err());
+        database.create(&Shape1::Point { id: 1 }).unwrap();
+        database.register::<Shape>().unwrap();
+        assert!(database.schemas("cars").is_err());
+
+        // Once every entry is upgraded, types no longer need to read the layouts they were upgraded from.
+        database.create(&LegacyNote { title: LegacyString(String::from("todo")), text: LegacyString(String::new()) }).unwrap();
+        database.register::<Note>().unwrap();
+        assert!(database.register::<UpgradedNote>().is_err());
+        database.upgrade_all::<Note>().unwrap();
+        database.register::<UpgradedNote>().unwrap();
+        assert_eq!(database.schemas("notes").unwrap(), vec![Note::schema()]);
+        assert_eq!(database.read::<UpgradedNote>(&String::from("todo")).unwrap().title, "todo");
+        database.delete_all::<UpgradedNote>().unwrap();
+        database.register::<LegacyNote>().unwrap();
+        assert!(database.schemas("notes").unwrap().is_empty());
+        database.create(&UpgradedNote { title: String::from("new"), text: String::new() }).unwrap();
+        assert_eq!(database.schemas("notes").unwrap(), vec![UpgradedNote::schema()]);
+        std::fs::remove_dir_all("data/register").unwrap();
+    }
+
+    #[test]
+    fn open() {
+        let _ = std::fs::remove_dir_all("data/open");
+        let path = std::path::Path::new("data/open");
+        assert!(Database::open(path, Options::new().create_if_missing(false)).is_err());
+        assert!(Database::open(path, Options::new().read_only(true)).is_err());
+        let database = Database::open(path, Options::new().durability(Durability::Sync)).unwrap();
+        assert!(path.join(".manifest").exists());
+        let peter = Person { name: String::from("Peter"), age: 25 };
+        database.create(&peter).unwrap();
+        database.update(&Person { age: 26, ..peter.clone() }).unwrap();
+        assert!(database.check(Repair::Report).unwrap().is_ok());
+
+        let read_only = Database::open(path, Options::new().read_only(true)).unwrap();
+        assert_eq!(read_only.read::<Person>(&peter.name).unwrap().age, 26);
+        assert_eq!(read_only.create(&Person { name: String::from("Anna"), age: 31 }).unwrap_err().kind(), &ErrorKind::ReadOnly);
+        assert_eq!(read_only.delete::<Person>(&peter.name).unwrap_err().kind(), &ErrorKind::ReadOnly);
+        assert!(read_only.check(Repair::Delete).is_err());
+
+        // The cache keeps entries read before, and drops them when they are written.
+        let cached = Database::open(path, Options::new().cache_size(1024)).unwrap();
+        assert_eq!(cached.read::<Person>(&peter.name).unwrap().age, 26);
+        let file = path.join(Person::NAME).join(Database::encode(&peter.name).unwrap());
+        let bytes = std::fs::read(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(cached.read::<Person>(&peter.name).unwrap().age, 26);
+        std::fs::write(&file, bytes).unwrap();
+        cached.update(&Person { age: 27, ..peter.clone() }).unwrap();
+        assert_eq!(cached.read::<Person>(&peter.name).unwrap().age, 27);
+        cached.delete::<Person>(&peter.name).unwrap();
+        assert!(cached.read::<Person>(&peter.name).is_err());
+
+        // Every entry of a database created by `open` has a header, so one without is corrupted.
+        std::fs::write(&file, peter.serialize()).unwrap();
+        assert!(matches!(database.read::<Person>(&peter.name).unwrap_err().kind(), ErrorKind::Corrupted(_)));
+        assert!(!database.check(Repair::Report).unwrap().is_ok());
+        std::fs::remove_file(&file).unwrap();
+
+        // Databases written before manifests were added get one, unless opened read-only.
+        std::fs::remove_file(path.join(".manifest")).unwrap();
+        Database::open(path, Options::new().read_only(true)).unwrap();
+        assert!(!path.join(".manifest").exists());
+        Database::open(path, Options::new().create_if_missing(false)).unwrap();
+        assert!(path.join(".manifest").exists());
+        let adopted = Database::open(path, Options::new()).unwrap();
+        std::fs::write(&file, peter.serialize()).unwrap();
+        assert_eq!(adopted.read::<Person>(&peter.name).unwrap().age, 25);
+
+        std::fs::write(path.join(".manifest"), seal(&7u32)).unwrap();
+        let error = Database::open(path, Options::new()).err().unwrap();
+        assert_eq!(error.kind(), &ErrorKind::UnsupportedFormat);
+        std::fs::remove_dir_all(path).unwrap();
+
+        // Other directories only become databases if they are empty and may be created.
+        std::fs::create_dir_all(path.join("documents")).unwrap();
+        std::fs::write(path.join("documents/notes.txt"), []).unwrap();
+        assert!(Database::open(path, Options::new()).is_err());
+        std::fs::remove_dir_all(path.join("documents")).unwrap();
+        assert!(Database::open(path, Options::new().create_if_missing(false)).is_err());
+        assert!(!path.join(".manifest").exists());
+        Database::open(path, Options::new()).unwrap();
+        assert!(path.join(".manifest").exists());
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
     #[test]
     fn auto_count() {
         let database = Database::new("data/auto-count");
         database.create_auto(&AutoNumber::new()).unwrap();
         database.create_auto(&AutoNumber::new()).unwrap();
         database.create_auto(&AutoNumber::new()).unwrap();
+
+        // Stray files don't stop new ids from being found.
+        let stray = std::path::Path::new("data/auto-count").join(AutoNumber::NAME).join("00.tmp");
+        std::fs::write(&stray, []).unwrap();
+        database.create_auto(&AutoNumber::new()).unwrap();
+        std::fs::remove_file(stray).unwrap();
     }
     
     #[test]
     fn thread_times() {
         let database = Arc::new(Database::new("data/thread-times"));
         for i in 0..6 {
-            let threads = (2 as u32).pow(i);
-            let workload = 128 as u32 / threads;
+            let threads = 2u32.pow(i);
+            let workload = 128 / threads;
             let start = Instant::now();
             let mut join_handles = Vec::new();
             for j in 0..threads {
                 let db = Arc::clone(&database);
                 join_handles.push(thread::spawn(move || {
                     for k in 0..workload {
-                        let num = j as u32 * workload + k as u32;
+                        let num = j * workload + k;
                         let mut number = Number::new(num);
                         db.create(&number).expect("Database create failed");
                         number = db.read(&number.id).expect("Database read failed");
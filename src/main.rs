@@ -0,0 +1,6 @@
+use database::Cli;
+use std::process;
+
+fn main() {
+    process::exit(Cli::new().run(std::env::args().skip(1)));
+}
@@ -0,0 +1,99 @@
+//! The manifest of a database, a file next to the collections identifying the directory as a database.
+//!
+//! The manifest records the version of the on-disk format and how entries are named and laid out.
+//! `Database::open` writes it when it creates a database, or opens one written before manifests were
+//! added, and refuses to open databases in a format this version doesn't know.
+
+use super::{Bytes, Error, Durability};
+use super::record;
+use std::fs;
+use std::path::Path;
+
+/// The name of the manifest file inside the database.
+pub(crate) const MANIFEST: &str = ".manifest";
+
+/// The version of the on-disk format written by this version: a directory per collection holding
+/// an entry file per id, entry files with a checksum header, and the catalog.
+pub(crate) const FORMAT_VERSION: u32 = 1;
+
+/// How ids are turned into entry names, as hexadecimal digits of the encoded id.
+const ID_ENCODING: &str = "hex";
+
+/// How entries are spread over directories, in a single directory per collection.
+const SHARDING: &str = "collection";
+
+#[derive(Bytes, Debug, Clone, PartialEq)]
+pub(crate) struct Manifest {
+    /// The version of the on-disk format.
+    format: u32,
+    id_encoding: String,
+    sharding: String,
+    /// Whether every entry starts with a checksum header, so entries without one are corrupted.
+    /// Unset for databases written before manifests were added, which may hold entries without one.
+    #[default] checksums: bool
+}
+
+impl Manifest {
+    /// Returns the manifest of databases created by this version.
+    pub(crate) fn current() -> Manifest {
+        Manifest {
+            format: FORMAT_VERSION,
+            id_encoding: String::from(ID_ENCODING),
+            sharding: String::from(SHARDING),
+            checksums: true
+        }
+    }
+
+    /// Returns the manifest of databases written before manifests were added.
+    pub(crate) fn adopted() -> Manifest {
+        Manifest { checksums: false, ..Manifest::current() }
+    }
+
+    pub(crate) fn checksums(&self) -> bool {
+        self.checksums
+    }
+
+    /// Reads the manifest of the database at `path`, which is `None` if it doesn't exist.
+    pub(crate) fn load(path: &Path) -> Result<Option<Manifest>, Error> {
+        let path = path.join(MANIFEST);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&path)?;
+        Manifest::deserialize_from(&mut record::open(&bytes, &path, true)?)
+            .map(Some)
+            .map_err(|error| Error::unsupported_format(format!(
+                "The manifest \"{}\" can't be read, the database was likely written by a newer version: {}", path.display(), error
+            )))
+    }
+
+    /// Fails unless the database at `path` is in the format written by this version.
+    pub(crate) fn check(&self, path: &Path) -> Result<(), Error> {
+        let current = Manifest::current();
+        if self.format > current.format {
+            return Err(Error::unsupported_format(format!(
+                "The database \"{}\" has format version {}, this version only reads up to {}", path.display(), self.format, current.format
+            )));
+        }
+        if self.id_encoding != current.id_encoding || self.sharding != current.sharding {
+            return Err(Error::unsupported_format(format!(
+                "The database \"{}\" names entries by {} ids and stores them by {}, which this version doesn't read",
+                path.display(), self.id_encoding, self.sharding
+            )));
+        }
+        Ok(())
+    }
+
+    /// Writes the manifest of the database at `path`, creating the directory if needed.
+    /// The manifest is replaced at once, so a failed write leaves the previous one in place.
+    pub(crate) fn write(&self, path: &Path, durability: Durability) -> Result<(), Error> {
+        fs::create_dir_all(path)?;
+        let temporary = path.join(format!("{}.tmp", MANIFEST));
+        let file = fs::File::create(&temporary)?;
+        std::io::Write::write_all(&mut &file, &record::seal(self))?;
+        durability.sync_file(&file)?;
+        fs::rename(&temporary, path.join(MANIFEST))?;
+        durability.sync_directory(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! How `Database::open` opens a database.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// When writes reach the disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Durability {
+    /// Entries are written and flushed, leaving it to the operating system when they reach the disk.
+    /// Writes that returned may be lost on a power failure, but not when the process crashes.
+    Flush,
+    /// Entry files, and the directories holding them, are synced to the disk before a write returns.
+    Sync
+}
+
+impl Durability {
+    /// Syncs a written file if required.
+    pub(crate) fn sync_file(self, file: &File) -> io::Result<()> {
+        match self {
+            Durability::Flush => Ok(()),
+            Durability::Sync => file.sync_all()
+        }
+    }
+
+    /// Syncs a directory whose entries were created, renamed or removed if required.
+    /// Directories can only be synced on Unix, elsewhere the file system is trusted to keep them.
+    pub(crate) fn sync_directory(self, directory: &Path) -> io::Result<()> {
+        match self {
+            Durability::Sync if cfg!(unix) => File::open(directory)?.sync_all(),
+            _ => Ok(())
+        }
+    }
+}
+
+/// The options of `Database::open`, set by chaining its methods onto `Options::new()`.
+///
+/// ```
+/// # use database::{Database, Options, Durability};
+/// # let _ = std::fs::remove_dir_all("data/doc-options");
+/// let database = Database::open("data/doc-options", Options::new().durability(Durability::Sync).cache_size(1 << 20))?;
+/// # drop(database);
+/// # std::fs::remove_dir_all("data/doc-options").unwrap();
+/// # Ok::<(), database::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub(crate) create_if_missing: bool,
+    pub(crate) read_only: bool,
+    pub(crate) durability: Durability,
+    pub(crate) cache_size: usize
+}
+
+impl Options {
+    /// Returns the default options, creating the database if it is missing, writable,
+    /// with `Durability::Flush` and without a cache.
+    pub fn new() -> Options {
+        Options {
+            create_if_missing: true,
+            read_only: false,
+            durability: Durability::Flush,
+            cache_size: 0
+        }
+    }
+
+    /// Sets whether a missing database is created, or opening it fails.
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Options {
+        self.create_if_missing = create_if_missing;
+        self
+    }
+
+    /// Sets whether every write fails. Read-only databases are never created, and the manifest
+    /// of databases written before manifests were added isn't written either.
+    pub fn read_only(mut self, read_only: bool) -> Options {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets when writes reach the disk.
+    pub fn durability(mut self, durability: Durability) -> Options {
+        self.durability = durability;
+        self
+    }
+
+    /// Sets how many bytes of recently read entries are kept in memory, `0` to read every entry from
+    /// its file. The cache only sees writes through this `Database`, so it should be left off if other
+    /// processes or `Database` values write to the same directory.
+    pub fn cache_size(mut self, cache_size: usize) -> Options {
+        self.cache_size = cache_size;
+        self
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new()
+    }
+}
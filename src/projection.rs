@@ -0,0 +1,13 @@
+use super::{Bytes, Error};
+
+/// A struct holding some of the fields of a stored type, used by `Database::read_fields`.
+///
+/// Implemented by the structs generated by `#[projection(Name(field, ...))]`, which decode
+/// the listed fields and skip over the others using `Bytes::skip`.
+pub trait Projection: Sized {
+    /// The type the fields are taken from.
+    type Source: Bytes;
+
+    /// Decodes the fields from an encoded `Source` at the front of `bytes` and advances it past the value.
+    fn project_from(bytes: &mut &[u8]) -> Result<Self, Error>;
+}
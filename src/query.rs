@@ -0,0 +1,177 @@
+use super::{Database, Store, Bytes, Value, Error};
+use std::cmp::Ordering;
+
+/// How a `Filter` compares the field of a record to its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual
+}
+
+impl Comparison {
+    fn accepts(self, ordering: Option<Ordering>) -> bool {
+        match self {
+            Comparison::Equal => ordering == Some(Ordering::Equal),
+            Comparison::NotEqual => ordering != Some(Ordering::Equal),
+            Comparison::Less => ordering == Some(Ordering::Less),
+            Comparison::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Comparison::Greater => ordering == Some(Ordering::Greater),
+            Comparison::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+        }
+    }
+}
+
+/// The order `Query::order_by` sorts records in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Ascending,
+    Descending
+}
+
+/// Compares the field at `field` of a record to `value`, see `Value::field` and `Value::compare`.
+/// Records without the field only match `Comparison::NotEqual`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub field: String,
+    pub comparison: Comparison,
+    pub value: Value
+}
+
+impl Filter {
+    /// Returns whether `record`, the value of a record, passes the filter.
+    pub fn matches(&self, record: &Value) -> bool {
+        let ordering = record.field(&self.field).and_then(|field| field.compare(&self.value));
+        self.comparison.accepts(ordering)
+    }
+}
+
+/// Selects records of a collection by their fields, used with `Database::select`.
+///
+/// ```no_run
+/// # use database::{Bytes, Store, Database, Query, Comparison, Direction};
+/// # #[derive(Bytes, Store)]
+/// # struct Person { #[id] name: String, age: u16 }
+/// # let database = Database::new("database");
+/// let query = Query::new()
+///     .filter("age", Comparison::Greater, 30)
+///     .order_by("age", Direction::Ascending)
+///     .limit(10);
+/// let persons = database.select::<Person>(&query).unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    pub id: Option<Value>,
+    pub filters: Vec<Filter>,
+    pub order: Option<(String, Direction)>,
+    pub limit: Option<usize>
+}
+
+impl Query {
+    /// Creates a query selecting every record.
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    /// Selects only the record whose id equals `id`, compared like `Value::compare` does.
+    /// Only that record is read, as its entry name follows from the id.
+    pub fn id<V>(mut self, id: V) -> Query
+        where V: Into<Value>
+    {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Selects only records passing the filter, in addition to the filters given before.
+    pub fn filter<F, V>(mut self, field: F, comparison: Comparison, value: V) -> Query
+        where F: Into<String>, V: Into<Value>
+    {
+        self.filters.push(Filter {
+            field: field.into(),
+            comparison,
+            value: value.into()
+        });
+        self
+    }
+
+    /// Sorts the records by `field`, in the order of `Value::total_cmp`. Records without the field
+    /// come first in ascending order and last in descending order. Records with equal values keep
+    /// the order they are read in.
+    pub fn order_by<F>(mut self, field: F, direction: Direction) -> Query
+        where F: Into<String>
+    {
+        self.order = Some((field.into(), direction));
+        self
+    }
+
+    /// Selects at most `limit` records, after sorting them.
+    pub fn limit(mut self, limit: usize) -> Query {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Returns whether `record`, the value of a record, passes every filter.
+    pub fn matches(&self, record: &Value) -> bool {
+        self.filters.iter().all(|filter| filter.matches(record))
+    }
+}
+
+impl Database {
+    /// Reads the entries of `T` selected by `query`.
+    /// Records are compared by their `Value`, so every record read is converted once.
+    pub fn select<T>(&self, query: &Query) -> Result<Vec<T>, Error>
+        where T: Store
+    {
+        let names = match &query.id {
+            Some(id) => match T::Id::from_value(id) {
+                Ok(found) if found.to_value().compare(id) == Some(Ordering::Equal) => {
+                    let name = self.entry_name::<T>(&found)?;
+                    if self.path().join(T::NAME).join(&name).is_file() { vec![name] } else { Vec::new() }
+                },
+                _ => Vec::new()
+            },
+            None => self.entry_names(T::NAME)?
+        };
+        let mut records = Vec::new();
+        for name in names {
+            let object = self.read_entry_with(T::NAME, &name, |bytes| T::deserialize_from(&mut &bytes[..]))?;
+            let value = object.to_value();
+            if query.matches(&value) {
+                records.push((value, object));
+            }
+        }
+
+        if let Some((field, direction)) = &query.order {
+            records.sort_by(|(a, _), (b, _)| {
+                let ordering = match (a.field(field), b.field(field)) {
+                    (Some(a), Some(b)) => a.total_cmp(b),
+                    (a, b) => a.is_some().cmp(&b.is_some())
+                };
+                match direction {
+                    Direction::Ascending => ordering,
+                    Direction::Descending => ordering.reverse()
+                }
+            });
+        }
+        if let Some(limit) = query.limit {
+            records.truncate(limit);
+        }
+
+        Ok(records.into_iter().map(|(_, object)| object).collect())
+    }
+
+    /// Returns the number of entries of `T` selected by `query`.
+    /// Without an id or filters, the entries are counted without being read.
+    pub fn count<T>(&self, query: &Query) -> Result<usize, Error>
+        where T: Store
+    {
+        if query.id.is_none() && query.filters.is_empty() {
+            let count = self.entry_names(T::NAME)?.len();
+            return Ok(query.limit.map_or(count, |limit| count.min(limit)));
+        }
+        self.select::<T>(query).map(|records| records.len())
+    }
+}
@@ -0,0 +1,86 @@
+//! The format of entry files.
+//!
+//! Entries start with `MAGIC`, followed by the CRC32C checksum of the encoded value as `u32`
+//! and the encoded value itself. Files written before checksums were added hold only the
+//! encoded value. They are still read, and `Database::upgrade_all` adds the header to them,
+//! unless the manifest records that every entry of the database has a header.
+
+use super::{Bytes, Error};
+use std::path::Path;
+
+/// Marks an entry file that starts with a header.
+const MAGIC: [u8; 8] = *b"dbrec\0\x01\0";
+const HEADER_SIZE: usize = MAGIC.len() + 4;
+
+/// The table of the CRC32C (Castagnoli) polynomial in reversed bit order, computed at compile time.
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Computes the CRC32C checksum of `bytes`.
+pub(crate) fn crc32c(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Returns the contents of the entry file storing `object`.
+pub(crate) fn seal<T>(object: &T) -> Vec<u8>
+    where T: Bytes
+{
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + object.size_hint());
+    bytes.extend_from_slice(&MAGIC);
+    bytes.extend_from_slice(&[0; 4]);
+    object.serialize_into(&mut bytes);
+    let checksum = crc32c(&bytes[HEADER_SIZE..]);
+    bytes[MAGIC.len()..HEADER_SIZE].copy_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/// Returns the encoded value of the entry file at `path` holding `bytes`, after verifying its checksum.
+/// Files without a header are corrupted if `required`, like `verify` says.
+pub(crate) fn open<'a>(bytes: &'a [u8], path: &Path, required: bool) -> Result<&'a [u8], Error> {
+    verify(bytes, required).map_err(|reason| Error::corrupted(path, reason))
+}
+
+/// Returns the encoded value of an entry file holding `bytes`, or why it is corrupted.
+///
+/// Files without a header are read as written before checksums were added, unless the header is
+/// `required`. Files starting with bytes close to `MAGIC` have a damaged header rather than none,
+/// as the hash a value starts with only matches it in most bytes by chance.
+pub(crate) fn verify(bytes: &[u8], required: bool) -> Result<&[u8], String> {
+    if !bytes.starts_with(&MAGIC) {
+        let differing = MAGIC.iter().zip(bytes).filter(|(magic, byte)| magic != byte).count();
+        if bytes.starts_with(&MAGIC[..5]) || (bytes.len() >= MAGIC.len() && differing <= 2) {
+            return Err(String::from("the header is damaged"));
+        }
+        if required {
+            return Err(String::from("the header is missing"));
+        }
+        return Ok(bytes);
+    }
+    if bytes.len() < HEADER_SIZE {
+        return Err(String::from("the header is incomplete"));
+    }
+    let (header, value) = bytes.split_at(HEADER_SIZE);
+    let mut stored = [0; 4];
+    stored.copy_from_slice(&header[MAGIC.len()..]);
+    let stored = u32::from_le_bytes(stored);
+    let computed = crc32c(value);
+    if stored != computed {
+        return Err(format!("the checksum {:#010x} doesn't match the stored {:#010x}", computed, stored));
+    }
+    Ok(value)
+}
@@ -0,0 +1,127 @@
+use super::Bytes;
+use std::fmt;
+
+/// A description of a stored type, returned by `Store::schema`.
+///
+/// Generated by `#[derive(Store)]` from the declaration of the type, so tools can list the
+/// fields of a collection without hand-written metadata. The database records the schema of
+/// every type it writes in its catalog.
+#[derive(Bytes, Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    /// The name of the type.
+    pub name: String,
+    /// The name of the collection holding the records, `Store::NAME`.
+    pub collection: String,
+    /// The name of the field marked with `#[id]`, or `None` if the variants of an enum name it differently.
+    pub id: Option<String>,
+    /// The hash of the current layout, `Bytes::hash`.
+    pub hash: u64,
+    /// The fields of a struct in declaration order, including fields that aren't stored.
+    pub fields: Vec<Field>,
+    /// The variants of an enum in declaration order, empty for structs.
+    pub variants: Vec<Variant>
+}
+
+/// A variant of an enum described by a `Schema`.
+#[derive(Bytes, Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<Field>
+}
+
+/// A field described by a `Schema`. Fields of tuple structs and tuple variants are named by their index.
+#[derive(Bytes, Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    /// The type as declared, like `Vec<String>`.
+    pub ty: String,
+    /// The attributes of the field as declared, without `#[` and `]`, like `default = 18`.
+    /// `varint` is listed for every stored field if the type is marked with `#[varint]`.
+    pub attributes: Vec<String>
+}
+
+impl Schema {
+    /// Returns the field of a struct named `name`.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns the variant of an enum named `name`.
+    pub fn variant(&self, name: &str) -> Option<&Variant> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+}
+
+impl Field {
+    /// Returns whether the field has the attribute `name`, with or without a value.
+    pub fn has(&self, name: &str) -> bool {
+        self.attributes.iter().any(|attribute| attribute == name || attribute.starts_with(&format!("{} ", name)))
+    }
+
+    /// Returns whether the field is stored, which it is unless it is marked with `#[skip]`.
+    pub fn is_stored(&self) -> bool {
+        !self.has("skip")
+    }
+}
+
+/// Writes a field like it is declared, with its name unless it belongs to a tuple.
+fn write_field(f: &mut fmt::Formatter<'_>, field: &Field, named: bool) -> fmt::Result {
+    for attribute in &field.attributes {
+        write!(f, "#[{}] ", attribute)?;
+    }
+    if named {
+        write!(f, "{}: ", field.name)?;
+    }
+    write!(f, "{}", field.ty)
+}
+
+/// Writes the fields like they are declared, in braces or parentheses, or nothing if there are none.
+fn write_fields(f: &mut fmt::Formatter<'_>, fields: &[Field], indent: &str) -> fmt::Result {
+    if fields.is_empty() {
+        Ok(())
+    } else if is_tuple(fields) {
+        write!(f, "(")?;
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write_field(f, field, false)?;
+        }
+        write!(f, ")")
+    } else {
+        writeln!(f, " {{")?;
+        for field in fields {
+            write!(f, "{}    ", indent)?;
+            write_field(f, field, true)?;
+            writeln!(f, ",")?;
+        }
+        write!(f, "{}}}", indent)
+    }
+}
+
+fn is_tuple(fields: &[Field]) -> bool {
+    fields.iter().all(|field| field.name.parse::<usize>().is_ok())
+}
+
+/// Shows the schema as the declaration of the type, preceded by a comment naming its collection and hash.
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "// Collection \"{}\", hash {:016x}", self.collection, self.hash)?;
+        if self.variants.is_empty() {
+            write!(f, "struct {}", self.name)?;
+            write_fields(f, &self.fields, "")?;
+            if is_tuple(&self.fields) {
+                write!(f, ";")?;
+            }
+            Ok(())
+        } else {
+            writeln!(f, "enum {} {{", self.name)?;
+            for variant in &self.variants {
+                write!(f, "    {}", variant.name)?;
+                write_fields(f, &variant.fields, "    ")?;
+                writeln!(f, ",")?;
+            }
+            write!(f, "}}")
+        }
+    }
+}
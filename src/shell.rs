@@ -0,0 +1,270 @@
+//! The query language of the `database shell` and `database query` commands.
+
+use super::{Query, Comparison, Direction, Value, Error};
+
+pub(crate) const HELP: &str = "\
+Statements:
+    select <collection> [where <condition> [and <condition>]...] [order by <field> [asc | desc]] [limit <n>]
+    count <collection> [where <condition> [and <condition>]...]
+    get <collection> <id>
+    delete <collection> (<id> | where <condition> [and <condition>]...)
+    collections
+    help
+    exit
+
+Conditions compare a field to a value with =, !=, <, <=, > or >=, like `age > 30`.
+Nested fields are joined by dots, like `address.city`. Values are numbers, strings in
+double or single quotes, true, false and null.";
+
+/// A parsed statement, with the name of the collection it reads and the records it selects.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Statement {
+    Select(String, Query),
+    Count(String, Query),
+    Get(String, Query),
+    Delete(String, Query),
+    Collections,
+    Help,
+    Exit
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Literal(Value),
+    Operator(Comparison)
+}
+
+/// Parses a statement, ignoring the case of keywords.
+pub(crate) fn parse(input: &str) -> Result<Statement, Error> {
+    let mut parser = Parser { tokens: tokenize(input)?, position: 0 };
+    let keyword = parser.word("a statement")?.to_lowercase();
+    let statement = match keyword.as_str() {
+        "select" => {
+            let collection = parser.word("a collection")?;
+            let mut query = parser.conditions(Query::new())?;
+            if parser.keyword("order") {
+                parser.expect("by")?;
+                let field = parser.word("a field")?;
+                let direction = if parser.keyword("desc") {
+                    Direction::Descending
+                } else {
+                    parser.keyword("asc");
+                    Direction::Ascending
+                };
+                query = query.order_by(field, direction);
+            }
+            if parser.keyword("limit") {
+                query = match parser.peek() {
+                    Some(Token::Literal(Value::UInt(limit))) => query.limit(*limit as usize),
+                    _ => return Err(parser.error("a number of records"))
+                };
+                parser.position += 1;
+            }
+            Statement::Select(collection, query)
+        },
+        "count" => {
+            let collection = parser.word("a collection")?;
+            Statement::Count(collection, parser.conditions(Query::new())?)
+        },
+        "get" => {
+            let collection = parser.word("a collection")?;
+            Statement::Get(collection, Query::new().id(parser.literal()?))
+        },
+        "delete" => {
+            let collection = parser.word("a collection")?;
+            let query = match parser.peek() {
+                Some(Token::Literal(_)) => Query::new().id(parser.literal()?),
+                _ => {
+                    let query = parser.conditions(Query::new())?;
+                    if query.filters.is_empty() {
+                        return Err(parser.error("an id or `where`"));
+                    }
+                    query
+                }
+            };
+            Statement::Delete(collection, query)
+        },
+        "collections" => Statement::Collections,
+        "help" => Statement::Help,
+        "exit" | "quit" => Statement::Exit,
+        _ => return Err(Error::new(format!("Unknown statement \"{}\", try `help`.", keyword)))
+    };
+    match parser.peek() {
+        None => Ok(statement),
+        Some(_) => Err(parser.error("the end of the statement"))
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Returns an error for the next token, which isn't `expected`.
+    fn error(&self, expected: &str) -> Error {
+        match self.peek() {
+            Some(token) => Error::new(format!("Expected {} but found {}.", expected, describe(token))),
+            None => Error::new(format!("Expected {} but the statement ended.", expected))
+        }
+    }
+
+    fn word(&mut self, expected: &str) -> Result<String, Error> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.position += 1;
+                Ok(word)
+            },
+            _ => Err(self.error(expected))
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, Error> {
+        match self.peek() {
+            Some(Token::Literal(value)) => {
+                let value = value.clone();
+                self.position += 1;
+                Ok(value)
+            },
+            _ => Err(self.error("a value"))
+        }
+    }
+
+    /// Consumes the next token if it is the keyword `keyword`.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn expect(&mut self, keyword: &str) -> Result<(), Error> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{}`", keyword)))
+        }
+    }
+
+    /// Parses an optional `where` clause into filters of `query`.
+    fn conditions(&mut self, mut query: Query) -> Result<Query, Error> {
+        if !self.keyword("where") {
+            return Ok(query);
+        }
+        loop {
+            let field = self.word("a field")?;
+            let comparison = match self.peek() {
+                Some(Token::Operator(comparison)) => *comparison,
+                _ => return Err(self.error("a comparison"))
+            };
+            self.position += 1;
+            query = query.filter(field, comparison, self.literal()?);
+            if !self.keyword("and") {
+                return Ok(query);
+            }
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("`{}`", word),
+        Token::Literal(value) => format!("the value {:?}", value),
+        Token::Operator(comparison) => format!("the comparison {:?}", comparison)
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('n') => string.push('\n'),
+                        Some('t') => string.push('\t'),
+                        Some(escaped) => string.push(escaped),
+                        None => return Err(Error::new(String::from("Unterminated string.")))
+                    },
+                    Some(end) if end == c => break,
+                    Some(other) => string.push(other),
+                    None => return Err(Error::new(String::from("Unterminated string.")))
+                }
+            }
+            tokens.push(Token::Literal(Value::String(string)));
+        } else if c.is_ascii_digit() || c == '-' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_ascii_digit() || c == '.' || (c == '-' && (number.is_empty() || number.ends_with(['e', 'E']))) || c == 'e' || c == 'E' {
+                    number.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Literal(number_value(&number)?));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' || c == '.' {
+                    word.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(match word.to_lowercase().as_str() {
+                "true" => Token::Literal(Value::Bool(true)),
+                "false" => Token::Literal(Value::Bool(false)),
+                "null" => Token::Literal(Value::Null),
+                _ => Token::Word(word)
+            });
+        } else {
+            let mut operator = String::new();
+            while let Some(&c) = chars.peek() {
+                if "=!<>".contains(c) {
+                    operator.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            let comparison = match operator.as_str() {
+                "=" | "==" => Comparison::Equal,
+                "!=" | "<>" => Comparison::NotEqual,
+                "<" => Comparison::Less,
+                "<=" => Comparison::LessOrEqual,
+                ">" => Comparison::Greater,
+                ">=" => Comparison::GreaterOrEqual,
+                _ => return Err(Error::new(format!("Unexpected character '{}'.", c)))
+            };
+            tokens.push(Token::Operator(comparison));
+        }
+    }
+    Ok(tokens)
+}
+
+fn number_value(number: &str) -> Result<Value, Error> {
+    let invalid = || Error::new(format!("Invalid number {}.", number));
+    if number.contains(['.', 'e', 'E']) {
+        number.parse().map(Value::Float).map_err(|_| invalid())
+    } else if number.starts_with('-') {
+        number.parse().map(Value::Int).map_err(|_| invalid())
+    } else {
+        number.parse().map(Value::UInt).map_err(|_| invalid())
+    }
+}
@@ -1,4 +1,4 @@
-use super::Bytes;
+use super::{Bytes, Schema, Field};
 
 /// This trait has to be implemented on a struct that should be stored.
 /// Note that the `Bytes` trait has to be implemented too as `Store` is a supertrait of `Bytes`.
@@ -8,4 +8,19 @@ pub trait Store: Bytes + Send + Sync {
     type Id: Bytes + Send + Sync;
     const NAME: &'static str;
     fn id(&self) -> &Self::Id;
-}
\ No newline at end of file
+
+    /// Describes the type, its collection and its fields.
+    /// Generated by the derive, and made up of the names in `Bytes::FIELDS` without types otherwise.
+    fn schema() -> Schema where Self: Sized {
+        Schema {
+            name: String::from(std::any::type_name::<Self>()),
+            collection: String::from(Self::NAME),
+            id: None,
+            hash: Self::hash(),
+            fields: Self::FIELDS.iter()
+                .map(|name| Field { name: String::from(*name), ty: String::new(), attributes: Vec::new() })
+                .collect(),
+            variants: Vec::new()
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use super::Error;
+use super::json;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// A value of any stored type, used to inspect and query records without knowing their types.
+///
+/// Values are produced by `Bytes::to_value` and turned back by `Bytes::from_value`. Types without
+/// a description of their own, like types implementing `Bytes` by hand, produce their encoded bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// `()` and `None`.
+    Null,
+    Bool(bool),
+    /// A signed integer.
+    Int(i128),
+    /// An unsigned integer.
+    UInt(u128),
+    Float(f64),
+    /// A string, a `char`, or a type shown as a string, like addresses and paths.
+    String(String),
+    /// A sequence, set, array or tuple.
+    List(Vec<Value>),
+    /// The entries of a map.
+    Map(Vec<(Value, Value)>),
+    /// The stored fields of a struct. Fields of tuple structs are named by their index.
+    Struct(Vec<(String, Value)>),
+    /// An enum variant, holding its fields like `Struct` does, or `Null` if it has none.
+    Variant(String, Box<Value>),
+    /// The encoded value of a type that doesn't describe itself.
+    Bytes(Vec<u8>)
+}
+
+impl Value {
+    /// Returns the value at `path`, the names of nested fields joined by dots like `address.city`.
+    ///
+    /// Struct fields are looked up by name, list elements by index and map entries by string keys.
+    /// Variants are looked through, so the fields of a variant are found like those of a struct.
+    pub fn field(&self, path: &str) -> Option<&Value> {
+        path.split('.').try_fold(self, |value, name| value.get(name))
+    }
+
+    fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(fields) => fields.iter().find(|(field, _)| field == name).map(|(_, value)| value),
+            Value::Variant(_, fields) => fields.get(name),
+            Value::List(elements) => elements.get(name.parse::<usize>().ok()?),
+            Value::Map(entries) => entries.iter()
+                .find(|(key, _)| matches!(key, Value::String(key) if key == name))
+                .map(|(_, value)| value),
+            _ => None
+        }
+    }
+
+    /// Compares two values, or returns `None` if they can't be compared.
+    ///
+    /// Integers and floats compare by their numeric value regardless of their kind, so a query for
+    /// `30` matches a field of any integer type. Lists compare element by element, variants of the
+    /// same name by their fields. Other values are only ordered if they are equal.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::UInt(a), Value::UInt(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::UInt(b)) => Some(if *a < 0 { Ordering::Less } else { (*a as u128).cmp(b) }),
+            (Value::UInt(_), Value::Int(_)) => other.compare(self).map(Ordering::reverse),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::UInt(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Int(_), Value::Float(_)) | (Value::UInt(_), Value::Float(_)) => other.compare(self).map(Ordering::reverse),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Bytes(a), Value::Bytes(b)) => Some(a.cmp(b)),
+            (Value::List(a), Value::List(b)) => {
+                for (a, b) in a.iter().zip(b) {
+                    match a.compare(b)? {
+                        Ordering::Equal => {},
+                        ordering => return Some(ordering)
+                    }
+                }
+                Some(a.len().cmp(&b.len()))
+            },
+            (Value::Variant(a, a_fields), Value::Variant(b, b_fields)) if a == b => a_fields.compare(b_fields),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None
+        }
+    }
+
+    /// Compares two values in a total order, used to sort records.
+    ///
+    /// Values are ordered by their kind first: null, booleans, numbers, strings, bytes, lists, maps,
+    /// structs and variants. Numbers compare by their numeric value like `compare` does, with floats
+    /// ordered by `f64::total_cmp`, so NaN sorts after every other number, or before if it's negative.
+    /// Collections compare element by element, variants by their name and then their fields.
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::UInt(a), Value::UInt(b)) => a.cmp(b),
+            (Value::Int(a), Value::UInt(b)) => if *a < 0 { Ordering::Less } else { (*a as u128).cmp(b) },
+            (Value::Float(a), Value::Float(b)) => a.total_cmp(b),
+            (Value::Float(a), Value::Int(b)) => float_cmp(*a, *b < 0, b.unsigned_abs()),
+            (Value::Float(a), Value::UInt(b)) => float_cmp(*a, false, *b),
+            (Value::UInt(_) | Value::Int(_), Value::Int(_) | Value::Float(_)) => other.total_cmp(self).reverse(),
+            (Value::String(a), Value::String(b)) => a.cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => lexicographic(a, b, Value::total_cmp),
+            (Value::Map(a), Value::Map(b)) => lexicographic(a, b, |(a_key, a_value), (b_key, b_value)| {
+                a_key.total_cmp(b_key).then_with(|| a_value.total_cmp(b_value))
+            }),
+            (Value::Struct(a), Value::Struct(b)) => lexicographic(a, b, |(a_name, a_value), (b_name, b_value)| {
+                a_name.cmp(b_name).then_with(|| a_value.total_cmp(b_value))
+            }),
+            (Value::Variant(a, a_fields), Value::Variant(b, b_fields)) => a.cmp(b).then_with(|| a_fields.total_cmp(b_fields)),
+            _ => self.rank().cmp(&other.rank())
+        }
+    }
+
+    /// Returns the position of the kind of this value in the order of `total_cmp`.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::UInt(_) | Value::Float(_) => 2,
+            Value::String(_) => 3,
+            Value::Bytes(_) => 4,
+            Value::List(_) => 5,
+            Value::Map(_) => 6,
+            Value::Struct(_) => 7,
+            Value::Variant(..) => 8
+        }
+    }
+}
+
+/// Compares the float `a` exactly with the integer of magnitude `b`, which is negative if `negative`.
+fn float_cmp(a: f64, negative: bool, b: u128) -> Ordering {
+    if a.is_nan() {
+        return if a.is_sign_negative() { Ordering::Less } else { Ordering::Greater };
+    }
+    match (a < 0.0, negative) {
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => magnitude_cmp(a, b),
+        (true, true) => magnitude_cmp(-a, b).reverse()
+    }
+}
+
+/// Compares the non-negative float `a` exactly with `b`.
+fn magnitude_cmp(a: f64, b: u128) -> Ordering {
+    // Floats below 2^128 have a whole part that fits into `u128` exactly.
+    if a >= 2f64.powi(128) {
+        return Ordering::Greater;
+    }
+    let whole = a.trunc();
+    (whole as u128).cmp(&b).then(if a > whole { Ordering::Greater } else { Ordering::Equal })
+}
+
+/// Compares two slices element by element with `compare`, and then by their length.
+fn lexicographic<T, F>(a: &[T], b: &[T], compare: F) -> Ordering
+    where F: Fn(&T, &T) -> Ordering
+{
+    a.iter().zip(b)
+        .map(|(a, b)| compare(a, b))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// Returns `value`, parsing strings that hold a JSON array or object, like CSV cells of nested values do.
+/// Used by `Bytes::from_value` of types that aren't scalars.
+#[doc(hidden)]
+pub fn structured(value: &Value) -> Result<Cow<'_, Value>, Error> {
+    match value {
+        Value::String(text) if text.trim_start().starts_with(['[', '{']) => json::parse(text).map(Cow::Owned),
+        value => Ok(Cow::Borrowed(value))
+    }
+}
+
+/// Returns the name and the fields of a variant, which may also be given as its name alone,
+/// or as a map with the name as its only key, like JSON does.
+#[doc(hidden)]
+pub fn variant(value: &Value) -> Result<(&str, &Value), Error> {
+    match value {
+        Value::Variant(name, fields) => Ok((name, fields)),
+        Value::String(name) => Ok((name, &Value::Null)),
+        Value::Map(entries) => match entries.as_slice() {
+            [(Value::String(name), fields)] => Ok((name, fields)),
+            _ => Err(unexpected("a variant", value))
+        },
+        value => Err(unexpected("a variant", value))
+    }
+}
+
+/// Returns the error of `Bytes::from_value` for a value that isn't `expected`.
+pub(crate) fn unexpected(expected: &str, value: &Value) -> Error {
+    let found = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a bool",
+        Value::Int(_) | Value::UInt(_) => "an integer",
+        Value::Float(_) => "a float",
+        Value::String(_) => "a string",
+        Value::List(_) => "a list",
+        Value::Map(_) => "a map",
+        Value::Struct(_) => "a struct",
+        Value::Variant(_, _) => "a variant",
+        Value::Bytes(_) => "bytes"
+    };
+    match value {
+        Value::Int(_) | Value::UInt(_) | Value::Float(_) | Value::String(_) => {
+            Error::new(format!("Expected {} but found {} {}.", expected, found, json::to_string(value)))
+        },
+        _ => Error::new(format!("Expected {} but found {}.", expected, found))
+    }
+}
+
+macro_rules! impl_From_for_Value {
+    ($variant:ident as $as:ty: $($t:ty),+) => {
+        $(
+            impl From<$t> for Value {
+                fn from(value: $t) -> Value {
+                    Value::$variant(value as $as)
+                }
+            }
+        )*
+    }
+}
+
+impl_From_for_Value!(Int as i128: i8, i16, i32, i64, i128, isize);
+impl_From_for_Value!(UInt as u128: u8, u16, u32, u64, u128, usize);
+impl_From_for_Value!(Float as f64: f32, f64);
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
+impl From<char> for Value {
+    fn from(value: char) -> Value {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Value {
+        Value::String(String::from(value))
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::String(value)
+    }
+}
@@ -0,0 +1,296 @@
+use super::{Bytes, LegacyString, Error};
+use super::bytes::{deserialize_str, deserialize_legacy_str, deserialize_count};
+use std::collections::{HashMap, BTreeMap, HashSet, BTreeSet, VecDeque, BinaryHeap};
+use std::hash::{Hash, BuildHasher};
+use std::borrow::Cow;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::marker::PhantomData;
+use std::fmt;
+
+/// Decodes values that borrow from the encoded bytes instead of allocating, used by `Database::read_with`.
+///
+/// Strings are viewed as `&str` and vectors as a `Sequence` that decodes its elements while
+/// iterating. Types without a cheaper form, like numbers or maps, are decoded as owned values.
+///
+/// Derived types are viewed as owned values unless marked with `#[view(Name)]`, which
+/// generates a struct `Name<'a>` holding the view of every stored field. Types implementing
+/// `Bytes` by hand can implement `View` with `type Borrowed<'a> = Self` to be used in such structs.
+pub trait View: Bytes {
+    /// The borrowed form of the type.
+    type Borrowed<'a>;
+
+    /// Decodes a view from the front of `bytes` and advances it past the value.
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<Self::Borrowed<'a>, Error>;
+}
+
+macro_rules! impl_View_for_owned {
+    ($($t:ty),+) => {
+        $(
+            impl View for $t {
+                type Borrowed<'a> = $t;
+
+                fn view_from(bytes: &mut &[u8]) -> Result<$t, Error> {
+                    <$t>::deserialize_from(bytes)
+                }
+            }
+        )*
+    }
+}
+
+impl_View_for_owned!(
+    i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64, usize, isize, char, bool, (),
+    Duration, SystemTime, Ipv4Addr, Ipv6Addr, IpAddr, SocketAddr, PathBuf
+);
+
+macro_rules! impl_View_for_owned_collections {
+    ($($t:ident<$($p:ident),+> [$($bound:tt)+]),+) => {
+        $(
+            impl<$($bound)+> View for $t<$($p),+> {
+                type Borrowed<'a> = $t<$($p),+>;
+
+                fn view_from(bytes: &mut &[u8]) -> Result<$t<$($p),+>, Error> {
+                    <$t<$($p),+>>::deserialize_from(bytes)
+                }
+            }
+        )*
+    }
+}
+
+impl_View_for_owned_collections!(
+    HashMap<K, V, H> [K: Bytes + Hash + Eq, V: Bytes, H: BuildHasher + Default],
+    BTreeMap<K, V> [K: Bytes + Ord, V: Bytes],
+    HashSet<S, H> [S: Bytes + Hash + Eq, H: BuildHasher + Default],
+    BTreeSet<S> [S: Bytes + Ord],
+    BinaryHeap<S> [S: Bytes + Ord],
+    VecDeque<S> [S: Bytes]
+);
+
+impl<S: Bytes, const N: usize> View for [S; N] {
+    type Borrowed<'a> = [S; N];
+
+    fn view_from(bytes: &mut &[u8]) -> Result<[S; N], Error> {
+        <[S; N]>::deserialize_from(bytes)
+    }
+}
+
+impl View for String {
+    type Borrowed<'a> = &'a str;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<&'a str, Error> {
+        deserialize_str(bytes)
+    }
+}
+
+impl View for Cow<'_, str> {
+    type Borrowed<'a> = &'a str;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<&'a str, Error> {
+        deserialize_str(bytes)
+    }
+}
+
+impl View for LegacyString {
+    type Borrowed<'a> = &'a str;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<&'a str, Error> {
+        deserialize_legacy_str(bytes)
+    }
+}
+
+impl<S: View> View for Vec<S> {
+    type Borrowed<'a> = Sequence<'a, S>;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<Sequence<'a, S>, Error> {
+        Sequence::view_from(bytes)
+    }
+}
+
+impl<S: View + Clone> View for Cow<'_, [S]> {
+    type Borrowed<'a> = Sequence<'a, S>;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<Sequence<'a, S>, Error> {
+        Sequence::view_from(bytes)
+    }
+}
+
+impl<S: View> View for Option<S> {
+    type Borrowed<'a> = Option<S::Borrowed<'a>>;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<Option<S::Borrowed<'a>>, Error> {
+        Ok(if bool::deserialize_from(bytes)? {
+            Some(S::view_from(bytes)?)
+        } else {
+            None
+        })
+    }
+}
+
+impl<T: View, E: View> View for Result<T, E> {
+    type Borrowed<'a> = Result<T::Borrowed<'a>, E::Borrowed<'a>>;
+
+    fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<Result<T::Borrowed<'a>, E::Borrowed<'a>>, Error> {
+        Ok(if bool::deserialize_from(bytes)? {
+            Ok(T::view_from(bytes)?)
+        } else {
+            Err(E::view_from(bytes)?)
+        })
+    }
+}
+
+macro_rules! impl_View_for_pointers {
+    ($($t:ident),+) => {
+        $(
+            impl<S: View> View for $t<S> {
+                type Borrowed<'a> = S::Borrowed<'a>;
+
+                fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<S::Borrowed<'a>, Error> {
+                    S::view_from(bytes)
+                }
+            }
+        )*
+    }
+}
+
+impl_View_for_pointers!(Box, Rc, Arc);
+
+macro_rules! impl_View_for_tuples {
+    ($(($($t:ident),+)),+) => {
+        $(
+            impl<$($t: View),+> View for ($($t,)+) {
+                type Borrowed<'a> = ($($t::Borrowed<'a>,)+);
+
+                fn view_from<'a>(bytes: &mut &'a [u8]) -> Result<($($t::Borrowed<'a>,)+), Error> {
+                    Ok(($($t::view_from(bytes)?,)+))
+                }
+            }
+        )*
+    }
+}
+
+impl_View_for_tuples!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F),
+    (A, B, C, D, E, F, G),
+    (A, B, C, D, E, F, G, H),
+    (A, B, C, D, E, F, G, H, I),
+    (A, B, C, D, E, F, G, H, I, J),
+    (A, B, C, D, E, F, G, H, I, J, K),
+    (A, B, C, D, E, F, G, H, I, J, K, L)
+);
+
+/// The view of a `Vec<S>`, which decodes the views of its elements while iterating.
+pub struct Sequence<'a, S> {
+    len: usize,
+    bytes: &'a [u8],
+    element: PhantomData<fn() -> S>
+}
+
+impl<'a, S: View> Sequence<'a, S> {
+    /// Checks every element once, so iterating afterwards can't fail.
+    fn view_from(bytes: &mut &'a [u8]) -> Result<Sequence<'a, S>, Error> {
+        let size = deserialize_count(bytes)?;
+        let start = *bytes;
+        for _ in 0..size {
+            S::view_from(bytes)?;
+        }
+        Ok(Sequence {
+            len: size as usize,
+            bytes: &start[..start.len() - bytes.len()],
+            element: PhantomData
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> SequenceIter<'a, S> {
+        SequenceIter {
+            remaining: self.len,
+            bytes: self.bytes,
+            element: PhantomData
+        }
+    }
+
+    /// Returns the views of all elements.
+    pub fn to_vec(&self) -> Vec<S::Borrowed<'a>> {
+        self.iter().collect()
+    }
+}
+
+impl<'a> Sequence<'a, u8> {
+    /// Returns the stored bytes of a `Vec<u8>`.
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+impl<S> Clone for Sequence<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Sequence<'_, S> {}
+
+impl<'a, S: View> fmt::Debug for Sequence<'a, S>
+    where S::Borrowed<'a>: fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, S: View> PartialEq for Sequence<'a, S>
+    where S::Borrowed<'a>: PartialEq
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<'a, S: View> IntoIterator for Sequence<'a, S> {
+    type Item = S::Borrowed<'a>;
+    type IntoIter = SequenceIter<'a, S>;
+
+    fn into_iter(self) -> SequenceIter<'a, S> {
+        self.iter()
+    }
+}
+
+/// Iterates over the element views of a `Sequence`.
+pub struct SequenceIter<'a, S> {
+    remaining: usize,
+    bytes: &'a [u8],
+    element: PhantomData<fn() -> S>
+}
+
+impl<'a, S: View> Iterator for SequenceIter<'a, S> {
+    type Item = S::Borrowed<'a>;
+
+    fn next(&mut self) -> Option<S::Borrowed<'a>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(S::view_from(&mut self.bytes).expect("Elements are checked when viewing the sequence"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, S: View> ExactSizeIterator for SequenceIter<'a, S> {}
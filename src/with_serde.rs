@@ -0,0 +1,613 @@
+//! Bridges between `Bytes` and serde, available with the `serde` feature.
+
+use super::{Bytes, Error};
+use super::bytes::{serialize_varint, deserialize_varint, deserialize_count, take, capacity};
+use ::serde::{ser, de};
+use ::serde::ser::Serialize;
+use ::serde::de::{DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::new(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::new(msg.to_string())
+    }
+}
+
+/// Stores any type implementing serde's `Serialize` and `Deserialize` through `Bytes`.
+///
+/// Values are encoded like the `Bytes` implementations of the corresponding types:
+/// integers and floats in fixed width little endian, `bool` as one byte, `char` as `u32`,
+/// strings and byte arrays as their varint length followed by their bytes, options as a
+/// `bool` flag followed by the value, sequences and maps as their length as `u64` followed
+/// by their elements, and enum variants as their index as `u32` followed by their fields.
+/// Structs and tuples are stored as their fields in order, without field names, and map
+/// entries are sorted by their encoding so hash maps produce identical bytes.
+///
+/// The signature is made of the type name only, since serde doesn't describe the fields
+/// of a type up front. Changing the fields doesn't change the schema hash, so old records
+/// have to be migrated by hand, for example by wrapping the new type in a new name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Serde<T>(pub T);
+
+impl<T> Serde<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Serde<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Serde<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for Serde<T> {
+    fn from(value: T) -> Serde<T> {
+        Serde(value)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Bytes for Serde<T> {
+    fn serialize_into(&self, bytes: &mut Vec<u8>) {
+        // The encoder only fails if the `Serialize` implementation reports an error itself.
+        if let Err(error) = self.0.serialize(&mut Encoder { bytes }) {
+            panic!("Failed to serialize {}: {}", std::any::type_name::<T>(), error);
+        }
+    }
+
+    fn deserialize_from(bytes: &mut &[u8]) -> Result<Serde<T>, Error> {
+        let mut decoder = Decoder { input: bytes };
+        let value = T::deserialize(&mut decoder)?;
+        *bytes = decoder.input;
+        Ok(Serde(value))
+    }
+
+    fn signature() -> String {
+        format!("Serde<{}>", std::any::type_name::<T>())
+    }
+}
+
+impl<T: Serialize> Serialize for Serde<T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: de::Deserialize<'de>> de::Deserialize<'de> for Serde<T> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Serde<T>, D::Error> {
+        T::deserialize(deserializer).map(Serde)
+    }
+}
+
+/// Serializes a `Bytes` type as a byte array in any serde format,
+/// to be used with `#[serde(with = "database::as_bytes")]`.
+pub mod as_bytes {
+    use super::super::Bytes;
+    use ::serde::{Serializer, Deserializer, de};
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    pub fn serialize<T: Bytes, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&value.serialize())
+    }
+
+    pub fn deserialize<'de, T: Bytes, D: Deserializer<'de>>(deserializer: D) -> Result<T, D::Error> {
+        deserializer.deserialize_byte_buf(BytesVisitor(PhantomData))
+    }
+
+    struct BytesVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Bytes> de::Visitor<'de> for BytesVisitor<T> {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "the bytes of {}", std::any::type_name::<T>())
+        }
+
+        fn visit_bytes<E: de::Error>(self, mut bytes: &[u8]) -> Result<T, E> {
+            let value = T::deserialize_from(&mut bytes).map_err(E::custom)?;
+            if !bytes.is_empty() {
+                return Err(E::custom(format!("{} trailing bytes", bytes.len())));
+            }
+            Ok(value)
+        }
+
+        // Formats without a byte array type, like JSON, store them as a sequence of numbers.
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+            let mut bytes = Vec::new();
+            while let Some(byte) = seq.next_element::<u8>()? {
+                bytes.push(byte);
+            }
+            self.visit_bytes(&bytes)
+        }
+    }
+}
+
+/// Serializes into the format described at `Serde`.
+struct Encoder<'a> {
+    bytes: &'a mut Vec<u8>
+}
+
+/// Writes the elements of a sequence or map. The length is written up front as a
+/// placeholder and filled in at the end, since serde doesn't always know it in advance.
+struct Compound<'a, 'b> {
+    encoder: &'b mut Encoder<'a>,
+    length_at: usize,
+    count: u64,
+    // Start of every map entry, so entries can be sorted at the end.
+    entries: Option<Vec<usize>>
+}
+
+impl<'a, 'b> Compound<'a, 'b> {
+    fn new(encoder: &'b mut Encoder<'a>, map: bool) -> Compound<'a, 'b> {
+        let length_at = encoder.bytes.len();
+        0u64.serialize_into(encoder.bytes);
+        Compound {
+            encoder,
+            length_at,
+            count: 0,
+            entries: if map { Some(Vec::new()) } else { None }
+        }
+    }
+
+    fn finish(self) {
+        let bytes = &mut *self.encoder.bytes;
+        bytes[self.length_at..self.length_at + 8].copy_from_slice(&self.count.to_le_bytes());
+        if let Some(mut starts) = self.entries {
+            let end = bytes.len();
+            let first = starts.first().copied().unwrap_or(end);
+            starts.push(end);
+            let mut ranges = starts.windows(2).map(|window| window[0]..window[1]).collect::<Vec<_>>();
+            ranges.sort_unstable_by(|a, b| bytes[a.clone()].cmp(&bytes[b.clone()]));
+            let mut sorted = Vec::with_capacity(end - first);
+            for range in ranges {
+                sorted.extend_from_slice(&bytes[range]);
+            }
+            bytes.truncate(first);
+            bytes.append(&mut sorted);
+        }
+    }
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Encoder<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, 'b>;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Compound<'a, 'b>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        value.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.serialize_bytes(value.as_bytes())
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        serialize_varint(value.len() as u128, self.bytes);
+        self.bytes.extend_from_slice(value);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        false.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        true.serialize_into(self.bytes);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, index: u32, _: &'static str) -> Result<(), Error> {
+        index.serialize_into(self.bytes);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _: &'static str, index: u32, _: &'static str, value: &T) -> Result<(), Error> {
+        index.serialize_into(self.bytes);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Compound<'a, 'b>, Error> {
+        Ok(Compound::new(self, false))
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(self, _: &'static str, index: u32, _: &'static str, _: usize) -> Result<Self, Error> {
+        index.serialize_into(self.bytes);
+        Ok(self)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Compound<'a, 'b>, Error> {
+        Ok(Compound::new(self, true))
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(self, _: &'static str, index: u32, _: &'static str, _: usize) -> Result<Self, Error> {
+        index.serialize_into(self.bytes);
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl<'a, 'b> ser::SerializeSeq for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.count += 1;
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl<'a, 'b> ser::SerializeMap for Compound<'a, 'b> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.count += 1;
+        if let Some(entries) = &mut self.entries {
+            entries.push(self.encoder.bytes.len());
+        }
+        key.serialize(&mut *self.encoder)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.encoder)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+macro_rules! impl_fields_for_Encoder {
+    ($($t:ident :: $method:ident ($($name:ty)?)),+) => {
+        $(
+            impl<'a, 'b> ser::$t for &'b mut Encoder<'a> {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T: ?Sized + Serialize>(&mut self, $(_: $name,)? value: &T) -> Result<(), Error> {
+                    value.serialize(&mut **self)
+                }
+
+                fn end(self) -> Result<(), Error> {
+                    Ok(())
+                }
+            }
+        )*
+    }
+}
+
+impl_fields_for_Encoder!(
+    SerializeTuple::serialize_element(),
+    SerializeTupleStruct::serialize_field(),
+    SerializeTupleVariant::serialize_field(),
+    SerializeStruct::serialize_field(&'static str),
+    SerializeStructVariant::serialize_field(&'static str)
+);
+
+/// Deserializes from the format described at `Serde`.
+/// The format isn't self describing, so `deserialize_any` isn't supported.
+struct Decoder<'de> {
+    input: &'de [u8]
+}
+
+impl<'de> Decoder<'de> {
+    fn read<T: Bytes>(&mut self) -> Result<T, Error> {
+        T::deserialize_from(&mut self.input)
+    }
+
+    fn read_bytes(&mut self) -> Result<&'de [u8], Error> {
+        let len = deserialize_varint(&mut self.input)?;
+        if len > self.input.len() as u128 {
+            return Err(Error::new(format!("Byte array of length {} exceeds the remaining {} bytes.", len, self.input.len())));
+        }
+        take(&mut self.input, len as usize)
+    }
+
+    fn read_str(&mut self) -> Result<&'de str, Error> {
+        std::str::from_utf8(self.read_bytes()?).map_err(|error| Error::new(format!("Invalid UTF-8 in string: {}", error)))
+    }
+}
+
+macro_rules! impl_primitives_for_Decoder {
+    ($($method:ident => $visit:ident: $t:ty),+) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.read::<$t>()?)
+            }
+        )*
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'de> {
+    type Error = Error;
+
+    impl_primitives_for_Decoder!(
+        deserialize_bool => visit_bool: bool,
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+        deserialize_char => visit_char: char
+    );
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+        Err(Error::new(String::from("The serde bridge isn't self describing, `deserialize_any` isn't supported.")))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.read_str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.read_bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.read::<bool>()? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = deserialize_count(&mut self.input)?;
+        visitor.visit_seq(Elements { decoder: self, remaining: len })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_seq(Elements { decoder: self, remaining: len as u64 })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _: &'static str, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = deserialize_count(&mut self.input)?;
+        visitor.visit_map(Elements { decoder: self, remaining: len })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, _: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _: &'static str, _: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+        Err(Error::new(String::from("The serde bridge doesn't store identifiers.")))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Error> {
+        Err(Error::new(String::from("The serde bridge isn't self describing, values can't be skipped.")))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Hands out the elements of a sequence or the entries of a map.
+struct Elements<'a, 'de> {
+    decoder: &'a mut Decoder<'de>,
+    remaining: u64
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for Elements<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(capacity(self.remaining, self.decoder.input))
+    }
+}
+
+impl<'de, 'a> de::MapAccess<'de> for Elements<'a, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(capacity(self.remaining, self.decoder.input))
+    }
+}
+
+impl<'de> de::EnumAccess<'de> for &mut Decoder<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let index = self.read::<u32>()?;
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Decoder<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
@@ -1,14 +1,25 @@
 extern crate proc_macro;
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn;
-use syn::{Data, Fields, Type, Meta, NestedMeta};
+use quote::{quote, ToTokens};
+use syn::{Data, Fields, Meta, NestedMeta, Lit, Attribute, Path, Member, Index, GenericParam, Field, Error};
 
-#[proc_macro_derive(Store, attributes(id, rename))]
+/// Derives `Store` for a struct or an enum.
+///
+/// The field marked with `#[id]` is used as id, every variant of an enum needs one.
+/// The collection name is derived from the type name, or from `#[rename(Name)]` if present.
+///
+/// `Store::schema` is generated from the declaration, listing every field with its type and
+/// its attributes among `#[id]`, `#[default]`, `#[skip]` and `#[varint]`.
+///
+/// The generated code refers to the `database` crate by `::database`.
+/// Use `#[database(crate = "path::to::database")]` if it is available under another path.
+#[proc_macro_derive(Store, attributes(id, rename, database))]
 pub fn store_derive(input: TokenStream) -> TokenStream {
-    let ast = syn::parse(input).unwrap();
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
     impl_store(&ast)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
 }
 
 /*
@@ -26,127 +37,216 @@ fn contains_name(nested: &Punctuated<NestedMeta, Comma>, name: &str) -> bool {
 }
 */
 
-fn impl_store(ast: &syn::DeriveInput) -> TokenStream {
-    let struct_name = &ast.ident;
-
-    let nested = 
-        if let Some(Meta::List(meta_list)) = 
-            if let Some(some) = &ast.attrs
-                .clone()
-                .into_iter()
-                .filter(|attr| attr.path.is_ident("rename"))
-                .nth(0)
-            {
-                Some(some
-                    .parse_meta()
-                    .unwrap())
-            } else {
-                None
+/// Returns the path of the `database` crate, `::database` unless overridden by
+/// `#[database(crate = "...")]`.
+fn crate_path(attrs: &[Attribute]) -> syn::Result<Path> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("database")) {
+        Some(attr) => attr,
+        None => return Ok(syn::parse_quote!(::database))
+    };
+    if let Meta::List(meta_list) = attr.parse_meta()? {
+        if let (1, Some(NestedMeta::Meta(Meta::NameValue(name_value)))) = (meta_list.nested.len(), meta_list.nested.first()) {
+            if name_value.path.is_ident("crate") {
+                return match &name_value.lit {
+                    Lit::Str(path) => path.parse(),
+                    lit => Err(Error::new_spanned(lit, "expected a string containing a path"))
+                };
             }
-        { Some(meta_list.nested) } else { None };
-
-    /*
-    let from = Vec::new();
-
-    for nested_meta in nested {
-        if let NestedMeta::Meta(meta) = nested_meta {
-            if let Meta::Path(path) = meta {
-                from.push(path);
-            } 
         }
     }
-    */
+    Err(Error::new_spanned(attr, "expected `#[database(crate = \"...\")]`"))
+}
 
-    let new_name = if let Some(nested) = nested {
-        let mut from = Vec::new();
+/// Returns the position and the field marked with `#[id]`, if there is exactly one.
+fn id_field<'a, I>(fields: I) -> syn::Result<Option<(usize, &'a Field)>>
+    where I: IntoIterator<Item = &'a Field>
+{
+    let mut id_field = None;
+    for (index, field) in fields.into_iter().enumerate() {
+        if let Some(attr) = field.attrs.iter().find(|attr| attr.path.is_ident("id")) {
+            if !attr.tokens.is_empty() {
+                return Err(Error::new_spanned(attr, "`#[id]` doesn't take any arguments"));
+            }
+            if id_field.is_some() {
+                return Err(Error::new_spanned(attr, "only one field can be marked with `#[id]`"));
+            }
+            id_field = Some((index, field));
+        }
+    }
+    Ok(id_field)
+}
 
-        for nested_meta in nested {
-            if let NestedMeta::Meta(meta) = nested_meta {
-                if let Meta::Path(path) = meta {
-                    from.push(path);
-                } 
+/// Returns the name given by `#[rename(Name)]`, if present.
+fn rename(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    let attr = match attrs.iter().find(|attr| attr.path.is_ident("rename")) {
+        Some(attr) => attr,
+        None => return Ok(None)
+    };
+    if let Meta::List(meta_list) = attr.parse_meta()? {
+        if meta_list.nested.len() == 1 {
+            if let Some(NestedMeta::Meta(Meta::Path(path))) = meta_list.nested.first() {
+                if let Some(ident) = path.get_ident() {
+                    return Ok(Some(ident.to_string()));
+                }
             }
         }
+    }
+    Err(Error::new_spanned(attr, "expected `#[rename(Name)]`"))
+}
 
-        if let Some(new_name) = from.into_iter().nth(0) {
-            Some(new_name.get_ident().unwrap().to_string())
-        } else {
-            None
+/// Returns the tokens as they would be written, without the spaces `quote` puts between all of them.
+fn tokens_string<T: ToTokens>(tokens: &T) -> String {
+    let spaced = tokens.to_token_stream().to_string();
+    let chars = spaced.chars().collect::<Vec<char>>();
+    let is_word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    let mut output = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == ' ' {
+            let (before, after) = (if i > 0 { chars.get(i - 1) } else { None }, chars.get(i + 1));
+            let separates = (is_word(before) && is_word(after))
+                || before == Some(&',')
+                || before == Some(&'=')
+                || after == Some(&'=');
+            if !separates {
+                continue;
+            }
         }
-    } else {
-        None
-    };
-    
+        output.push(c);
+    }
+    output
+}
+
+/// Returns the expressions building the schema of every field, with the attributes the derives know.
+fn field_schemas(fields: &Fields, type_varint: bool, krate: &Path) -> Vec<proc_macro2::TokenStream> {
+    fields.iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let name = match &field.ident {
+                Some(ident) => ident.to_string(),
+                None => index.to_string()
+            };
+            let ty = tokens_string(&field.ty);
+            let mut attributes = field.attrs.iter()
+                .filter(|attr| ["id", "default", "skip", "varint"].iter().any(|name| attr.path.is_ident(name)))
+                .map(|attr| {
+                    let name = tokens_string(&attr.path);
+                    match tokens_string(&attr.tokens) {
+                        tokens if tokens.is_empty() => name,
+                        tokens if tokens.starts_with('=') => format!("{} {}", name, tokens),
+                        tokens => format!("{}{}", name, tokens)
+                    }
+                })
+                .collect::<Vec<String>>();
+            let stored = !field.attrs.iter().any(|attr| attr.path.is_ident("skip"));
+            if type_varint && stored && !attributes.iter().any(|attribute| attribute == "varint") {
+                attributes.push(String::from("varint"));
+            }
+            quote! {
+                #krate::Field {
+                    name: ::std::string::String::from(#name),
+                    ty: ::std::string::String::from(#ty),
+                    attributes: ::std::vec![#(::std::string::String::from(#attributes)),*]
+                }
+            }
+        })
+        .collect()
+}
 
-    let (id_name, id_type) = match *&ast.data {
+fn impl_store(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &ast.ident;
+
+    let krate = crate_path(&ast.attrs)?;
+    let new_name = rename(&ast.attrs)?;
+    let type_varint = ast.attrs.iter().any(|attr| attr.path.is_ident("varint"));
+
+    let (id_access, id_type, id_schema, fields_schema, variants_schema) = match ast.data {
         Data::Struct(ref data) => {
-            match data.fields {
-                Fields::Named(ref fields) => {
-                    let mut id_name = Option::None;
-                    let mut id_type = Option::None;
-                    for field in &fields.named {
-                        if let Some(_) = field.attrs
-                            .clone()
-                            .into_iter()
-                            .filter(|attr| attr.path.is_ident("id"))
-                            .nth(0)
-                        {
-                            if let Some(field_name) = &field.ident {
-                                if let Type::Path(tp) =  &field.ty {
-                                    id_name = Some(field_name);
-                                    id_type = Some(&tp.path);
-                                }
-                            }
-                        }
-                        /*
-                        let nested = 
-                            if let Meta::List(meta_list) = 
-                                if let Some(some) = field.attrs
-                                    .clone()
-                                    .into_iter()
-                                    .filter(|attr| attr.path.is_ident("store"))
-                                    .nth(0)
-                                {
-                                    some
-                                        .parse_meta()
-                                        .unwrap()
-                                } else {
-                                    continue;
-                                }
-                            { meta_list.nested } else { panic!() };
-
-                        if let Some(field_name) = &field.ident {
-                            if let Type::Path(tp) =  &field.ty {
-                                if contains_name(&nested, "id") {
-                                    id_name = Some(field_name);
-                                    id_type = Some(&tp.path);
-                                }
-                            }
+            let (index, field) = id_field(&data.fields)?.ok_or_else(|| Error::new_spanned(
+                struct_name,
+                "storable structs require a field marked with `#[id]`"
+            ))?;
+            let id_name = match &field.ident {
+                Some(field_name) => Member::Named(field_name.clone()),
+                None => Member::Unnamed(Index::from(index))
+            };
+            let id_type = &field.ty;
+            let fields = field_schemas(&data.fields, type_varint, &krate);
+            (
+                quote! { &self.#id_name },
+                quote! { #id_type },
+                Some(tokens_string(&id_name)),
+                quote! { ::std::vec![#(#fields),*] },
+                quote! { ::std::vec::Vec::new() }
+            )
+        },
+        Data::Enum(ref data) => {
+            // Every variant has to carry an id of the same type.
+            let mut arms = Vec::new();
+            let mut id_type: Option<&syn::Type> = Option::None;
+            let mut id_names = Vec::new();
+            let mut variants = Vec::new();
+            for variant in &data.variants {
+                let variant_name = &variant.ident;
+                let (position, field) = id_field(&variant.fields)?.ok_or_else(|| Error::new_spanned(
+                    variant_name,
+                    "every variant of a storable enum requires a field marked with `#[id]`"
+                ))?;
+                let field_type = &field.ty;
+                if let Some(id_type) = id_type {
+                    if quote!(#id_type).to_string() != quote!(#field_type).to_string() {
+                        return Err(Error::new_spanned(field_type, "the ids of all variants have to be of the same type"));
+                    }
+                }
+                id_type = Some(field_type);
+                id_names.push(match &field.ident {
+                    Some(field_name) => field_name.to_string(),
+                    None => position.to_string()
+                });
+                let variant_string = variant_name.to_string();
+                let fields = field_schemas(&variant.fields, type_varint, &krate);
+                variants.push(quote! {
+                    #krate::Variant {
+                        name: ::std::string::String::from(#variant_string),
+                        fields: ::std::vec![#(#fields),*]
+                    }
+                });
+                arms.push(match &field.ident {
+                    Some(field_name) => quote! {
+                        Self::#variant_name { #field_name, .. } => #field_name
+                    },
+                    None => {
+                        let skipped = (0..position).map(|_| quote! { _ });
+                        quote! {
+                            Self::#variant_name(#(#skipped,)* id, ..) => id
                         }
-                        */
                     }
-                    (id_name, id_type)
-                },
-                Fields::Unnamed(_) => unimplemented!(),
-                Fields::Unit => unimplemented!()
+                });
             }
+            let id_type = id_type.ok_or_else(|| Error::new_spanned(
+                struct_name,
+                "storable enums require at least one variant"
+            ))?;
+            // The id is only named if every variant names it alike.
+            let id_name = Some(id_names[0].clone()).filter(|name| id_names.iter().all(|other| other == name));
+            (
+                quote! { match self { #(#arms),* } },
+                quote! { #id_type },
+                id_name,
+                quote! { ::std::vec::Vec::new() },
+                quote! { ::std::vec![#(#variants),*] }
+            )
         },
-        Data::Enum(_) | Data::Union(_) => unimplemented!()
+        Data::Union(ref data) => return Err(Error::new_spanned(
+            data.union_token,
+            "`Store` can't be derived for unions"
+        ))
     };
 
-    if id_name == Option::None {
-        panic!("Storable structs without id are not allowed");
-    }
-
-    let name = format!("{}", struct_name);
-    if name.len() > 128 {
-        panic!("Name exceeds maximum length.");
-    }
-    let name = format!("{}s", 
-        if let Some(new_name) = new_name { new_name } else { name }
+    let name = format!("{}s",
+        new_name.unwrap_or_else(|| struct_name.to_string())
             .chars()
             .enumerate()
-            .map(|(i, c)| {
+            .flat_map(|(i, c)| {
                 let mut output = Vec::new();
                 if i > 0 && c.is_ascii_uppercase() {
                     output.push('-');
@@ -154,20 +254,49 @@ fn impl_store(ast: &syn::DeriveInput) -> TokenStream {
                 output.push(c.to_ascii_lowercase());
                 output
             })
-            .flatten()
             .collect::<String>()
     );
+    if name.len() > 128 {
+        return Err(Error::new_spanned(struct_name, "collection name exceeds maximum length of 128 characters"));
+    }
+
+    // Stored types are shared between threads, so every type parameter has to be too.
+    let mut generics = ast.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            type_param.bounds.push(syn::parse_quote!(#krate::Bytes));
+            type_param.bounds.push(syn::parse_quote!(::std::marker::Send));
+            type_param.bounds.push(syn::parse_quote!(::std::marker::Sync));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let type_string = struct_name.to_string();
+    let id_schema = match id_schema {
+        Some(id_name) => quote! { ::std::option::Option::Some(::std::string::String::from(#id_name)) },
+        None => quote! { ::std::option::Option::None }
+    };
 
     let gen = quote! {
-        impl Store for #struct_name {
+        impl #impl_generics #krate::Store for #struct_name #ty_generics #where_clause {
             type Id = #id_type;
 
             const NAME: &'static str = #name;
-            
+
             fn id(&self) -> &#id_type {
-                &self.#id_name
+                #id_access
+            }
+
+            fn schema() -> #krate::Schema {
+                #krate::Schema {
+                    name: ::std::string::String::from(#type_string),
+                    collection: ::std::string::String::from(<Self as #krate::Store>::NAME),
+                    id: #id_schema,
+                    hash: <Self as #krate::Bytes>::hash(),
+                    fields: #fields_schema,
+                    variants: #variants_schema
+                }
             }
         }
     };
-    gen.into()
+    Ok(gen)
 }
@@ -0,0 +1,249 @@
+use database::{Database, Bytes, Store, Cli};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+#[derive(Bytes, Store, PartialEq, Debug)]
+struct Person {
+    #[id] name: String,
+    age: u16,
+}
+
+/// A database directory in the temporary directory, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("database-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        TempDir(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_database")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn check() {
+    let dir = TempDir::new("check");
+    let path = dir.path().to_str().unwrap();
+    let database = Database::new(path);
+    database.create(&Person { name: String::from("Peter"), age: 25 }).unwrap();
+
+    let output = run(&["check", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("1 entries checked, 0 problems found"));
+
+    std::fs::write(dir.path().join("persons").join("00.tmp"), []).unwrap();
+    let output = run(&["check", path]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("00.tmp: temporary file"));
+
+    let output = run(&["check", path, "--quarantine"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("(quarantined)"));
+    assert!(dir.path().join(".quarantine/persons/00.tmp").exists());
+    assert_eq!(run(&["check", path]).status.code(), Some(0));
+
+    assert_eq!(run(&["check"]).status.code(), Some(2));
+    assert_eq!(run(&["check", path, "--quarantine", "--delete"]).status.code(), Some(2));
+    assert_eq!(run(&["unknown"]).status.code(), Some(2));
+    assert_eq!(run(&["check", dir.path().join("missing").to_str().unwrap()]).status.code(), Some(1));
+
+    // Directories that don't hold a database aren't turned into one.
+    let documents = TempDir::new("documents");
+    std::fs::create_dir_all(documents.path()).unwrap();
+    std::fs::write(documents.path().join("notes.txt"), "notes").unwrap();
+    assert_eq!(run(&["check", documents.path().to_str().unwrap(), "--delete"]).status.code(), Some(1));
+    assert!(!documents.path().join(".manifest").exists());
+}
+
+#[test]
+fn inspect() {
+    let dir = TempDir::new("inspect");
+    let path = dir.path().to_str().unwrap();
+    let database = Database::new(path);
+    database.create(&Person { name: String::from("Peter"), age: 25 }).unwrap();
+    database.create(&Person { name: String::from("Anna"), age: 31 }).unwrap();
+    std::fs::create_dir_all(dir.path().join(".quarantine")).unwrap();
+    // Files that aren't entries aren't counted or listed.
+    std::fs::write(dir.path().join("persons").join("notes.txt"), "notes").unwrap();
+
+    let output = run(&["collections", path]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "persons: 2 entries\n");
+
+    let output = run(&["count", path, "persons"]);
+    assert_eq!(stdout(&output), "2\n");
+    assert_eq!(run(&["count", path, "cars"]).status.code(), Some(1));
+
+    let output = run(&["list", path, "persons"]);
+    // Inspecting a database written before manifests were added doesn't change it.
+    assert!(!dir.path().join(".manifest").exists());
+    let list = stdout(&output);
+    let hash = format!("schema {:016x}", Person::hash());
+    assert_eq!(list.lines().count(), 2);
+    assert!(list.lines().all(|line| line.ends_with(&hash)));
+    assert!(list.contains("\"Peter\""));
+    assert!(list.contains("\"Anna\""));
+
+    let name = list.lines().find(|line| line.contains("Peter")).unwrap().split(' ').next().unwrap().to_owned();
+    let output = run(&["show", path, "persons", &name]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "{\"name\":\"Peter\",\"age\":25}\n");
+    // Without the catalog, the entry is shown as bytes.
+    std::fs::rename(dir.path().join(".catalog"), dir.path().join("catalog.bak")).unwrap();
+    assert!(stdout(&run(&["show", path, "persons", &name])).starts_with("00000000  "));
+    std::fs::rename(dir.path().join("catalog.bak"), dir.path().join(".catalog")).unwrap();
+
+    assert_eq!(stdout(&run(&["schema", path, "persons"])), format!("{}\n", Person::schema()));
+
+    let output = run(&["delete", path, "persons", &name]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(dir.path().join(".manifest").exists());
+    assert!(database.read::<Person>(&String::from("Peter")).is_err());
+    assert_eq!(run(&["delete", path, "persons", &name]).status.code(), Some(1));
+    assert_eq!(stdout(&run(&["count", path, "persons"])), "1\n");
+    assert_eq!(stdout(&run(&["query", path, "count persons"])), "1\n");
+    assert_eq!(run(&["query", path, "select persons"]).status.code(), Some(1));
+
+    assert_eq!(run(&["show", path, "persons"]).status.code(), Some(2));
+    assert_eq!(run(&["delete", path, "persons"]).status.code(), Some(2));
+
+    // Names leading outside the collection aren't entries.
+    std::fs::write(dir.path().join("notes"), "notes").unwrap();
+    assert_eq!(run(&["show", path, "persons", "../notes"]).status.code(), Some(1));
+    assert_eq!(run(&["delete", path, "persons", "../notes"]).status.code(), Some(1));
+    assert_eq!(run(&["delete", path, "..", "notes"]).status.code(), Some(1));
+    assert_eq!(run(&["delete", path, ".", ".catalog"]).status.code(), Some(1));
+    assert!(dir.path().join("notes").exists() && dir.path().join(".catalog").exists());
+}
+
+#[test]
+fn registered() {
+    let dir = TempDir::new("registered");
+    let path = dir.path().to_str().unwrap();
+    let database = Database::new(path);
+    database.create(&Person { name: String::from("Peter"), age: 25 }).unwrap();
+
+    let cli = Cli::new().register::<Person>();
+    let run = |args: &[&str]| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = cli.run_with(args.iter().map(|arg| arg.to_string()), &mut &b""[..], &mut out, &mut err);
+        (code, String::from_utf8(out).unwrap())
+    };
+
+    let (code, list) = run(&["list", path, "persons"]);
+    assert_eq!(code, 0);
+    assert!(list.contains("\"Peter\""));
+    assert!(list.trim_end().ends_with("(current)"));
+
+    let name = list.split(' ').next().unwrap();
+    let (code, value) = run(&["show", path, "persons", name]);
+    assert_eq!(code, 0);
+    assert_eq!(value, format!("{:#?}\n", Person { name: String::from("Peter"), age: 25 }));
+
+    let (code, schema) = run(&["schema", path, "persons"]);
+    assert_eq!(code, 0);
+    assert_eq!(schema, format!("{}\n", Person::schema()));
+    assert!(schema.contains("#[id] name: String,"));
+    assert_eq!(run(&["schema", path, "animals"]).0, 1);
+
+    // Entries of registered types are decoded when checking, and counted once.
+    std::fs::copy(dir.path().join("persons").join(name), dir.path().join("persons").join("00")).unwrap();
+    assert_eq!(self::run(&["check", path]).status.code(), Some(0));
+    let (code, report) = run(&["check", path]);
+    assert_eq!(code, 1);
+    assert!(report.contains("00: name doesn't match the id"));
+    assert!(report.ends_with("2 entries checked, 1 problems found\n"));
+}
+
+#[test]
+fn queries() {
+    let dir = TempDir::new("queries");
+    let path = dir.path().to_str().unwrap();
+    let database = Database::new(path);
+    for (name, age) in &[("Peter", 25), ("Anna", 31), ("Josef", 45)] {
+        database.create(&Person { name: String::from(*name), age: *age }).unwrap();
+    }
+
+    let cli = Cli::new().register::<Person>();
+    let run = |args: &[&str], input: &str| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = cli.run_with(args.iter().map(|arg| arg.to_string()), &mut input.as_bytes(), &mut out, &mut err);
+        (code, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+    };
+
+    let (code, out, _) = run(&["query", path, "select persons where age > 30 order by age desc limit 10"], "");
+    assert_eq!(code, 0);
+    assert!(out.find("Josef").unwrap() < out.find("Anna").unwrap());
+    assert!(!out.contains("Peter"));
+    assert!(out.ends_with("2 records\n"));
+    let (_, out, _) = run(&["query", path, "count", "persons", "where", "age", "<=", "31"], "");
+    assert_eq!(out, "2\n");
+    let (_, out, _) = run(&["query", path, "get persons 'Anna'"], "");
+    assert_eq!(out, format!("{:#?}\n", Person { name: String::from("Anna"), age: 31 }));
+    let (code, _, err) = run(&["query", path, "get persons \"Emil\""], "");
+    assert_eq!(code, 1);
+    assert!(err.contains("No entry"));
+    let (code, _, err) = run(&["query", path, "select persons where age >"], "");
+    assert_eq!(code, 1);
+    assert!(err.contains("Expected a value"));
+
+    let input = "count persons\nselect cars\ndelete persons \"Peter\"\nexit\ncount persons\n";
+    let (code, out, err) = run(&["shell", path], input);
+    assert_eq!(code, 0);
+    assert_eq!(out, "> 3\n> > Deleted 1 entries\n> ");
+    assert!(err.contains("No type is registered for collection \"cars\""));
+    assert_eq!(database.read_all::<Person>().unwrap().len(), 2);
+}
+
+#[test]
+fn exports() {
+    let dir = TempDir::new("exports");
+    let path = dir.path().to_str().unwrap();
+    let database = Database::new(path);
+    database.create(&Person { name: String::from("Peter"), age: 25 }).unwrap();
+    database.create(&Person { name: String::from("Anna, Maria"), age: 31 }).unwrap();
+
+    let cli = Cli::new().register::<Person>();
+    let run = |args: &[&str], input: &str| {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = cli.run_with(args.iter().map(|arg| arg.to_string()), &mut input.as_bytes(), &mut out, &mut err);
+        (code, String::from_utf8(out).unwrap())
+    };
+
+    let (code, json) = run(&["export", path, "persons", "json"], "");
+    assert_eq!(code, 0);
+    assert_eq!(json, "[\n{\"name\":\"Peter\",\"age\":25},\n{\"name\":\"Anna, Maria\",\"age\":31}\n]\n");
+    let (_, csv) = run(&["export", path, "persons", "csv"], "");
+    assert_eq!(csv, "name,age\nPeter,25\n\"Anna, Maria\",31\n");
+
+    let file = dir.path().join("persons.csv");
+    let file = file.to_str().unwrap();
+    assert_eq!(run(&["export", path, "persons", "csv", file], ""), (0, String::from("Exported 2 entries\n")));
+    database.delete_all::<Person>().unwrap();
+    assert_eq!(run(&["import", path, "persons", "csv", file], ""), (0, String::from("Imported 2 entries\n")));
+    assert_eq!(run(&["import", path, "persons", "csv", file], "").0, 1);
+    assert_eq!(run(&["import", path, "persons", "json", "--skip"], &json), (0, String::from("Imported 0 entries\n")));
+    let older = "name,age\nPeter,26\n";
+    assert_eq!(run(&["import", path, "persons", "csv", "--overwrite"], older), (0, String::from("Imported 1 entries\n")));
+    assert_eq!(database.read::<Person>(&String::from("Peter")).unwrap().age, 26);
+
+    assert_eq!(run(&["export", path, "persons", "xml"], "").0, 2);
+    assert_eq!(run(&["import", path, "persons", "csv", "--skip", "--overwrite"], "").0, 2);
+}